@@ -0,0 +1,84 @@
+#!/usr/bin/env python3
+
+# Generates the placeholder network embedded in the engine when it's built with the 'nnue'
+# feature, until we have a trained network to ship instead.
+#
+# The network reproduces the classical material and piece-square table evaluation, averaged across
+# the midgame and endgame values, so that it plays sensibly out of the box. Each piece kind gets a
+# hidden neuron (two for pawns, so that they can't saturate the activation) which sums the values
+# of our pieces of that kind, and the output layer subtracts the opponent's sums from ours.
+#
+# usage: ./etc/nnue/generate_placeholder_net.py [params.rs] [output file]
+
+import re
+import struct
+import sys
+
+INPUT_SIZE = 768
+HIDDEN_SIZE = 128
+
+QA = 255
+QB = 64
+SCALE = 400
+
+# Each unit of a hidden neuron is worth this many centipawns
+UNIT = 5
+BIAS = 32
+
+PST_NAMES = ["PAWNS", "KNIGHTS", "BISHOPS", "ROOKS", "QUEENS", "KING"]
+
+
+def parse_phased_evals(text):
+    return [(int(mg), int(eg)) for mg, eg in re.findall(r"s\(\s*(-?\d+),\s*(-?\d+)\)", text)]
+
+
+def parse_const(params, name):
+    match = re.search(rf"pub const {name}: [^=]+= \[(.*?)\n\];", params, re.S)
+    return parse_phased_evals(match.group(1))
+
+
+def main():
+    params_path = sys.argv[1] if len(sys.argv) > 1 else "src/engine/eval/params.rs"
+    output_path = sys.argv[2] if len(sys.argv) > 2 else "src/engine/eval/nnue/default.nnue"
+
+    with open(params_path) as f:
+        params = f.read()
+
+    piece_values = parse_const(params, "PIECE_VALUES")
+
+    feature_weights = [[0] * HIDDEN_SIZE for _ in range(INPUT_SIZE)]
+
+    for kind, name in enumerate(PST_NAMES):
+        pst = parse_const(params, name)
+
+        for sq in range(64):
+            file, rank = sq % 8, sq // 8
+
+            # Tables are written with the 8th rank first
+            mg, eg = pst[(7 - rank) * 8 + file]
+            mg += piece_values[kind][0]
+            eg += piece_values[kind][1]
+
+            value = round((mg + eg) / 2 / UNIT)
+
+            # Neurons 0 and 1 are for pawns on the queenside and kingside, then one per piece kind
+            neuron = (0 if file < 4 else 1) if kind == 0 else kind + 1
+
+            # Features for our own pieces come first, followed by the opponent's
+            feature_weights[kind * 64 + sq][neuron] = value
+
+    feature_bias = [BIAS if i <= 6 else 0 for i in range(HIDDEN_SIZE)]
+
+    output_weight = round(UNIT * QA * QB / SCALE)
+    our_output_weights = [output_weight if i <= 6 else 0 for i in range(HIDDEN_SIZE)]
+    their_output_weights = [-w for w in our_output_weights]
+
+    values = [w for weights in feature_weights for w in weights]
+    values += feature_bias + our_output_weights + their_output_weights + [0]
+
+    with open(output_path, "wb") as f:
+        f.write(struct.pack(f"<{len(values)}h", *values))
+
+
+if __name__ == "__main__":
+    main()
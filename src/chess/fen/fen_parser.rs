@@ -1,11 +1,9 @@
-use std::collections::HashSet;
-
 use crate::chess::{
     board::Board,
-    game::{CastleRights, Game},
+    game::{CastleRights, CastleRightsSide, Game},
     piece::Piece,
     player::Player,
-    square::{File, Rank, Square},
+    square::{squares, File, Rank, Square},
 };
 
 use crate::chess::player::ByPlayer;
@@ -105,50 +103,94 @@ fn fen_color(input: &str) -> IResult<&str, Player> {
     ))(input)
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Clone, Copy)]
 enum FenCastleRight {
-    WhiteKingside,
-    WhiteQueenside,
-    BlackKingside,
-    BlackQueenside,
+    // 'KQkq': castle with the outermost rook on the given side, as in standard FEN and X-FEN
+    Kingside(Player),
+    Queenside(Player),
+
+    // Shredder-FEN (and X-FEN, when the outermost rook isn't the castling rook) gives the file of
+    // the castling rook instead
+    RookFile(Player, File),
 }
 
 fn fen_castle_right(input: &str) -> IResult<&str, FenCastleRight> {
-    let (input, piece) = one_of("KQkq")(input)?;
+    let (input, right) = one_of("KQkqABCDEFGHabcdefgh")(input)?;
 
     Ok((
         input,
-        match piece {
-            'K' => FenCastleRight::WhiteKingside,
-            'Q' => FenCastleRight::WhiteQueenside,
-            'k' => FenCastleRight::BlackKingside,
-            'q' => FenCastleRight::BlackQueenside,
+        match right {
+            'K' => FenCastleRight::Kingside(Player::White),
+            'Q' => FenCastleRight::Queenside(Player::White),
+            'k' => FenCastleRight::Kingside(Player::Black),
+            'q' => FenCastleRight::Queenside(Player::Black),
+            'A'..='H' => {
+                FenCastleRight::RookFile(Player::White, File::from_idx(right as u8 - b'A'))
+            }
+            'a'..='h' => {
+                FenCastleRight::RookFile(Player::Black, File::from_idx(right as u8 - b'a'))
+            }
             _ => unreachable!(),
         },
     ))
 }
 
-fn fen_castling(input: &str) -> IResult<&str, ByPlayer<CastleRights>> {
-    alt((
-        value(
-            ByPlayer::new(CastleRights::none(), CastleRights::none()),
-            tag("-"),
-        ),
-        map(many1(fen_castle_right), |rs| {
-            let rights: HashSet<FenCastleRight> = rs.iter().copied().collect();
-
-            ByPlayer::new(
-                CastleRights {
-                    king_side: rights.contains(&FenCastleRight::WhiteKingside),
-                    queen_side: rights.contains(&FenCastleRight::WhiteQueenside),
-                },
-                CastleRights {
-                    king_side: rights.contains(&FenCastleRight::BlackKingside),
-                    queen_side: rights.contains(&FenCastleRight::BlackQueenside),
-                },
-            )
-        }),
-    ))(input)
+fn fen_castling(input: &str) -> IResult<&str, Vec<FenCastleRight>> {
+    alt((value(Vec::new(), tag("-")), many1(fen_castle_right)))(input)
+}
+
+// Which rook a castle right refers to depends on where the king and rooks are, so we can only
+// work out the castle rights once we've parsed the board.
+fn castle_rights_for_board(board: &Board, rights: &[FenCastleRight]) -> ByPlayer<CastleRights> {
+    let mut castle_rights = ByPlayer::new(CastleRights::none(), CastleRights::none());
+
+    for right in rights {
+        let (FenCastleRight::Kingside(player)
+        | FenCastleRight::Queenside(player)
+        | FenCastleRight::RookFile(player, _)) = *right;
+
+        let back_rank = squares::back_rank(player);
+        let king = board.king(player);
+
+        if king.count() != 1 || king.single().rank() != back_rank {
+            continue;
+        }
+
+        let king_file = king.single().file();
+
+        let (side, rook_file) = match *right {
+            FenCastleRight::Kingside(_) => (
+                CastleRightsSide::Kingside,
+                super::outermost_rook_file(board, player, king_file, CastleRightsSide::Kingside),
+            ),
+            FenCastleRight::Queenside(_) => (
+                CastleRightsSide::Queenside,
+                super::outermost_rook_file(board, player, king_file, CastleRightsSide::Queenside),
+            ),
+            FenCastleRight::RookFile(_, file) => {
+                let has_rook = board
+                    .rooks(player)
+                    .contains(squares::castle_rook_start(player, file));
+
+                (
+                    if file > king_file {
+                        CastleRightsSide::Kingside
+                    } else {
+                        CastleRightsSide::Queenside
+                    },
+                    Some(file).filter(|_| has_rook),
+                )
+            }
+        };
+
+        let player_castle_rights = castle_rights.for_player_mut(player);
+        match side {
+            CastleRightsSide::Kingside => player_castle_rights.king_side = rook_file,
+            CastleRightsSide::Queenside => player_castle_rights.queen_side = rook_file,
+        }
+    }
+
+    castle_rights
 }
 
 fn fen_file(input: &str) -> IResult<&str, File> {
@@ -225,6 +267,7 @@ fn fen_parser(input: &str) -> IResult<&str, Game> {
     let fullmove_number = fullmove_number.unwrap_or(1);
 
     let plies = plies_from_fullmove_number(fullmove_number, player);
+    let castle_rights = castle_rights_for_board(&board, &castle_rights);
 
     Ok((
         input,
@@ -1,7 +1,8 @@
 use crate::chess::{
     board::Board,
-    game::Game,
+    game::{CastleRightsSide, Game},
     piece::Piece,
+    player::Player,
     square::{Square, FILES, RANKS},
 };
 
@@ -79,23 +80,44 @@ fn format_current_player(game: &Game) -> String {
     }
 }
 
+// Castle rights are written in X-FEN: 'KQkq' when castling with the outermost rook on that side
+// (which is always the case in standard chess), otherwise the file of the castling rook.
+fn format_castle_right(game: &Game, player: Player, side: CastleRightsSide) -> Option<String> {
+    let rook_file = game.castle_rights.for_player(player).rook_file(side)?;
+
+    let king_file = game.board.king(player).single().file();
+    let outermost_rook_file = super::outermost_rook_file(&game.board, player, king_file, side);
+
+    let castle_right = if outermost_rook_file == Some(rook_file) {
+        match side {
+            CastleRightsSide::Kingside => "K".to_string(),
+            CastleRightsSide::Queenside => "Q".to_string(),
+        }
+    } else {
+        rook_file.notation().to_uppercase()
+    };
+
+    Some(match player {
+        Player::White => castle_right,
+        Player::Black => castle_right.to_lowercase(),
+    })
+}
+
 fn format_castle_rights(game: &Game) -> String {
-    let [white_castle_rights, black_castle_rights] = game.castle_rights.inner();
-
-    match (
-        white_castle_rights.king_side,
-        white_castle_rights.queen_side,
-        black_castle_rights.king_side,
-        black_castle_rights.queen_side,
-    ) {
-        (false, false, false, false) => "-".to_string(),
-        (white_king, white_queen, black_king, black_queen) => format!(
-            "{}{}{}{}",
-            if white_king { "K" } else { "" },
-            if white_queen { "Q" } else { "" },
-            if black_king { "k" } else { "" },
-            if black_queen { "q" } else { "" }
-        ),
+    let castle_rights = [
+        format_castle_right(game, Player::White, CastleRightsSide::Kingside),
+        format_castle_right(game, Player::White, CastleRightsSide::Queenside),
+        format_castle_right(game, Player::Black, CastleRightsSide::Kingside),
+        format_castle_right(game, Player::Black, CastleRightsSide::Queenside),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<String>();
+
+    if castle_rights.is_empty() {
+        "-".to_string()
+    } else {
+        castle_rights
     }
 }
 
@@ -4,4 +4,32 @@ mod fen_writer;
 pub use fen_parser::parse;
 pub use fen_writer::write;
 
+use crate::chess::board::Board;
+use crate::chess::game::CastleRightsSide;
+use crate::chess::player::Player;
+use crate::chess::square::{squares, File};
+
 pub const START_POS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// In X-FEN, 'K' and 'Q' refer to the outermost rook on that side of the king
+fn outermost_rook_file(
+    board: &Board,
+    player: Player,
+    king_file: File,
+    side: CastleRightsSide,
+) -> Option<File> {
+    let rooks = board.rooks(player);
+    let has_rook_on = |file: &File| rooks.contains(squares::castle_rook_start(player, *file));
+
+    match side {
+        CastleRightsSide::Kingside => File::ALL
+            .into_iter()
+            .rev()
+            .take_while(|f| *f > king_file)
+            .find(has_rook_on),
+        CastleRightsSide::Queenside => File::ALL
+            .into_iter()
+            .take_while(|f| *f < king_file)
+            .find(has_rook_on),
+    }
+}
@@ -6,7 +6,7 @@ use crate::chess::square::squares;
 use crate::chess::zobrist::ZobristHash;
 use crate::chess::{
     board::Board, fen, movegen::generate_legal_moves, moves::Move, piece::PieceKind,
-    player::Player, square::Square, zobrist,
+    player::Player, square::File, square::Square, zobrist,
 };
 use crate::engine::eval::IncrementalEvalFields;
 
@@ -18,38 +18,55 @@ pub enum CastleRightsSide {
 
 impl CastleRightsSide {
     pub const N: usize = 2;
+    pub const ALL: [Self; Self::N] = [Self::Kingside, Self::Queenside];
 
     #[inline(always)]
     pub const fn array_idx(self) -> usize {
         self as usize
     }
+
+    // The king always ends up on the same square after castling, even in Chess960, so we can
+    // tell which side we castled to from the king's destination.
+    pub fn from_castle_dest(king_moved_to: Square) -> Self {
+        if king_moved_to.file() == File::G {
+            Self::Kingside
+        } else {
+            Self::Queenside
+        }
+    }
 }
 
+// We store the file of the rook we'd castle with rather than just whether we can castle, since
+// in Chess960 the rooks don't necessarily start in the corners.
 #[derive(Copy, Clone, Debug)]
 pub struct CastleRights {
-    pub king_side: bool,
-    pub queen_side: bool,
+    pub king_side: Option<File>,
+    pub queen_side: Option<File>,
 }
 
 impl CastleRights {
     pub const fn none() -> Self {
         Self {
-            king_side: false,
-            queen_side: false,
+            king_side: None,
+            queen_side: None,
         }
     }
 
-    pub fn can_castle_to_side(self, side: CastleRightsSide) -> bool {
+    pub const fn rook_file(self, side: CastleRightsSide) -> Option<File> {
         match side {
             CastleRightsSide::Kingside => self.king_side,
             CastleRightsSide::Queenside => self.queen_side,
         }
     }
 
+    pub const fn can_castle_to_side(self, side: CastleRightsSide) -> bool {
+        self.rook_file(side).is_some()
+    }
+
     pub fn remove_rights(&mut self, side: CastleRightsSide) {
         match side {
-            CastleRightsSide::Kingside => self.king_side = false,
-            CastleRightsSide::Queenside => self.queen_side = false,
+            CastleRightsSide::Kingside => self.king_side = None,
+            CastleRightsSide::Queenside => self.queen_side = None,
         }
     }
 }
@@ -57,8 +74,8 @@ impl CastleRights {
 impl Default for CastleRights {
     fn default() -> Self {
         Self {
-            king_side: true,
-            queen_side: true,
+            king_side: Some(File::H),
+            queen_side: Some(File::A),
         }
     }
 }
@@ -71,6 +88,7 @@ pub struct History {
     pub en_passant_target: Option<Square>,
     pub halfmove_clock: u32,
     pub zobrist: ZobristHash,
+    pub pawn_zobrist: ZobristHash,
     pub incremental_eval: IncrementalEvalFields,
 }
 
@@ -84,6 +102,7 @@ pub struct Game {
     pub plies: u32,
 
     pub zobrist: ZobristHash,
+    pub pawn_zobrist: ZobristHash,
     pub incremental_eval: IncrementalEvalFields,
     pub history: Vec<History>,
 }
@@ -112,11 +131,13 @@ impl Game {
             plies,
 
             zobrist: ZobristHash::uninit(),
+            pawn_zobrist: ZobristHash::uninit(),
             incremental_eval: incremental_eval_fields,
             history: Vec::new(),
         };
 
         game.zobrist = zobrist::hash(&game);
+        game.pawn_zobrist = zobrist::pawn_hash(&game);
         game
     }
 
@@ -185,6 +206,36 @@ impl Game {
         }
     }
 
+    // Unlike is_repeated_position, which the search uses to treat any repetition as a draw, this
+    // is only true on the third occurrence of the position
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .filter(|h| h.zobrist == self.zobrist)
+            .count()
+            >= 2
+    }
+
+    // Whether neither side can possibly mate, for ending games. Unlike
+    // is_stalemate_by_insufficient_material, positions which are only very likely to be drawn
+    // don't count: this is just kings with either a single knight or bishops on one colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        let knights = self.board.all_knights();
+        let bishops = self.board.all_bishops();
+
+        let minor_pieces_only = self.board.occupancy().count()
+            == self.board.all_kings().count() + knights.count() + bishops.count();
+
+        let light_bishops = (bishops & bitboards::LIGHT_SQUARES).count();
+        let bishops_on_one_colour = light_bishops == 0 || light_bishops == bishops.count();
+
+        minor_pieces_only
+            && ((knights.is_empty() && bishops_on_one_colour)
+                || (knights.count() == 1 && bishops.is_empty()))
+    }
+
     #[inline(always)]
     pub fn is_king_in_check(&self) -> bool {
         self.board.king_in_check(self.player)
@@ -194,6 +245,10 @@ impl Game {
         self.board.set_at(sq, piece);
         self.zobrist.toggle_piece_on_square(sq, piece);
         self.incremental_eval.set_at(sq, piece);
+
+        if piece.kind == PieceKind::Pawn {
+            self.pawn_zobrist.toggle_piece_on_square(sq, piece);
+        }
     }
 
     fn remove_at(&mut self, sq: Square) -> Piece {
@@ -201,6 +256,10 @@ impl Game {
         self.board.remove_at(sq);
         self.zobrist.toggle_piece_on_square(sq, removed_piece);
         self.incremental_eval.remove_at(sq, removed_piece);
+
+        if removed_piece.kind == PieceKind::Pawn {
+            self.pawn_zobrist.toggle_piece_on_square(sq, removed_piece);
+        }
         removed_piece
     }
 
@@ -218,6 +277,21 @@ impl Game {
             .toggle_castle_rights(player, castle_rights_side);
     }
 
+    /// The square of the rook that `player` would castle with on `side`, if they still have the
+    /// right to castle there.
+    pub fn castle_rook_square(&self, player: Player, side: CastleRightsSide) -> Option<Square> {
+        self.castle_rights
+            .for_player(player)
+            .rook_file(side)
+            .map(|file| squares::castle_rook_start(player, file))
+    }
+
+    fn castle_side_for_rook(&self, player: Player, sq: Square) -> Option<CastleRightsSide> {
+        CastleRightsSide::ALL
+            .into_iter()
+            .find(|side| self.castle_rook_square(player, *side) == Some(sq))
+    }
+
     // Convenience method to prevent tests from having to construct their own
     // movelist and allow them to iterate easily over the resulting list of moves
     pub fn moves(&self) -> MoveList {
@@ -232,7 +306,12 @@ impl Game {
         let player = self.player;
         let other_player = player.other();
 
-        let maybe_captured_piece = self.board.piece_at(to);
+        // When castling in Chess960, the king may move onto the square of its own rook
+        let maybe_captured_piece = if mv.is_castling() {
+            None
+        } else {
+            self.board.piece_at(to)
+        };
 
         // Capture the irreversible aspects of the position so that they can be restored
         // if we undo this move.
@@ -243,6 +322,7 @@ impl Game {
             en_passant_target: self.en_passant_target,
             halfmove_clock: self.halfmove_clock,
             zobrist: self.zobrist.clone(),
+            pawn_zobrist: self.pawn_zobrist.clone(),
             incremental_eval: self.incremental_eval.clone(),
         };
 
@@ -250,15 +330,27 @@ impl Game {
 
         let moved_piece = self.remove_at(from);
 
-        if maybe_captured_piece.is_some() {
-            self.remove_at(to);
-        }
+        if mv.is_castling() {
+            let side = CastleRightsSide::from_castle_dest(to);
+            let rook_from = self.castle_rook_square(player, side).unwrap();
+            let rook_to = squares::rook_castle_dest(player, side);
 
-        if let Some(promoted_to) = mv.promotion() {
-            let promoted_piece = Piece::new(player, promoted_to.piece());
-            self.set_at(to, promoted_piece);
-        } else {
+            // In Chess960, the king and rook can end up on each other's starting squares, so we
+            // take both off the board before putting either of them back.
+            let rook = self.remove_at(rook_from);
             self.set_at(to, moved_piece);
+            self.set_at(rook_to, rook);
+        } else {
+            if maybe_captured_piece.is_some() {
+                self.remove_at(to);
+            }
+
+            if let Some(promoted_to) = mv.promotion() {
+                let promoted_piece = Piece::new(player, promoted_to.piece());
+                self.set_at(to, promoted_piece);
+            } else {
+                self.set_at(to, moved_piece);
+            }
         }
 
         // If we moved a pawn to the en passant target, this was an en passant capture, so we
@@ -291,33 +383,22 @@ impl Game {
             .set_en_passant(self.en_passant_target, new_en_passant_target);
         self.en_passant_target = new_en_passant_target;
 
-        if mv.is_castling() {
-            if let Some((rook_from, rook_to)) = squares::castle_squares(player, to) {
-                let rook = self.remove_at(rook_from);
-                self.set_at(rook_to, rook);
-            }
-        }
-
         // Check if we lost castle rights.
         // If we moved the king, we lose all rights to castle.
         // If we moved one of our rooks, we lose rights to castle on that side.
-        if moved_piece.kind == PieceKind::King && from == squares::king_start(player) {
+        if moved_piece.kind == PieceKind::King {
             self.try_remove_castle_rights(player, CastleRightsSide::Kingside);
             self.try_remove_castle_rights(player, CastleRightsSide::Queenside);
         } else if moved_piece.kind == PieceKind::Rook {
-            if from == squares::kingside_rook_start(player) {
-                self.try_remove_castle_rights(player, CastleRightsSide::Kingside);
-            } else if from == squares::queenside_rook_start(player) {
-                self.try_remove_castle_rights(player, CastleRightsSide::Queenside);
+            if let Some(side) = self.castle_side_for_rook(player, from) {
+                self.try_remove_castle_rights(player, side);
             }
         }
 
         // Check if we removed our enemy's ability to castle, i.e. if we took one of their rooks
         if maybe_captured_piece.is_some() {
-            if to == squares::kingside_rook_start(other_player) {
-                self.try_remove_castle_rights(other_player, CastleRightsSide::Kingside);
-            } else if to == squares::queenside_rook_start(other_player) {
-                self.try_remove_castle_rights(other_player, CastleRightsSide::Queenside);
+            if let Some(side) = self.castle_side_for_rook(other_player, to) {
+                self.try_remove_castle_rights(other_player, side);
             }
         }
 
@@ -346,6 +427,7 @@ impl Game {
             en_passant_target: self.en_passant_target,
             halfmove_clock: self.halfmove_clock,
             zobrist: self.zobrist.clone(),
+            pawn_zobrist: self.pawn_zobrist.clone(),
             incremental_eval: self.incremental_eval.clone(),
         };
 
@@ -374,18 +456,25 @@ impl Game {
         self.plies -= 1;
         self.player = player;
         self.zobrist = history.zobrist;
+        self.pawn_zobrist = history.pawn_zobrist;
         self.halfmove_clock = history.halfmove_clock;
         self.castle_rights = history.castle_rights;
         self.en_passant_target = history.en_passant_target;
         self.incremental_eval = history.incremental_eval;
 
-        // Undo castling, if we castled
+        // Undo castling, if we castled. The castle rights have already been restored, so we
+        // know which rook we castled with.
         if mv.is_castling() {
-            if let Some((rook_from, rook_to)) = squares::castle_squares(player, to) {
-                self.board.remove_at(rook_to);
-                self.board
-                    .set_at(rook_from, Piece::new(player, PieceKind::Rook));
-            }
+            let side = CastleRightsSide::from_castle_dest(to);
+            let rook_from = self.castle_rook_square(player, side).unwrap();
+            let rook_to = squares::rook_castle_dest(player, side);
+
+            self.board.remove_at(to);
+            self.board.remove_at(rook_to);
+            self.board.set_at(from, Piece::new(player, PieceKind::King));
+            self.board
+                .set_at(rook_from, Piece::new(player, PieceKind::Rook));
+            return;
         }
 
         // Replace the pawn taken by en-passant capture
@@ -417,6 +506,7 @@ impl Game {
         self.plies -= 1;
         self.player = self.player.other();
         self.zobrist = history.zobrist;
+        self.pawn_zobrist = history.pawn_zobrist;
         self.en_passant_target = history.en_passant_target;
         self.halfmove_clock = history.halfmove_clock;
         self.incremental_eval = history.incremental_eval;
@@ -452,4 +542,79 @@ mod tests {
             .unwrap()
             .is_stalemate_by_insufficient_material());
     }
+
+    #[test]
+    fn test_insufficient_material() {
+        crate::init();
+
+        for fen in [
+            "8/8/3k4/8/8/2K5/8/8 w - - 0 1",
+            "8/8/3k4/4n3/8/2K5/8/8 w - - 0 1",
+            "8/8/3kb3/8/8/2KB4/8/8 w - - 0 1",
+        ] {
+            assert!(Game::from_fen(fen).unwrap().is_insufficient_material());
+        }
+
+        for fen in [
+            // Bishops on opposite colours, knight vs bishop and two knights can all mate
+            "8/8/3k1b2/8/8/2KB4/8/8 w - - 0 1",
+            "8/8/3k4/4n3/8/2KB4/8/8 w - - 0 1",
+            "8/8/3k4/4n3/8/2KN4/8/8 w - - 0 1",
+            "8/8/3k4/8/8/2K5/4P3/8 w - - 0 1",
+        ] {
+            assert!(!Game::from_fen(fen).unwrap().is_insufficient_material());
+        }
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        use crate::chess::moves::MoveListExt;
+        use crate::chess::square::squares::all::*;
+
+        crate::init();
+
+        let mut game = Game::new();
+        let shuffle = [(G1, F3), (G8, F6), (F3, G1), (F6, G8)];
+
+        for _ in 0..2 {
+            assert!(!game.is_threefold_repetition());
+
+            for (src, dst) in shuffle {
+                let mv = game.moves().expect_matching(src, dst, None);
+                game.make_move(mv);
+            }
+
+            assert!(game.is_repeated_position());
+        }
+
+        assert!(game.is_threefold_repetition());
+    }
+
+    #[test]
+    fn test_pawn_zobrist_updated_incrementally() {
+        use crate::chess::piece::PromotionPieceKind;
+        use crate::chess::square::squares::all::*;
+
+        crate::init();
+
+        let mut game = Game::from_fen("4k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let initial_pawn_zobrist = game.pawn_zobrist.clone();
+
+        game.make_move(Move::en_passant(E5, D6));
+        assert_eq!(game.pawn_zobrist, zobrist::pawn_hash(&game));
+        assert_ne!(game.pawn_zobrist, initial_pawn_zobrist);
+
+        // Moves which don't involve pawns shouldn't change the pawn hash
+        let pawn_zobrist_before_king_move = game.pawn_zobrist.clone();
+        game.make_move(Move::quiet(E8, E7));
+        assert_eq!(game.pawn_zobrist, pawn_zobrist_before_king_move);
+
+        game.make_move(Move::quiet_promotion(B7, B8, PromotionPieceKind::Queen));
+        assert_eq!(game.pawn_zobrist, zobrist::pawn_hash(&game));
+
+        game.undo_move();
+        game.undo_move();
+        game.undo_move();
+        assert_eq!(game.pawn_zobrist, initial_pawn_zobrist);
+    }
 }
@@ -6,6 +6,8 @@ pub mod game;
 pub mod movegen;
 pub mod moves;
 pub mod perft;
+#[cfg(not(feature = "release"))]
+pub mod pgn;
 pub mod piece;
 pub mod player;
 pub mod san;
@@ -2,7 +2,11 @@ use crate::chess::bitboard::{bitboards, Bitboard};
 use crate::chess::movegen::{attackers, pins, tables};
 use crate::chess::moves::MoveList;
 use crate::chess::square::{squares, Square};
-use crate::chess::{game::Game, moves::Move, piece::PromotionPieceKind};
+use crate::chess::{
+    game::{CastleRightsSide, Game},
+    moves::Move,
+    piece::{Piece, PieceKind, PromotionPieceKind},
+};
 
 pub struct MovegenCache {
     checkers: Bitboard,
@@ -154,6 +158,139 @@ pub fn generate_quiets(game: &Game, moves: &mut MoveList, movegencache: &Movegen
     }
 }
 
+// Whether a move which didn't come from movegen, like one from the transposition table, is legal in
+// this position. Only the moves of the piece being moved are generated.
+pub fn is_legal(game: &Game, mv: Move) -> bool {
+    let src = mv.src();
+
+    let Some(piece) = game.board.piece_at(src) else {
+        return false;
+    };
+
+    if piece.player != game.player {
+        return false;
+    }
+
+    let all_pieces = game.board.occupancy();
+    let their_pieces = game.board.occupancy_for(game.player.other());
+    let king = game.board.king(game.player).single();
+
+    let checkers = attackers::generate_attackers_of(&game.board, game.player, king);
+    let number_of_checkers = checkers.count();
+
+    let mut moves = MoveList::new();
+
+    if piece.kind == PieceKind::King {
+        generate_king_captures(&mut moves, game, king, their_pieces);
+        generate_king_quiets(&mut moves, game, king, all_pieces);
+
+        if !checkers.any() {
+            generate_castles(&mut moves, game, all_pieces);
+        }
+
+        return moves.contains(&mv);
+    }
+
+    // If we're in check by more than one attacker, we can only get out of check via a king move
+    if number_of_checkers > 1 {
+        return false;
+    }
+
+    let check_mask = if number_of_checkers == 1 {
+        let checker_sq = checkers.single();
+        tables::between(checker_sq, king) | checkers
+    } else {
+        Bitboard::FULL
+    };
+
+    let (orthogonal_pins, diagonal_pins) = pins::get_pins(&game.board, game.player, king);
+    let pieces = src.bb();
+
+    if piece.kind == PieceKind::Pawn {
+        generate_pawn_captures(
+            &mut moves,
+            game,
+            pieces,
+            king,
+            their_pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+        generate_pawn_quiets(
+            &mut moves,
+            game,
+            pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+    }
+
+    if piece.kind == PieceKind::Knight {
+        generate_knight_captures(
+            &mut moves,
+            pieces,
+            their_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+        generate_knight_quiets(
+            &mut moves,
+            pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+    }
+
+    if matches!(piece.kind, PieceKind::Bishop | PieceKind::Queen) {
+        generate_diagonal_slider_captures(
+            &mut moves,
+            pieces,
+            their_pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+        generate_diagonal_slider_quiets(
+            &mut moves,
+            pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+    }
+
+    if matches!(piece.kind, PieceKind::Rook | PieceKind::Queen) {
+        generate_orthogonal_slider_captures(
+            &mut moves,
+            pieces,
+            their_pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+        generate_orthogonal_slider_quiets(
+            &mut moves,
+            pieces,
+            all_pieces,
+            check_mask,
+            orthogonal_pins,
+            diagonal_pins,
+        );
+    }
+
+    moves.contains(&mv)
+}
+
 fn generate_pawn_captures(
     moves: &mut MoveList,
     game: &Game,
@@ -255,14 +392,17 @@ fn generate_pawn_captures(
                 if !diagonal_pins.contains(potential_en_passant_capture_start)
                     || diagonal_pins.contains(en_passant_target)
                 {
-                    // We need to check that we do not reveal a check by making this en-passant capture
-                    let mut board_without_en_passant_participants = game.board.clone();
-                    board_without_en_passant_participants
-                        .remove_at(potential_en_passant_capture_start);
-                    board_without_en_passant_participants.remove_at(captured_pawn);
+                    // We need to check that we do not reveal a check by making this en-passant capture.
+                    // The capturing pawn needs to be on its target square, since it may still be
+                    // blocking a pin along the diagonal it captured on.
+                    let mut board_after_en_passant = game.board.clone();
+                    board_after_en_passant.remove_at(potential_en_passant_capture_start);
+                    board_after_en_passant.remove_at(captured_pawn);
+                    board_after_en_passant
+                        .set_at(en_passant_target, Piece::new(game.player, PieceKind::Pawn));
 
                     let king_in_check = attackers::generate_attackers_of(
-                        &board_without_en_passant_participants,
+                        &board_after_en_passant,
                         game.player,
                         king,
                     )
@@ -524,33 +664,51 @@ fn generate_king_quiets(moves: &mut MoveList, game: &Game, king: Square, all_pie
 }
 
 fn generate_castles(moves: &mut MoveList, game: &Game, all_pieces: Bitboard) {
-    let castle_rights_for_player = game.castle_rights.for_player(game.player);
-
-    if castle_rights_for_player.king_side {
-        generate_castle_move_for_side::<true>(moves, game, all_pieces);
-    }
-
-    if castle_rights_for_player.queen_side {
-        generate_castle_move_for_side::<false>(moves, game, all_pieces);
+    for side in CastleRightsSide::ALL {
+        if let Some(rook) = game.castle_rook_square(game.player, side) {
+            generate_castle_move_for_side(moves, game, all_pieces, side, rook);
+        }
     }
 }
 
-fn generate_castle_move_for_side<const KINGSIDE: bool>(
+fn generate_castle_move_for_side(
     moves: &mut MoveList,
     game: &Game,
     all_pieces: Bitboard,
+    side: CastleRightsSide,
+    rook: Square,
 ) {
-    let king_start_square = squares::king_start(game.player);
+    let king = game.board.king(game.player).single();
+    let king_dest = squares::castle_dest(game.player, side);
+    let rook_dest = squares::rook_castle_dest(game.player, side);
+
+    // All of the squares the king and rook travel through (including where they end up) need to
+    // be empty, apart from the king and rook themselves. In Chess960, they may pass over each other.
+    let king_path = tables::between(king, king_dest) | king_dest.bb();
+    let rook_path = tables::between(rook, rook_dest) | rook_dest.bb();
+    let blockers = all_pieces & !king.bb() & !rook.bb();
+
+    if ((king_path | rook_path) & blockers).any() {
+        return;
+    }
 
-    let (required_empty_squares, target_square, middle_square) =
-        bitboards::castle_squares::<KINGSIDE>(game.player);
+    // The king can't castle out of, through or into check
+    for sq in king_path {
+        if attackers::generate_attackers_of(&game.board, game.player, sq).any() {
+            return;
+        }
+    }
+
+    // In Chess960, the rook we're castling with may be shielding the king's destination from a
+    // slider further along the back rank, so we need to check the destination without it.
+    let mut board_without_rook = game.board.clone();
+    board_without_rook.remove_at(rook);
 
-    if (required_empty_squares & all_pieces).is_empty()
-        && attackers::generate_attackers_of(&game.board, game.player, middle_square).is_empty()
-        && attackers::generate_attackers_of(&game.board, game.player, target_square).is_empty()
-    {
-        moves.push(Move::castles(king_start_square, target_square));
+    if attackers::generate_attackers_of(&board_without_rook, game.player, king_dest).any() {
+        return;
     }
+
+    moves.push(Move::castles(king, king_dest));
 }
 
 #[cfg(test)]
@@ -637,4 +795,25 @@ mod tests {
             (A5, B6),
         );
     }
+
+    #[test]
+    fn test_en_passant_along_pin_ray() {
+        should_allow_move(
+            "qbn1brkr/ppp1p1p1/2n4p/5p2/PPp5/6PP/Q2PPP2/1BNNBRKR b - b3 0 10",
+            (C4, B3),
+        );
+    }
+
+    #[test]
+    fn test_chess960_castle_onto_rook_square() {
+        should_allow_move(
+            "b1q1rrkb/pppppppp/3nn3/8/8/8/PPPPPPPP/B3RKRB w GE - 0 1",
+            (F1, G1),
+        );
+    }
+
+    #[test]
+    fn test_chess960_forbid_castling_when_rook_shields_destination() {
+        should_not_allow_move("4k3/8/8/8/8/8/8/rRK5 w B - 0 1", (C1, C1));
+    }
 }
@@ -4,7 +4,7 @@ mod pins;
 pub mod tables;
 
 pub use attackers::{all_attackers_of, generate_attackers_of};
-pub use gen::{generate_captures, generate_legal_moves, generate_quiets, MovegenCache};
+pub use gen::{generate_captures, generate_legal_moves, generate_quiets, is_legal, MovegenCache};
 
 pub fn init() {
     tables::init();
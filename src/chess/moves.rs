@@ -172,6 +172,16 @@ impl Move {
         self.0.get()
     }
 
+    #[inline]
+    pub fn to_u16(self) -> u16 {
+        self.data()
+    }
+
+    #[inline]
+    pub fn from_u16(data: u16) -> Option<Self> {
+        NonZeroU16::new(data).map(Self)
+    }
+
     #[inline]
     pub fn src(self) -> Square {
         Square::from_index((self.data() & SRC_MASK) as u8)
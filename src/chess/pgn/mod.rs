@@ -0,0 +1,89 @@
+mod pgn_parser;
+mod pgn_writer;
+
+pub use pgn_parser::parse;
+pub use pgn_writer::write;
+
+use crate::chess::fen;
+use crate::chess::game::Game;
+use crate::chess::moves::Move;
+
+const RESULT_WHITE_WIN: &str = "1-0";
+const RESULT_BLACK_WIN: &str = "0-1";
+const RESULT_DRAW: &str = "1/2-1/2";
+const RESULT_UNKNOWN: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub const fn notation(self) -> &'static str {
+        match self {
+            Self::WhiteWin => RESULT_WHITE_WIN,
+            Self::BlackWin => RESULT_BLACK_WIN,
+            Self::Draw => RESULT_DRAW,
+            Self::Unknown => RESULT_UNKNOWN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+
+    // Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Line>,
+}
+
+impl PgnMove {
+    pub const fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    // A comment before the first move of the line
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: Game,
+    pub mainline: Line,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => value.clone_into(existing),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn has_custom_start(&self) -> bool {
+        self.start.to_fen() != fen::START_POS
+    }
+}
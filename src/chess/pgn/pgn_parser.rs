@@ -0,0 +1,370 @@
+use crate::chess::game::Game;
+use crate::chess::pgn::{self, GameResult, Line, PgnGame, PgnMove};
+use crate::chess::san;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, tag, take_till, take_while1},
+    character::complete::{char, digit1, multispace1, none_of, not_line_ending, one_of},
+    combinator::{map, map_res, not, opt, recognize, value},
+    multi::{many0, many0_count, many1_count},
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+#[derive(Debug)]
+enum Token<'a> {
+    Move(&'a str, Option<u8>),
+    Nag(u8),
+    Comment(&'a str),
+    Variation(Vec<Self>),
+}
+
+// Whitespace, including '%' escape lines
+fn pgn_whitespace(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        many0_count(alt((
+            value((), multispace1),
+            value((), pair(char('%'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn pgn_tag_value(input: &str) -> IResult<&str, String> {
+    map(
+        opt(escaped_transform(
+            none_of("\\\""),
+            '\\',
+            alt((value("\\", tag("\\")), value("\"", tag("\"")))),
+        )),
+        Option::unwrap_or_default,
+    )(input)
+}
+
+fn pgn_tag(input: &str) -> IResult<&str, (String, String)> {
+    let (input, (_, (), name, (), value, (), _)) = tuple((
+        char('['),
+        pgn_whitespace,
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        pgn_whitespace,
+        delimited(char('"'), pgn_tag_value, char('"')),
+        pgn_whitespace,
+        char(']'),
+    ))(input)?;
+
+    Ok((input, (name.to_string(), value)))
+}
+
+fn pgn_result(input: &str) -> IResult<&str, GameResult> {
+    alt((
+        value(GameResult::WhiteWin, tag(pgn::RESULT_WHITE_WIN)),
+        value(GameResult::BlackWin, tag(pgn::RESULT_BLACK_WIN)),
+        value(GameResult::Draw, tag(pgn::RESULT_DRAW)),
+        value(GameResult::Unknown, tag(pgn::RESULT_UNKNOWN)),
+    ))(input)
+}
+
+// Traditional suffix annotations, which are equivalent to the first six NAGs
+fn pgn_suffix_annotation(input: &str) -> IResult<&str, u8> {
+    alt((
+        value(3, tag("!!")),
+        value(4, tag("??")),
+        value(5, tag("!?")),
+        value(6, tag("?!")),
+        value(1, tag("!")),
+        value(2, tag("?")),
+    ))(input)
+}
+
+fn pgn_nag(input: &str) -> IResult<&str, u8> {
+    alt((
+        preceded(char('$'), map_res(digit1, str::parse)),
+        pgn_suffix_annotation,
+    ))(input)
+}
+
+fn pgn_comment(input: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(char('{'), take_till(|c| c == '}'), char('}')),
+        preceded(char(';'), not_line_ending),
+    ))(input)
+}
+
+fn pgn_move_number(input: &str) -> IResult<&str, ()> {
+    value((), pair(digit1, many1_count(char('.'))))(input)
+}
+
+fn pgn_move(input: &str) -> IResult<&str, Token<'_>> {
+    let (input, (mv, nag)) = pair(
+        recognize(pair(
+            take_while1(|c: char| c.is_ascii_alphanumeric() || "=-".contains(c)),
+            many0_count(one_of("+#")),
+        )),
+        opt(pgn_suffix_annotation),
+    )(input)?;
+
+    Ok((input, Token::Move(mv, nag)))
+}
+
+fn pgn_variation(input: &str) -> IResult<&str, Vec<Token<'_>>> {
+    delimited(char('('), pgn_tokens, preceded(pgn_whitespace, char(')')))(input)
+}
+
+// Move numbers carry no information beyond the position, so they are skipped
+fn pgn_token(input: &str) -> IResult<&str, Option<Token<'_>>> {
+    preceded(
+        pgn_whitespace,
+        alt((
+            map(pgn_comment, |comment| Some(Token::Comment(comment))),
+            map(pgn_variation, |tokens| Some(Token::Variation(tokens))),
+            map(pgn_nag, |nag| Some(Token::Nag(nag))),
+            map(pgn_move_number, |()| None),
+            map(pgn_move, Some),
+        )),
+    )(input)
+}
+
+fn pgn_tokens(input: &str) -> IResult<&str, Vec<Token<'_>>> {
+    map(many0(pgn_token), |tokens| {
+        tokens.into_iter().flatten().collect()
+    })(input)
+}
+
+fn pgn_game(
+    input: &str,
+) -> IResult<&str, (Vec<(String, String)>, Vec<Token<'_>>, Option<GameResult>)> {
+    let (input, tags) = many0(preceded(pgn_whitespace, pgn_tag))(input)?;
+
+    // A result looks like the start of a move number, so it is checked for before each token
+    let (input, tokens) = many0(preceded(
+        pgn_whitespace,
+        preceded(not(pgn_result), pgn_token),
+    ))(input)?;
+    let (input, result) = opt(preceded(pgn_whitespace, pgn_result))(input)?;
+
+    Ok((
+        input,
+        (tags, tokens.into_iter().flatten().collect(), result),
+    ))
+}
+
+fn resolve_line(game: &mut Game, tokens: &[Token<'_>]) -> Result<Line, String> {
+    let mut line = Line::default();
+
+    for token in tokens {
+        match token {
+            Token::Move(mv, nag) => {
+                let normalised = mv.replace('0', "O");
+                let mv = san::parse_move(game, &normalised)
+                    .map_err(|e| format!("Illegal move {mv} ({e:?})"))?;
+
+                game.make_move(mv);
+
+                let mut pgn_move = PgnMove::new(mv);
+                pgn_move.nags.extend(nag);
+                line.moves.push(pgn_move);
+            }
+            Token::Nag(nag) => {
+                let pgn_move = line
+                    .moves
+                    .last_mut()
+                    .ok_or_else(|| format!("NAG ${nag} before the first move"))?;
+                pgn_move.nags.push(*nag);
+            }
+            Token::Comment(comment) => {
+                let existing = match line.moves.last_mut() {
+                    Some(pgn_move) => &mut pgn_move.comment,
+                    None => &mut line.comment,
+                };
+                append_comment(existing, comment);
+            }
+            Token::Variation(tokens) => {
+                let pgn_move = line
+                    .moves
+                    .last_mut()
+                    .ok_or("Variation before the first move")?;
+
+                let mut variation_game = game.clone();
+                variation_game.undo_move();
+
+                let variation = resolve_line(&mut variation_game, tokens)?;
+                pgn_move.variations.push(variation);
+            }
+        }
+    }
+
+    Ok(line)
+}
+
+fn append_comment(existing: &mut Option<String>, comment: &str) {
+    let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    *existing = match existing.take() {
+        Some(existing) => Some(format!("{existing} {comment}")),
+        None => Some(comment),
+    };
+}
+
+fn parse_result_tag(tags: &[(String, String)]) -> GameResult {
+    tags.iter()
+        .find(|(name, _)| name == "Result")
+        .and_then(|(_, result)| pgn_result(result).ok())
+        .map_or(GameResult::Unknown, |(_, result)| result)
+}
+
+fn resolve_game(
+    tags: Vec<(String, String)>,
+    tokens: &[Token<'_>],
+    result: Option<GameResult>,
+) -> Result<PgnGame, String> {
+    let fen = tags
+        .iter()
+        .find(|(name, _)| name == "FEN")
+        .map(|(_, fen)| fen.as_str());
+
+    let start = match fen {
+        Some(fen) => Game::from_fen(fen)?,
+        None => Game::new(),
+    };
+
+    let mut game = start.clone();
+    let mainline = resolve_line(&mut game, tokens)?;
+    let result = result.unwrap_or_else(|| parse_result_tag(&tags));
+
+    Ok(PgnGame {
+        tags,
+        start,
+        mainline,
+        result,
+    })
+}
+
+pub fn parse(input: &str) -> Result<Vec<PgnGame>, String> {
+    let mut input = input.trim_start_matches('\u{feff}');
+    let mut games = Vec::new();
+
+    loop {
+        let (start, ()) = pgn_whitespace(input).map_err(|e| format!("Invalid PGN ({e})"))?;
+        if start.is_empty() {
+            return Ok(games);
+        }
+
+        let game_number = games.len() + 1;
+
+        let (rest, (tags, tokens, result)) =
+            pgn_game(start).map_err(|e| format!("Invalid PGN in game {game_number} ({e})"))?;
+
+        if rest.len() == start.len() {
+            let context: String = rest.chars().take(20).collect();
+            return Err(format!(
+                "Invalid PGN in game {game_number} near \"{context}\""
+            ));
+        }
+
+        let game = resolve_game(tags, &tokens, result)
+            .map_err(|e| format!("Invalid PGN in game {game_number}: {e}"))?;
+
+        games.push(game);
+        input = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::moves::MoveListExt;
+    use crate::chess::square::squares::all::*;
+
+    const PGN: &str = r#"[Event "Test \"match\""]
+[Site "?"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3!? Nc6 $2 3. Bb5 {Ruy Lopez} (3. Bc4 Bc5; Italian
+4. c3 (4. O-O) Nf6) 3... a6 4. Ba4 Nf6 5. 0-0 Be7 1-0
+
+[Event "Second"]
+[SetUp "1"]
+[FEN "4k3/4P3/3K4/8/8/8/8/8 b - - 0 1"]
+
+1... Kf7?? 2. e8=Q+ *
+"#;
+
+    #[test]
+    fn parse_multiple_games() {
+        crate::init();
+
+        let games = parse(PGN).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        assert_eq!(game.tag("Event"), Some("Test \"match\""));
+        assert_eq!(game.tag("Round"), None);
+        assert_eq!(game.result, GameResult::WhiteWin);
+        assert_eq!(game.mainline.comment.as_deref(), Some("Opening comment"));
+        assert_eq!(game.mainline.moves.len(), 10);
+
+        let game = &games[1];
+        assert_eq!(game.result, GameResult::Unknown);
+        assert_eq!(game.mainline.moves.len(), 2);
+        assert_eq!(game.mainline.moves[0].nags, vec![4]);
+        assert_eq!(
+            game.mainline.moves[1].mv.promotion(),
+            Some(crate::chess::piece::PromotionPieceKind::Queen)
+        );
+    }
+
+    #[test]
+    fn parse_nags_comments_and_variations() {
+        crate::init();
+
+        let games = parse(PGN).unwrap();
+        let moves = &games[0].mainline.moves;
+
+        assert_eq!(moves[2].nags, vec![5]);
+        assert_eq!(moves[3].nags, vec![2]);
+        assert_eq!(moves[4].comment.as_deref(), Some("Ruy Lopez"));
+
+        let variation = &moves[4].variations[0];
+        assert_eq!(variation.moves.len(), 4);
+        assert_eq!(variation.moves[1].comment.as_deref(), Some("Italian"));
+
+        let expected =
+            Game::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap()
+                .moves()
+                .expect_matching(F1, C4, None);
+        assert_eq!(variation.moves[0].mv, expected);
+
+        let nested = &variation.moves[2].variations[0];
+        assert_eq!(nested.moves.len(), 1);
+        assert!(nested.moves[0].mv.is_castling());
+    }
+
+    #[test]
+    fn parse_result_from_tag_when_missing() {
+        crate::init();
+
+        let games = parse("[Result \"1/2-1/2\"]\n\n1. e4 e5").unwrap();
+        assert_eq!(games[0].result, GameResult::Draw);
+    }
+
+    #[test]
+    fn parse_empty_input() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("\n  \n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        crate::init();
+
+        assert!(parse("1. e4 e4").unwrap_err().contains("Illegal move e4"));
+        assert!(parse("1. e4 e5 *\n\n1. Nf6")
+            .unwrap_err()
+            .contains("game 2"));
+        assert!(parse("1. e4 {unterminated").is_err());
+        assert!(parse("(1. e4) 1. d4").is_err());
+        assert!(parse("[FEN \"not a fen\"]\n\n*").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::chess::game::Game;
+use crate::chess::pgn::{Line, PgnGame};
+use crate::chess::player::Player;
+use crate::chess::san;
+
+const MAX_LINE_LENGTH: usize = 80;
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!("[{name} \"{}\"]", escape_tag_value(value))
+}
+
+fn format_comment(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', ")"))
+}
+
+fn write_line(game: &mut Game, line: &Line, tokens: &mut Vec<String>) {
+    if let Some(comment) = &line.comment {
+        tokens.push(format_comment(comment));
+    }
+
+    // Black moves only need a move number at the start of a line or after an interruption
+    let mut needs_move_number = true;
+
+    for pgn_move in &line.moves {
+        match game.player {
+            Player::White => tokens.push(format!("{}.", game.turn())),
+            Player::Black if needs_move_number => tokens.push(format!("{}...", game.turn())),
+            Player::Black => {}
+        }
+
+        tokens.push(san::format_move(game, pgn_move.mv));
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${nag}")));
+
+        if let Some(comment) = &pgn_move.comment {
+            tokens.push(format_comment(comment));
+        }
+
+        for variation in &pgn_move.variations {
+            let mut variation_tokens = Vec::new();
+            write_line(&mut game.clone(), variation, &mut variation_tokens);
+
+            tokens.push(format!("({})", variation_tokens.join(" ")));
+        }
+
+        needs_move_number = pgn_move.comment.is_some() || !pgn_move.variations.is_empty();
+        game.make_move(pgn_move.mv);
+    }
+}
+
+fn wrap(movetext: &str) -> String {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in movetext.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > MAX_LINE_LENGTH {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    lines.push(current);
+    lines.join("\n")
+}
+
+pub fn write(pgn: &PgnGame) -> String {
+    let mut output = String::new();
+
+    for (name, value) in &pgn.tags {
+        output.push_str(&format_tag(name, value));
+        output.push('\n');
+    }
+
+    if pgn.has_custom_start() && pgn.tag("FEN").is_none() {
+        output.push_str(&format_tag("SetUp", "1"));
+        output.push('\n');
+        output.push_str(&format_tag("FEN", &pgn.start.to_fen()));
+        output.push('\n');
+    }
+
+    if !pgn.tags.is_empty() || pgn.has_custom_start() {
+        output.push('\n');
+    }
+
+    let mut tokens = Vec::new();
+    write_line(&mut pgn.start.clone(), &pgn.mainline, &mut tokens);
+    tokens.push(pgn.result.notation().to_string());
+
+    output.push_str(&wrap(&tokens.join(" ")));
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pgn;
+    use crate::chess::pgn::pgn_parser::parse;
+
+    #[test]
+    fn write_round_trip() {
+        crate::init();
+
+        let input = r#"[Event "Test \"match\""]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3 $5 Nc6 $2 3. Bb5 {Ruy Lopez} (3. Bc4 Bc5
+{Italian} 4. c3 (4. O-O) 4... Nf6) 3... a6 4. Ba4 Nf6 5. O-O Be7 1-0
+"#;
+
+        let games = parse(input).unwrap();
+        assert_eq!(write(&games[0]), input);
+    }
+
+    #[test]
+    fn write_custom_start() {
+        crate::init();
+
+        let mut game = parse("1. e4").unwrap().remove(0);
+        game.start = Game::from_fen("4k3/4P3/3K4/8/8/8/8/8 b - - 0 1").unwrap();
+        game.mainline.moves.clear();
+        game.mainline.moves.push(pgn::PgnMove::new(
+            game.start.moves().into_iter().next().unwrap(),
+        ));
+
+        let output = write(&game);
+        assert!(output
+            .starts_with("[SetUp \"1\"]\n[FEN \"4k3/4P3/3K4/8/8/8/8/8 b - - 0 1\"]\n\n1... K"));
+        assert!(output.ends_with(" *\n"));
+    }
+
+    #[test]
+    fn write_wraps_long_lines() {
+        crate::init();
+
+        let input = "1. Nf3 Nf6 2. Ng1 Ng8 ".repeat(20);
+        let games = parse(&input).unwrap();
+        let output = write(&games[0]);
+
+        assert!(output.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(parse(&output).unwrap()[0].mainline.moves.len(), 80);
+    }
+}
@@ -1,5 +1,5 @@
-use crate::chess::game::Game;
-use crate::chess::moves::{Move, MoveListExt};
+use crate::chess::game::{CastleRightsSide, Game};
+use crate::chess::moves::Move;
 use crate::chess::piece::{PieceKind, PromotionPieceKind};
 use crate::chess::san;
 use crate::chess::square::{squares, File, Rank, Square};
@@ -30,6 +30,9 @@ pub enum ParseError {
     InvalidPromotionPiece,
     InvalidAmbiguityResolution,
     NoXInCaptureMove,
+    InvalidSquare,
+    IllegalMove,
+    AmbiguousMove,
 }
 
 fn parse_ambiguity_resolution(chars: &[char]) -> Result<AmbiguityResolution, ParseError> {
@@ -116,67 +119,63 @@ fn parse_piece(c: char) -> Option<PieceKind> {
     }
 }
 
-fn parse_source_square(game: &Game, src: &str, dst: Square) -> Result<Square, ParseError> {
-    let piece_moves: Vec<(PieceKind, Move)> = game
-        .moves()
-        .iter()
-        .map(|mv| (game.board.piece_at(mv.src()).unwrap().kind, *mv))
-        .collect();
+fn single_source_square(matching_source_squares: HashSet<Square>) -> Result<Square, ParseError> {
+    let mut squares = matching_source_squares.into_iter();
 
-    // Pawn move
-    if src.is_empty() {
-        let matching_source_squares: HashSet<Square> = piece_moves
-            .into_iter()
-            .filter(|&(piece, mv)| piece == PieceKind::Pawn && mv.dst() == dst)
-            .map(|(_, mv)| mv.src())
-            .collect();
-
-        assert_eq!(matching_source_squares.len(), 1);
-        return Ok(*matching_source_squares.iter().next().unwrap());
+    match (squares.next(), squares.next()) {
+        (Some(sq), None) => Ok(sq),
+        (None, _) => Err(ParseError::IllegalMove),
+        (Some(_), Some(_)) => Err(ParseError::AmbiguousMove),
     }
+}
 
+// Promotions generate one move per promotion piece, so matching source squares are collected into
+// a set rather than counting moves.
+fn parse_source_square(game: &Game, src: &str, dst: Square) -> Result<Square, ParseError> {
     let src_chars: Vec<char> = src.chars().collect();
-    let (first_char, rest) = src_chars.split_first().unwrap();
-
-    if let Some(moved_piece) = parse_piece(*first_char) {
-        let ambiguity_resolution = parse_ambiguity_resolution(rest)?;
 
-        let matching_source_squares: Vec<Square> = piece_moves
-            .into_iter()
-            .filter(|&(piece, mv)| {
-                piece == moved_piece && mv.dst() == dst && ambiguity_resolution.satisfied_by(mv)
-            })
-            .map(|(_, mv)| mv.src())
-            .collect();
-
-        assert_eq!(matching_source_squares.len(), 1);
-        return Ok(*matching_source_squares.first().unwrap());
-    }
-
-    let ambiguity_resolution = parse_ambiguity_resolution(&src_chars)?;
+    // A missing piece letter means a pawn move, possibly with the file of a capturing pawn
+    let (moved_piece, ambiguity_resolution) = match src_chars.split_first() {
+        Some((first_char, rest)) => match parse_piece(*first_char) {
+            Some(moved_piece) => (moved_piece, parse_ambiguity_resolution(rest)?),
+            None => (PieceKind::Pawn, parse_ambiguity_resolution(&src_chars)?),
+        },
+        None => (PieceKind::Pawn, AmbiguityResolution::None),
+    };
 
-    let matching_source_squares: Vec<Square> = piece_moves
-        .into_iter()
-        .filter(|&(_, mv)| mv.dst() == dst && ambiguity_resolution.satisfied_by(mv))
-        .map(|(_, mv)| mv.src())
+    let matching_source_squares: HashSet<Square> = game
+        .moves()
+        .iter()
+        .filter(|mv| {
+            game.board.piece_at(mv.src()).unwrap().kind == moved_piece
+                && mv.dst() == dst
+                && ambiguity_resolution.satisfied_by(**mv)
+        })
+        .map(|mv| mv.src())
         .collect();
 
-    assert_eq!(matching_source_squares.len(), 1);
-    Ok(*matching_source_squares.first().unwrap())
+    single_source_square(matching_source_squares)
 }
 
 fn parse_destination_square(sq: &str) -> Result<Square, ParseError> {
-    assert_eq!(sq.len(), 2);
-
     let mut chars = sq.chars();
-    let file = parse_file(chars.next().unwrap())?;
-    let rank = parse_rank(chars.next().unwrap())?;
+    let (Some(file), Some(rank), None) = (chars.next(), chars.next(), chars.next()) else {
+        return Err(ParseError::InvalidSquare);
+    };
+
+    let file = parse_file(file)?;
+    let rank = parse_rank(rank)?;
 
     Ok(Square::from_file_and_rank(file, rank))
 }
 
 fn parse_move_squares(game: &Game, mv: &str) -> Result<(Square, Square), ParseError> {
-    let (src, dst) = mv.split_at(mv.len() - 2);
+    let split = mv
+        .char_indices()
+        .nth_back(1)
+        .ok_or(ParseError::InvalidSquare)?
+        .0;
+    let (src, dst) = mv.split_at(split);
 
     let dst = parse_destination_square(dst)?;
     let src = parse_source_square(game, src, dst)?;
@@ -204,27 +203,30 @@ fn parse_squares(game: &Game, mv: &str) -> Result<(Square, Square), ParseError>
     parse_move_squares(game, mv)
 }
 
+// In Chess960 the king may have a normal move to its castling destination too, so we need to
+// make sure we find the castling move.
+fn castle_move(game: &Game, side: CastleRightsSide) -> Result<Move, ParseError> {
+    let castle_dest = squares::castle_dest(game.player, side);
+
+    game.moves()
+        .into_iter()
+        .find(|mv| mv.is_castling() && mv.dst() == castle_dest)
+        .ok_or(ParseError::IllegalMove)
+}
+
 pub fn parse_move(game: &Game, mv: &str) -> Result<Move, ParseError> {
+    let mv = mv
+        .trim_end_matches(san::CHECK)
+        .trim_end_matches(san::CHECKMATE);
+
     if mv == san::KINGSIDE_CASTLE {
-        return Ok(game.moves().expect_matching(
-            squares::king_start(game.player),
-            squares::kingside_castle_dest(game.player),
-            None,
-        ));
+        return castle_move(game, CastleRightsSide::Kingside);
     }
 
     if mv == san::QUEENSIDE_CASTLE {
-        return Ok(game.moves().expect_matching(
-            squares::king_start(game.player),
-            squares::queenside_castle_dest(game.player),
-            None,
-        ));
+        return castle_move(game, CastleRightsSide::Queenside);
     }
 
-    let mv = mv
-        .trim_end_matches(san::CHECK)
-        .trim_end_matches(san::CHECKMATE);
-
     let (mv, promotion) = if mv.contains(san::PROMOTION) {
         let (rest, promotion_piece) = mv
             .split_once(san::PROMOTION)
@@ -237,7 +239,10 @@ pub fn parse_move(game: &Game, mv: &str) -> Result<Move, ParseError> {
 
     let (src, dst) = parse_squares(game, mv)?;
 
-    Ok(game.moves().expect_matching(src, dst, promotion))
+    game.moves()
+        .into_iter()
+        .find(|mv| mv.src() == src && mv.dst() == dst && mv.promotion() == promotion)
+        .ok_or(ParseError::IllegalMove)
 }
 
 #[cfg(test)]
@@ -245,6 +250,7 @@ mod tests {
     use super::*;
     use crate::chess::fen;
     use crate::chess::game::Game;
+    use crate::chess::moves::MoveListExt;
     use crate::chess::piece::PromotionPieceKind;
     use crate::chess::square::squares::all::*;
 
@@ -347,4 +353,61 @@ mod tests {
             "g8=Q+",
         );
     }
+
+    #[test]
+    fn san_castling_with_check() {
+        test_parse_san("5k2/8/8/8/8/8/8/4K2R w K - 0 1", (E1, G1), "O-O+");
+    }
+
+    #[test]
+    fn san_pawn_capture_with_piece_on_same_file() {
+        test_parse_san("4k3/8/8/3p4/4P3/8/4Q3/4K3 w - - 0 1", (E4, D5), "exd5");
+    }
+
+    #[test]
+    fn san_capture_promotion() {
+        test_parse_san_with_promotion(
+            "3r3k/4P3/8/8/8/8/8/K7 w - - 0 1",
+            (E7, D8, PromotionPieceKind::Queen),
+            "exd8=Q+",
+        );
+    }
+
+    #[test]
+    fn san_invalid_moves_are_errors() {
+        crate::init();
+
+        let game = Game::new();
+
+        assert!(matches!(
+            parse_move(&game, "e5"),
+            Err(ParseError::IllegalMove)
+        ));
+        assert!(matches!(
+            parse_move(&game, "O-O"),
+            Err(ParseError::IllegalMove)
+        ));
+        assert!(matches!(
+            parse_move(&game, "Nd2"),
+            Err(ParseError::IllegalMove)
+        ));
+        assert!(matches!(
+            parse_move(&game, "e"),
+            Err(ParseError::InvalidSquare)
+        ));
+        assert!(matches!(
+            parse_move(&game, ""),
+            Err(ParseError::InvalidSquare)
+        ));
+        assert!(matches!(
+            parse_move(&game, "Né4"),
+            Err(ParseError::InvalidFile)
+        ));
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert!(matches!(
+            parse_move(&game, "Rd1"),
+            Err(ParseError::AmbiguousMove)
+        ));
+    }
 }
@@ -1,8 +1,7 @@
-use crate::chess::game::Game;
+use crate::chess::game::{CastleRightsSide, Game};
 use crate::chess::moves::Move;
 use crate::chess::piece::{PieceKind, PromotionPieceKind};
 use crate::chess::san;
-use crate::chess::square::squares;
 
 #[derive(Debug, Eq, PartialEq)]
 enum AmbiguityResolution {
@@ -18,17 +17,11 @@ pub fn format_move(game: &Game, mv: Move) -> String {
 
     let piece = game.board.piece_at(from).unwrap();
 
-    let king_start = squares::king_start(game.player);
-    if piece.kind == PieceKind::King && from == king_start {
-        let kingside_castle_dest = squares::kingside_castle_dest(game.player);
-        if to == kingside_castle_dest {
-            return san::KINGSIDE_CASTLE.to_string();
-        }
-
-        let queenside_castle_dest = squares::queenside_castle_dest(game.player);
-        if to == queenside_castle_dest {
-            return san::QUEENSIDE_CASTLE.to_string();
-        }
+    if mv.is_castling() {
+        return match CastleRightsSide::from_castle_dest(to) {
+            CastleRightsSide::Kingside => san::KINGSIDE_CASTLE.to_string(),
+            CastleRightsSide::Queenside => san::QUEENSIDE_CASTLE.to_string(),
+        };
     }
 
     let mut game_after_move = game.clone();
@@ -81,10 +74,12 @@ pub fn format_move(game: &Game, mv: Move) -> String {
         ),
     };
 
-    let opponent_in_check_specifier = if places_opponent_in_check {
-        san::CHECK.to_string()
-    } else {
+    let opponent_in_check_specifier = if !places_opponent_in_check {
         String::new()
+    } else if game_after_move.moves().is_empty() {
+        san::CHECKMATE.to_string()
+    } else {
+        san::CHECK.to_string()
     };
 
     format!("{piece_identifier}{ambiguity_resolution}{capture_x}{destination_notation}{promotion_specifier}{opponent_in_check_specifier}")
@@ -125,11 +120,15 @@ fn required_ambiguity_resolution(game: &Game, mv: Move) -> AmbiguityResolution {
         .iter()
         .any(|m| m.src().rank() == mv.src().rank());
 
-    match (ambiguity_by_file, ambiguity_by_rank) {
-        (false, false) => AmbiguityResolution::None,
-        (true, false) => AmbiguityResolution::Rank,
-        (false, true) => AmbiguityResolution::File,
-        (true, true) => AmbiguityResolution::Exact,
+    // The file is preferred whenever it's enough to tell the moves apart
+    if potentially_ambiguous_moves.is_empty() {
+        AmbiguityResolution::None
+    } else if !ambiguity_by_file {
+        AmbiguityResolution::File
+    } else if !ambiguity_by_rank {
+        AmbiguityResolution::Rank
+    } else {
+        AmbiguityResolution::Exact
     }
 }
 
@@ -282,4 +281,22 @@ mod tests {
             "g8=Q+",
         );
     }
+
+    #[test]
+    fn san_ambiguity_on_different_files_and_ranks() {
+        test_san_string(
+            "rnbq1rk1/ppp1bppp/4pn2/3p2B1/2PP4/2N1PN2/PP3PPP/R2QKB1R b KQ - 0 6",
+            (B8, D7),
+            "Nbd7",
+        );
+    }
+
+    #[test]
+    fn san_checkmate() {
+        test_san_string(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            (H5, F7),
+            "Qxf7#",
+        );
+    }
 }
@@ -314,84 +314,37 @@ impl std::ops::BitOr for Square {
 }
 
 pub mod squares {
-    use self::all::*;
+    use crate::chess::game::CastleRightsSide;
     use crate::chess::player::Player;
-    use crate::chess::square::Square;
+    use crate::chess::square::{File, Rank, Square};
 
-    pub const fn king_start(player: Player) -> Square {
+    pub const fn back_rank(player: Player) -> Rank {
         match player {
-            Player::White => INIT_WHITE_KING,
-            Player::Black => INIT_BLACK_KING,
+            Player::White => Rank::R1,
+            Player::Black => Rank::R8,
         }
     }
 
-    pub const fn kingside_rook_start(player: Player) -> Square {
-        match player {
-            Player::White => H1,
-            Player::Black => H8,
-        }
-    }
-
-    pub const fn queenside_rook_start(player: Player) -> Square {
-        match player {
-            Player::White => A1,
-            Player::Black => A8,
-        }
+    pub const fn castle_rook_start(player: Player, rook_file: File) -> Square {
+        Square::from_file_and_rank(rook_file, back_rank(player))
     }
 
-    pub fn castle_squares(player: Player, king_moved_to: Square) -> Option<(Square, Square)> {
-        let kingside_castle_dest = kingside_castle_dest(player);
-        let queenside_castle_dest = queenside_castle_dest(player);
-
-        match king_moved_to {
-            s if s == kingside_castle_dest => Some((
-                kingside_rook_start(player),
-                kingside_rook_castle_end(player),
-            )),
-            s if s == queenside_castle_dest => Some((
-                queenside_rook_start(player),
-                queenside_rook_castle_end(player),
-            )),
-            _ => None,
+    // Regardless of where the king and rook start (e.g. in Chess960), they always end up on the
+    // same squares after castling.
+    pub const fn castle_dest(player: Player, side: CastleRightsSide) -> Square {
+        match side {
+            CastleRightsSide::Kingside => Square::from_file_and_rank(File::G, back_rank(player)),
+            CastleRightsSide::Queenside => Square::from_file_and_rank(File::C, back_rank(player)),
         }
     }
 
-    pub const fn kingside_castle_dest(player: Player) -> Square {
-        match player {
-            Player::White => WHITE_KINGSIDE_CASTLE_SQUARE,
-            Player::Black => BLACK_KINGSIDE_CASTLE_SQUARE,
-        }
-    }
-
-    const fn kingside_rook_castle_end(player: Player) -> Square {
-        match player {
-            Player::White => F1,
-            Player::Black => F8,
+    pub const fn rook_castle_dest(player: Player, side: CastleRightsSide) -> Square {
+        match side {
+            CastleRightsSide::Kingside => Square::from_file_and_rank(File::F, back_rank(player)),
+            CastleRightsSide::Queenside => Square::from_file_and_rank(File::D, back_rank(player)),
         }
     }
 
-    pub const fn queenside_castle_dest(player: Player) -> Square {
-        match player {
-            Player::White => WHITE_QUEENSIDE_CASTLE_SQUARE,
-            Player::Black => BLACK_QUEENSIDE_CASTLE_SQUARE,
-        }
-    }
-
-    const fn queenside_rook_castle_end(player: Player) -> Square {
-        match player {
-            Player::White => D1,
-            Player::Black => D8,
-        }
-    }
-
-    pub const INIT_WHITE_KING: Square = E1;
-    pub const WHITE_KINGSIDE_CASTLE_SQUARE: Square = G1;
-    pub const WHITE_QUEENSIDE_CASTLE_SQUARE: Square = C1;
-
-    pub const INIT_BLACK_KING: Square = E8;
-    pub const BLACK_KINGSIDE_CASTLE_SQUARE: Square = G8;
-    pub const BLACK_QUEENSIDE_CASTLE_SQUARE: Square = C8;
-
     pub mod all {
         use super::super::*;
 
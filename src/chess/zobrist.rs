@@ -149,20 +149,20 @@ pub fn hash(game: &Game) -> ZobristHash {
     let [white_castle_rights, black_castle_rights] = game.castle_rights.inner();
 
     // White
-    if white_castle_rights.king_side {
+    if white_castle_rights.king_side.is_some() {
         hash ^= castle_rights(Player::White, CastleRightsSide::Kingside);
     }
 
-    if white_castle_rights.queen_side {
+    if white_castle_rights.queen_side.is_some() {
         hash ^= castle_rights(Player::White, CastleRightsSide::Queenside);
     }
 
     // Black
-    if black_castle_rights.king_side {
+    if black_castle_rights.king_side.is_some() {
         hash ^= castle_rights(Player::Black, CastleRightsSide::Kingside);
     }
 
-    if black_castle_rights.queen_side {
+    if black_castle_rights.queen_side.is_some() {
         hash ^= castle_rights(Player::Black, CastleRightsSide::Queenside);
     }
 
@@ -177,6 +177,19 @@ pub fn hash(game: &Game) -> ZobristHash {
     ZobristHash(hash)
 }
 
+// A hash of just the pawns on the board, used to cache pawn structure evaluation
+pub fn pawn_hash(game: &Game) -> ZobristHash {
+    let mut hash = 0u64;
+
+    for player in [Player::White, Player::Black] {
+        for s in game.board.pawns(player) {
+            hash ^= piece_on_square(player, PieceKind::Pawn, s);
+        }
+    }
+
+    ZobristHash(hash)
+}
+
 fn piece_on_square(player: Player, piece: PieceKind, square: Square) -> ZobristComponent {
     *unsafe {
         components::PIECE_SQUARE
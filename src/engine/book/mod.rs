@@ -0,0 +1,270 @@
+use crate::chess::game::{CastleRightsSide, Game};
+use crate::chess::moves::Move;
+use crate::chess::piece::{PieceKind, PromotionPieceKind};
+use crate::chess::player::Player;
+use crate::chess::square::{File, Rank, Square};
+use crate::engine::uci::UciMove;
+use rand::Rng;
+
+mod keys;
+
+// Each entry in a polyglot book is 16 bytes, stored big-endian:
+//   key (8 bytes), move (2 bytes), weight (2 bytes), learn (4 bytes)
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct BookEntry {
+    key: u64,
+    mv: u16,
+    weight: u16,
+}
+
+pub struct OpeningBook {
+    // Polyglot books are sorted by key, so we can binary search for a position's entries
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        if path.is_empty() {
+            *self = Self::new();
+            return Ok(());
+        }
+
+        let bytes = std::fs::read(path).map_err(|e| format!("Unable to read {path}: {e}"))?;
+        *self = Self::from_bytes(&bytes)?;
+        Ok(())
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err("Invalid polyglot book".to_string());
+        }
+
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| BookEntry {
+                key: u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(entry[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(entry[10..12].try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+
+        // Books should already be sorted, but we rely on it for lookups so make sure
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(Self { entries })
+    }
+
+    pub fn moves(&self, game: &Game) -> Vec<(Move, u16)> {
+        let key = polyglot_key(game);
+        let first_entry = self.entries.partition_point(|entry| entry.key < key);
+
+        self.entries[first_entry..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| Some((polyglot_move(game, entry.mv)?, entry.weight)))
+            .collect()
+    }
+
+    // Pick one of the book moves at random, with each move's chance of being picked proportional
+    // to its weight.
+    pub fn pick_move(&self, game: &Game) -> Option<Move> {
+        let moves = self.moves(game);
+        let total_weight: u32 = moves.iter().map(|(_, weight)| u32::from(*weight)).sum();
+
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut choice = rand::thread_rng().gen_range(0..total_weight);
+
+        for (mv, weight) in moves {
+            if choice < u32::from(weight) {
+                return Some(mv);
+            }
+
+            choice -= u32::from(weight);
+        }
+
+        unreachable!()
+    }
+}
+
+// Polyglot moves are encoded as:
+//   bits 0-2: to file, 3-5: to rank, 6-8: from file, 9-11: from rank, 12-14: promotion piece
+// Castling is encoded as the king capturing its own rook, which `UciMove` already understands.
+fn polyglot_move(game: &Game, mv: u16) -> Option<Move> {
+    let field = |shift: u16| ((mv >> shift) & 0b111) as u8;
+
+    let dst = Square::from_file_and_rank(File::from_idx(field(0)), Rank::from_idx(field(3)));
+    let src = Square::from_file_and_rank(File::from_idx(field(6)), Rank::from_idx(field(9)));
+
+    let promotion = match field(12) {
+        1 => Some(PromotionPieceKind::Knight),
+        2 => Some(PromotionPieceKind::Bishop),
+        3 => Some(PromotionPieceKind::Rook),
+        4 => Some(PromotionPieceKind::Queen),
+        _ => None,
+    };
+
+    UciMove {
+        src,
+        dst,
+        promotion,
+    }
+    .to_move(game)
+}
+
+// Polyglot books use their own keys, which are different to our Zobrist hashes
+pub fn polyglot_key(game: &Game) -> u64 {
+    let mut key = 0;
+
+    for sq in game.board.occupancy() {
+        let piece = game.board.piece_at(sq).unwrap();
+        let piece_idx = 2 * piece.kind.array_idx() + usize::from(piece.player == Player::White);
+
+        key ^= keys::RANDOM[64 * piece_idx + sq.array_idx()];
+    }
+
+    for (i, (player, side)) in [
+        (Player::White, CastleRightsSide::Kingside),
+        (Player::White, CastleRightsSide::Queenside),
+        (Player::Black, CastleRightsSide::Kingside),
+        (Player::Black, CastleRightsSide::Queenside),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if game
+            .castle_rights
+            .for_player(player)
+            .can_castle_to_side(side)
+        {
+            key ^= keys::RANDOM[768 + i];
+        }
+    }
+
+    // The en passant file is only included if there's a pawn that could make the capture
+    if let Some(en_passant_target) = game.en_passant_target {
+        let capture_from = en_passant_target.backward(game.player).bb();
+        let capturers = capture_from.west() | capture_from.east();
+
+        if (capturers & game.board.pieces_of_kind(PieceKind::Pawn, game.player)).any() {
+            key ^= keys::RANDOM[772 + en_passant_target.file().idx() as usize];
+        }
+    }
+
+    if game.player == Player::White {
+        key ^= keys::RANDOM[780];
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::square::squares::all::*;
+
+    // Test positions from the polyglot book format specification
+    #[test]
+    fn test_polyglot_keys() {
+        crate::init();
+
+        let positions = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                0x463b_9618_1691_fc9c,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                0x823c_9b50_fd11_4196,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                0x0756_b944_61c5_0fb0,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                0x662f_afb9_65db_29d4,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                0x22a4_8b5a_8e47_ff78,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+                0x652a_607c_a3f2_42c1,
+            ),
+            (
+                "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+                0x00fd_d303_c946_bdd9,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+                0x3c81_23ea_7b06_7637,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+                0x5c3f_9b82_9b27_9560,
+            ),
+        ];
+
+        for (fen, expected_key) in positions {
+            assert_eq!(
+                polyglot_key(&Game::from_fen(fen).unwrap()),
+                expected_key,
+                "{fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pick_weighted_book_move() {
+        crate::init();
+
+        let game = Game::new();
+        let key = polyglot_key(&game);
+
+        // e2e4 with a weight, d2d4 with no weight (so it should never be picked), and an entry
+        // for another position which should be ignored
+        let mut bytes = Vec::new();
+        for (key, mv, weight) in [
+            (key, 0b000_001_100_011_100_u16, 10_u16),
+            (key, 0b000_001_011_011_011_u16, 0_u16),
+            (key + 1, 0b000_001_010_011_010_u16, 10_u16),
+        ] {
+            bytes.extend(key.to_be_bytes());
+            bytes.extend(mv.to_be_bytes());
+            bytes.extend(weight.to_be_bytes());
+            bytes.extend(0_u32.to_be_bytes());
+        }
+
+        let book = OpeningBook::from_bytes(&bytes).unwrap();
+        assert_eq!(book.moves(&game).len(), 2);
+
+        for _ in 0..10 {
+            let mv = book.pick_move(&game).unwrap();
+            assert_eq!((mv.src(), mv.dst()), (E2, E4));
+        }
+    }
+
+    #[test]
+    fn test_castling_book_move() {
+        crate::init();
+
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        // Polyglot encodes castling as e1h1
+        let mv = polyglot_move(&game, 0b000_000_100_000_111).unwrap();
+        assert!(mv.is_castling());
+        assert_eq!((mv.src(), mv.dst()), (E1, G1));
+    }
+}
@@ -1,7 +1,7 @@
 macro_rules! parameters {
     (
         $(
-            ($param:ident, $size:expr, $type:ident, $name:expr)
+            ($param:ident, $size:expr, $type:ident, $name:ident)
         ),* $(,)?
     ) => {
         #[cfg(feature = "tuner")]
@@ -41,6 +41,18 @@ macro_rules! parameters {
 
                 parameter_components
             }
+
+            // The parameters the engine is currently using, as defined in params.rs
+            #[cfg(test)]
+            pub fn current() -> Self {
+                use crate::utils::tuner::parameters::ToParameters;
+
+                Self {
+                    $(
+                        $param: params::$name.to_parameters(),
+                    )*
+                }
+            }
         }
 
         #[cfg(feature = "tuner")]
@@ -48,9 +60,9 @@ macro_rules! parameters {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 $(
                     match stringify!($type) {
-                        "array" => crate::utils::tuner::parameters::print_array(f, &self.$param, $name)?,
-                        "pst" => crate::utils::tuner::parameters::print_pst(f, &self.$param, $name)?,
-                        "single" => crate::utils::tuner::parameters::print_single(f, &self.$param, $name)?,
+                        "array" => crate::utils::tuner::parameters::print_array(f, &self.$param, stringify!($name))?,
+                        "pst" => crate::utils::tuner::parameters::print_pst(f, &self.$param, stringify!($name))?,
+                        "single" => crate::utils::tuner::parameters::print_single(f, &self.$param, stringify!($name))?,
                         _ => unimplemented!()
                     }
                 )*
@@ -2,7 +2,10 @@
 mod macros;
 mod material;
 mod mobility_and_king_safety;
+#[cfg(feature = "nnue")]
+pub mod nnue;
 mod params;
+pub mod pawn_hash_table;
 pub mod pawn_structure;
 mod phased_eval;
 pub mod piece_square_tables;
@@ -17,29 +20,38 @@ use crate::chess::game::Game;
 use crate::chess::piece::{Piece, PieceKind};
 use crate::chess::player::ByPlayer;
 use crate::chess::player::Player;
-use crate::chess::square::Square;
+use crate::chess::square::{Rank, Square};
 pub use crate::engine::eval::phased_eval::PhasedEval;
+use pawn_hash_table::PawnHashTable;
 
 parameters!(
-    (material, PieceKind::N, array, "PIECE_VALUES"),
-    (pawn_pst, Square::N, pst, "PAWNS"),
-    (knight_pst, Square::N, pst, "KNIGHTS"),
-    (bishop_pst, Square::N, pst, "BISHOPS"),
-    (rook_pst, Square::N, pst, "ROOKS"),
-    (queen_pst, Square::N, pst, "QUEENS"),
-    (king_pst, Square::N, pst, "KING"),
-    (passed_pawn_pst, Square::N, pst, "PASSED_PAWNS"),
-    (knight_mobility, 9, array, "KNIGHT_MOBILITY"),
-    (bishop_mobility, 14, array, "BISHOP_MOBILITY"),
-    (rook_mobility, 15, array, "ROOK_MOBILITY"),
-    (queen_mobility, 28, array, "QUEEN_MOBILITY"),
-    (attacked_king_squares, 9, array, "ATTACKED_KING_SQUARES"),
-    (bishop_pair, 1, single, "BISHOP_PAIR_BONUS"),
+    (material, PieceKind::N, array, PIECE_VALUES),
+    (pawn_pst, Square::N, pst, PAWNS),
+    (knight_pst, Square::N, pst, KNIGHTS),
+    (bishop_pst, Square::N, pst, BISHOPS),
+    (rook_pst, Square::N, pst, ROOKS),
+    (queen_pst, Square::N, pst, QUEENS),
+    (king_pst, Square::N, pst, KING),
+    (passed_pawn_pst, Square::N, pst, PASSED_PAWNS),
+    (doubled_pawns, 1, single, DOUBLED_PAWN_PENALTY),
+    (isolated_pawns, 1, single, ISOLATED_PAWN_PENALTY),
+    (backward_pawns, 1, single, BACKWARD_PAWN_PENALTY),
+    (phalanx_pawns, Rank::N, array, PHALANX_PAWNS),
+    (supported_pawns, Rank::N, array, SUPPORTED_PAWNS),
+    (knight_mobility, 9, array, KNIGHT_MOBILITY),
+    (bishop_mobility, 14, array, BISHOP_MOBILITY),
+    (rook_mobility, 15, array, ROOK_MOBILITY),
+    (queen_mobility, 28, array, QUEEN_MOBILITY),
+    (attacked_king_squares, 9, array, ATTACKED_KING_SQUARES),
+    (bishop_pair, 1, single, BISHOP_PAIR_BONUS),
 );
 
 pub fn init() {
     piece_square_tables::init();
     pawn_structure::init();
+
+    #[cfg(feature = "nnue")]
+    nnue::init();
 }
 
 #[derive(Debug, Clone)]
@@ -47,17 +59,26 @@ pub struct IncrementalEvalFields {
     pub phase_value: i16,
 
     pub piece_square_tables: PhasedEval,
+
+    #[cfg(feature = "nnue")]
+    pub accumulators: nnue::Accumulators,
 }
 
 impl IncrementalEvalFields {
     pub fn set_at(&mut self, sq: Square, piece: Piece) {
         self.phase_value += phased_eval::piece_phase_value_contribution(piece.kind);
         self.piece_square_tables += piece_square_tables::piece_contributions(sq, piece);
+
+        #[cfg(feature = "nnue")]
+        self.accumulators.set_at(sq, piece);
     }
 
     pub fn remove_at(&mut self, sq: Square, piece: Piece) {
         self.phase_value -= phased_eval::piece_phase_value_contribution(piece.kind);
         self.piece_square_tables -= piece_square_tables::piece_contributions(sq, piece);
+
+        #[cfg(feature = "nnue")]
+        self.accumulators.remove_at(sq, piece);
     }
 }
 
@@ -70,20 +91,46 @@ impl IncrementalEvalFields {
             phase_value,
 
             piece_square_tables,
+
+            #[cfg(feature = "nnue")]
+            accumulators: nnue::Accumulators::init(board),
         }
     }
 }
 
-pub fn eval(game: &Game) -> Eval {
-    let absolute_eval = absolute_eval(game);
-    Eval::from_white_eval(absolute_eval, game.player)
-}
-
 pub fn absolute_eval(game: &Game) -> WhiteEval {
     let mut trace = Trace::new();
     absolute_eval_with_trace::<false>(game, &mut trace)
 }
 
+// Evaluate the position, using the pawn hash table to avoid recalculating the pawn structure
+// eval for pawn structures we've already seen.
+pub fn eval(game: &Game, pawn_hash_table: &PawnHashTable) -> Eval {
+    // When the 'nnue' feature is enabled and a network has been loaded, the search uses the
+    // network rather than the classical eval. The classical eval is still used for 'd eval' and by
+    // the tuner.
+    #[cfg(feature = "nnue")]
+    if nnue::is_loaded() {
+        return nnue::eval(game);
+    }
+
+    let pawn_structure_eval = if let Some(entry) = pawn_hash_table.get(&game.pawn_zobrist) {
+        entry.eval
+    } else {
+        let eval = pawn_structure::eval::<false>(game, &mut Trace::new());
+        pawn_hash_table.insert(
+            &game.pawn_zobrist,
+            &pawn_hash_table::PawnHashTableData { eval },
+        );
+        eval
+    };
+
+    let absolute_eval =
+        absolute_eval_with_pawn_structure::<false>(game, &mut Trace::new(), pawn_structure_eval);
+
+    Eval::from_white_eval(absolute_eval, game.player)
+}
+
 pub fn absolute_eval_with_trace<const TRACE: bool>(game: &Game, trace: &mut Trace) -> WhiteEval {
     if TRACE {
         // Material counts and PSTs are updated incrementally so if we're tuning we need
@@ -91,10 +138,20 @@ pub fn absolute_eval_with_trace<const TRACE: bool>(game: &Game, trace: &mut Trac
         material::trace_psts_and_material(game, trace);
     }
 
+    let pawn_structure_eval = pawn_structure::eval::<TRACE>(game, trace);
+
+    absolute_eval_with_pawn_structure::<TRACE>(game, trace, pawn_structure_eval)
+}
+
+fn absolute_eval_with_pawn_structure<const TRACE: bool>(
+    game: &Game,
+    trace: &mut Trace,
+    pawn_structure_eval: PhasedEval,
+) -> WhiteEval {
     let eval = game.incremental_eval.piece_square_tables
         + material::eval::<TRACE>(game, trace)
         + mobility_and_king_safety::eval::<TRACE>(game, trace)
-        + pawn_structure::eval::<TRACE>(game, trace);
+        + pawn_structure_eval;
 
     eval.for_phase(game.incremental_eval.phase_value)
 }
@@ -131,6 +188,11 @@ pub struct EvalComponents {
 
     pub piece_square: EvalComponent,
     pub passed_pawns: EvalComponent,
+    pub doubled_pawns: EvalComponent,
+    pub isolated_pawns: EvalComponent,
+    pub backward_pawns: EvalComponent,
+    pub phalanx_pawns: EvalComponent,
+    pub supported_pawns: EvalComponent,
 }
 
 pub fn eval_components(game: &Game) -> EvalComponents {
@@ -139,6 +201,16 @@ pub fn eval_components(game: &Game) -> EvalComponents {
 
     let piece_square_eval = piece_square_tables::eval_by_player(&game.board);
     let passed_pawns_eval = pawn_structure::eval_passed_pawns_by_player(&game.board);
+    let pawn_structure_terms = pawn_structure::eval_pawn_structure_by_player(&game.board);
+
+    let pawn_structure_term = |term: fn(&pawn_structure::PawnStructureTerms) -> PhasedEval| {
+        let phased_player_eval = ByPlayer::new(
+            term(pawn_structure_terms.white()),
+            term(pawn_structure_terms.black()),
+        );
+
+        EvalComponent::from_phased_eval(phased_player_eval, phase_value)
+    };
 
     EvalComponents {
         eval,
@@ -146,5 +218,10 @@ pub fn eval_components(game: &Game) -> EvalComponents {
 
         piece_square: EvalComponent::from_phased_eval(piece_square_eval, phase_value),
         passed_pawns: EvalComponent::from_phased_eval(passed_pawns_eval, phase_value),
+        doubled_pawns: pawn_structure_term(|terms| terms.doubled),
+        isolated_pawns: pawn_structure_term(|terms| terms.isolated),
+        backward_pawns: pawn_structure_term(|terms| terms.backward),
+        phalanx_pawns: pawn_structure_term(|terms| terms.phalanx),
+        supported_pawns: pawn_structure_term(|terms| terms.supported),
     }
 }
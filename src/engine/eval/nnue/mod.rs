@@ -0,0 +1,320 @@
+use crate::chess::board::Board;
+use crate::chess::game::Game;
+use crate::chess::piece::{Piece, PieceKind};
+use crate::chess::player::Player;
+use crate::chess::square::Square;
+use crate::engine::eval::{Eval, WhiteEval};
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::OnceLock;
+
+// Network architecture: (768 -> HIDDEN_SIZE)x2 -> 1
+//
+// Each side has its own accumulator, which sees the board from that side's perspective: our pieces
+// first, then the opponent's, with the board flipped for black. The output layer is applied to
+// the side to move's accumulator followed by the other side's.
+pub const HIDDEN_SIZE: usize = 128;
+const INPUT_SIZE: usize = 2 * PieceKind::N * Square::N;
+
+// Quantisation factors for the hidden and output layers
+const QA: i32 = 255;
+const QB: i32 = 64;
+
+// Converts the network's output into centipawns
+const SCALE: i32 = 400;
+
+// Keep network evaluations well away from mate scores
+const MAX_EVAL: i32 = 20000;
+
+// A placeholder network which reproduces the classical material and PST values, generated by
+// `etc/nnue/generate_placeholder_net.py`. It's weaker than the tuned classical eval, so the search
+// only switches to the network once a trained one is loaded with 'EvalFile'. Until then, the
+// placeholder is what the accumulators are built with.
+static DEFAULT_NETWORK_BYTES: &[u8] = include_bytes!("default.nnue");
+
+static DEFAULT_NETWORK: OnceLock<Box<Network>> = OnceLock::new();
+
+// The network loaded with 'EvalFile', if any. Search threads read the network without locking, so
+// a network is never freed once it's been loaded, in case another thread is still using it.
+// Networks are only loaded when the option is set, so very little memory is leaked.
+static LOADED_NETWORK: AtomicPtr<Network> = AtomicPtr::new(std::ptr::null_mut());
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(64))]
+pub struct Accumulator([i16; HIDDEN_SIZE]);
+
+impl Accumulator {
+    // These are written as simple loops over fixed size arrays so that the compiler can vectorise
+    // them.
+    #[inline(always)]
+    fn add(&mut self, weights: &Self) {
+        for (value, weight) in self.0.iter_mut().zip(&weights.0) {
+            *value += weight;
+        }
+    }
+
+    #[inline(always)]
+    fn sub(&mut self, weights: &Self) {
+        for (value, weight) in self.0.iter_mut().zip(&weights.0) {
+            *value -= weight;
+        }
+    }
+
+    #[inline(always)]
+    fn activate_and_dot(&self, weights: &Self) -> i32 {
+        self.0
+            .iter()
+            .zip(&weights.0)
+            .map(|(value, weight)| i32::from(*value).clamp(0, QA) * i32::from(*weight))
+            .sum()
+    }
+}
+
+#[repr(C)]
+struct Network {
+    feature_weights: [Accumulator; INPUT_SIZE],
+    feature_bias: Accumulator,
+    output_weights: [Accumulator; 2],
+    output_bias: i16,
+}
+
+impl Network {
+    const NUMBER_OF_PARAMETERS: usize =
+        INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1;
+
+    // The network is too large to build on the stack, so it's allocated on the heap and filled in
+    // there.
+    #[expect(
+        clippy::cast_ptr_alignment,
+        reason = "The allocation uses the alignment of the network"
+    )]
+    fn from_bytes(bytes: &[u8]) -> Result<Box<Self>, String> {
+        let layout = Layout::new::<Self>();
+
+        // !: The network has a non-zero size.
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) }.cast::<Self>();
+
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+
+        // !: The memory was allocated with the layout of a network, and the network is made up
+        // entirely of integers, for which all zeros is a valid value.
+        let mut network = unsafe { Box::from_raw(ptr) };
+
+        network.load_from_bytes(bytes)?;
+        Ok(network)
+    }
+
+    // Networks are stored as little-endian i16s, in the same order as the fields above. Some
+    // trainers pad the file to a multiple of 64 bytes, so we allow (and ignore) trailing padding.
+    fn load_from_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let expected_size = Self::NUMBER_OF_PARAMETERS * size_of::<i16>();
+
+        if bytes.len() < expected_size || bytes.len() - expected_size >= 64 {
+            return Err(format!(
+                "Expected a network of {expected_size} bytes, but got {} bytes",
+                bytes.len()
+            ));
+        }
+
+        let mut parameters = bytes
+            .chunks_exact(size_of::<i16>())
+            .map(|b| i16::from_le_bytes([b[0], b[1]]));
+
+        let accumulators = self
+            .feature_weights
+            .iter_mut()
+            .chain(std::iter::once(&mut self.feature_bias))
+            .chain(self.output_weights.iter_mut());
+
+        for accumulator in accumulators {
+            for value in &mut accumulator.0 {
+                *value = parameters.next().unwrap();
+            }
+        }
+
+        self.output_bias = parameters.next().unwrap();
+
+        Ok(())
+    }
+}
+
+fn default_network() -> &'static Network {
+    DEFAULT_NETWORK.get_or_init(|| {
+        Network::from_bytes(DEFAULT_NETWORK_BYTES).expect("The default network is invalid")
+    })
+}
+
+fn network() -> &'static Network {
+    let loaded = LOADED_NETWORK.load(Ordering::Acquire);
+
+    // !: Loaded networks are never freed, so the pointer is either null or valid forever.
+    unsafe { loaded.as_ref() }.unwrap_or_else(default_network)
+}
+
+pub fn init() {
+    default_network();
+}
+
+// Whether a network has been loaded with 'EvalFile', and so should be used by the search
+pub fn is_loaded() -> bool {
+    !LOADED_NETWORK.load(Ordering::Acquire).is_null()
+}
+
+// Replace the current network, or go back to the classical eval if the path is empty. Any
+// existing accumulators will need to be refreshed.
+pub fn load(path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        LOADED_NETWORK.store(std::ptr::null_mut(), Ordering::Release);
+        return Ok(());
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| format!("Unable to read {path}: {e}"))?;
+    let network = Network::from_bytes(&bytes)?;
+
+    LOADED_NETWORK.store(Box::into_raw(network), Ordering::Release);
+
+    Ok(())
+}
+
+fn feature_idx(perspective: Player, sq: Square, piece: Piece) -> usize {
+    let side = usize::from(piece.player != perspective);
+    let relative_sq = sq.relative_for(perspective);
+
+    (side * PieceKind::N + piece.kind.array_idx()) * Square::N + relative_sq.array_idx()
+}
+
+#[derive(Debug, Clone)]
+pub struct Accumulators([Accumulator; Player::N]);
+
+impl Accumulators {
+    pub fn init(board: &Board) -> Self {
+        let network = network();
+        let mut accumulators = Self([network.feature_bias; Player::N]);
+
+        for sq in board.occupancy() {
+            accumulators.set_at(sq, board.piece_at(sq).unwrap());
+        }
+
+        accumulators
+    }
+
+    #[inline(always)]
+    pub fn set_at(&mut self, sq: Square, piece: Piece) {
+        let network = network();
+
+        for perspective in [Player::White, Player::Black] {
+            let weights = &network.feature_weights[feature_idx(perspective, sq, piece)];
+            self.0[perspective.array_idx()].add(weights);
+        }
+    }
+
+    #[inline(always)]
+    pub fn remove_at(&mut self, sq: Square, piece: Piece) {
+        let network = network();
+
+        for perspective in [Player::White, Player::Black] {
+            let weights = &network.feature_weights[feature_idx(perspective, sq, piece)];
+            self.0[perspective.array_idx()].sub(weights);
+        }
+    }
+
+    fn evaluate(&self, player: Player) -> Eval {
+        let network = network();
+
+        let us = &self.0[player.array_idx()];
+        let them = &self.0[player.other().array_idx()];
+
+        let output = us.activate_and_dot(&network.output_weights[0])
+            + them.activate_and_dot(&network.output_weights[1])
+            + i32::from(network.output_bias);
+
+        let eval = (output * SCALE / (QA * QB)).clamp(-MAX_EVAL, MAX_EVAL);
+
+        Eval::new(i16::try_from(eval).unwrap())
+    }
+}
+
+pub fn eval(game: &Game) -> Eval {
+    let absolute_eval = absolute_eval(game);
+    Eval::from_white_eval(absolute_eval, game.player)
+}
+
+pub fn absolute_eval(game: &Game) -> WhiteEval {
+    let eval = game.incremental_eval.accumulators.evaluate(game.player);
+
+    match game.player {
+        Player::White => WhiteEval(eval.0),
+        Player::Black => WhiteEval(-eval.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::eval::pawn_hash_table::PawnHashTable;
+
+    #[test]
+    fn test_incremental_updates_match_refresh() {
+        crate::init();
+
+        let mut game =
+            Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        for _ in 0..4 {
+            let mv = *game.moves().last().unwrap();
+            game.make_move(mv);
+
+            let refreshed = Accumulators::init(&game.board);
+            assert_eq!(game.incremental_eval.accumulators.0[0].0, refreshed.0[0].0);
+            assert_eq!(game.incremental_eval.accumulators.0[1].0, refreshed.0[1].0);
+        }
+    }
+
+    #[test]
+    fn test_eval_is_symmetric() {
+        crate::init();
+
+        let white = Game::from_fen("4k3/pp3ppp/2n5/8/3P4/5N2/PP3PPP/4K3 w - - 0 1").unwrap();
+        let black = Game::from_fen("4k3/pp3ppp/5n2/3p4/8/2N5/PP3PPP/4K3 b - - 0 1").unwrap();
+
+        assert_eq!(eval(&white), eval(&black));
+        assert_eq!(eval(&Game::new()).0, 0);
+    }
+
+    #[test]
+    fn test_placeholder_network_values_material() {
+        crate::init();
+
+        // White is a rook up
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(eval(&game) > Eval::new(300));
+
+        // The same position, from black's point of view
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert!(eval(&game) < Eval::new(-300));
+        assert!(absolute_eval(&game) > WhiteEval(300));
+    }
+
+    #[test]
+    fn test_load_rejects_wrong_size() {
+        assert!(Network::from_bytes(&[0; 100]).is_err());
+        assert!(Network::from_bytes(DEFAULT_NETWORK_BYTES).is_ok());
+    }
+
+    #[test]
+    fn test_search_uses_classical_eval_without_eval_file() {
+        crate::init();
+
+        let game = Game::from_fen("4k3/pp3ppp/2n5/8/3P4/5N2/PP3PPP/4K3 w - - 0 1").unwrap();
+        let pawn_hash_table = PawnHashTable::new(1);
+
+        assert!(!is_loaded());
+        assert_eq!(
+            crate::engine::eval::eval(&game, &pawn_hash_table),
+            Eval::from_white_eval(crate::engine::eval::absolute_eval(&game), game.player)
+        );
+    }
+}
@@ -95,6 +95,34 @@ pub const PASSED_PAWNS: PieceSquareTableDefinition = [
     [s(    0,     0), s(    0,     0), s(    0,     0), s(    0,     0), s(    0,     0), s(    0,     0), s(    0,     0), s(    0,     0)],
 ];
 
+pub const DOUBLED_PAWN_PENALTY: PhasedEval = s(   -8,   -24);
+
+pub const ISOLATED_PAWN_PENALTY: PhasedEval = s(  -12,   -10);
+
+pub const BACKWARD_PAWN_PENALTY: PhasedEval = s(   -6,   -12);
+
+pub const PHALANX_PAWNS: [PhasedEval; 8] = [
+    s(    0,     0),
+    s(    4,    -2),
+    s(    8,     4),
+    s(   12,    10),
+    s(   22,    28),
+    s(   40,    70),
+    s(   80,   110),
+    s(    0,     0),
+];
+
+pub const SUPPORTED_PAWNS: [PhasedEval; 8] = [
+    s(    0,     0),
+    s(    0,     0),
+    s(   12,     8),
+    s(    8,     8),
+    s(   12,    16),
+    s(   28,    40),
+    s(   60,    80),
+    s(    0,     0),
+];
+
 pub const KNIGHT_MOBILITY: [PhasedEval; 9] = [
     s(  -89,   -19),
     s(   55,    79),
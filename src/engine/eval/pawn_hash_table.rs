@@ -0,0 +1,105 @@
+use crate::engine::eval::PhasedEval;
+use crate::engine::transposition_table::{TTOverwriteable, TTPackable, TranspositionTable};
+
+// The pawn structure rarely changes between positions in the search tree, so even a small table
+// gets a high hit rate.
+pub const PAWN_HASH_TABLE_SIZE_MB: usize = 2;
+
+const OCCUPIED: u64 = 1 << 32;
+
+#[derive(Debug, Clone)]
+pub struct PawnHashTableData {
+    pub eval: PhasedEval,
+}
+
+impl TTOverwriteable for PawnHashTableData {
+    fn should_overwrite_with(&self, _: &Self) -> bool {
+        // The pawn structure eval is the same however we reached a position, so there's no reason
+        // to prefer the existing entry.
+        true
+    }
+
+    fn replacement_priority(&self, _generation: u8) -> i32 {
+        // Every entry is as useful as any other, but if we always replaced the same slot then the
+        // rest of the bucket would keep the first positions we saw forever. Instead we evict a
+        // pseudo-random entry by hashing its contents.
+        (self.pack().wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 33) as i32
+    }
+}
+
+impl TTPackable for PawnHashTableData {
+    // An eval of zero would pack to zero, which the table treats as an empty slot, so we set a
+    // bit above the eval to mark the entry as occupied.
+    #[expect(clippy::cast_sign_loss, reason = "We're storing the bits of the eval")]
+    fn pack(&self) -> u64 {
+        u64::from(self.eval.midgame().0 as u16)
+            | u64::from(self.eval.endgame().0 as u16) << 16
+            | OCCUPIED
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        reason = "We're extracting fields that were packed into the entry"
+    )]
+    fn unpack(data: u64) -> Self {
+        Self {
+            eval: PhasedEval::new(data as u16 as i16, (data >> 16) as u16 as i16),
+        }
+    }
+}
+
+pub type PawnHashTable = TranspositionTable<PawnHashTableData>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::zobrist::ZobristHash;
+
+    #[test]
+    fn test_pack_and_unpack() {
+        for eval in [
+            PhasedEval::new(0, 0),
+            PhasedEval::new(-35, 120),
+            PhasedEval::new(250, -3),
+            PhasedEval::new(-1, -1),
+        ] {
+            let data = PawnHashTableData { eval };
+            assert_eq!(PawnHashTableData::unpack(data.pack()).eval, eval);
+        }
+    }
+
+    #[test]
+    fn test_zero_eval_is_stored() {
+        let table = PawnHashTable::new(1);
+        let key = ZobristHash(0x1234_5678_9abc_def0);
+
+        table.insert(
+            &key,
+            &PawnHashTableData {
+                eval: PhasedEval::new(0, 0),
+            },
+        );
+
+        assert_eq!(table.get(&key).unwrap().eval, PhasedEval::new(0, 0));
+    }
+
+    #[test]
+    fn test_replacement_uses_whole_bucket() {
+        let table = PawnHashTable::new(1);
+
+        // Keys which only differ in their lowest bits all map to the same bucket
+        let key = |i: u64| ZobristHash(i + 1);
+        let data = |i: u64| PawnHashTableData {
+            eval: PhasedEval::new(i16::try_from(i).unwrap(), -i16::try_from(i).unwrap()),
+        };
+
+        for i in 0..64 {
+            table.insert(&key(i), &data(i));
+        }
+
+        let surviving_early_entries = (0..3).filter(|&i| table.get(&key(i)).is_some()).count();
+        assert_eq!(surviving_early_entries, 0);
+        assert_eq!(table.get(&key(63)).unwrap().eval, data(63).eval);
+    }
+}
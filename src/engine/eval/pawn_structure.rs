@@ -9,7 +9,7 @@ use crate::engine::eval::piece_square_tables::{flatten, flip, negate, PieceSquar
 use crate::engine::eval::{params, PhasedEval, Trace, TraceComponentIncr};
 
 pub fn eval<const TRACE: bool>(game: &Game, trace: &mut Trace) -> PhasedEval {
-    let eval = eval_passed_pawns::<TRACE>(game, trace);
+    let eval = eval_passed_pawns::<TRACE>(game, trace) + eval_pawn_structure::<TRACE>(game, trace);
 
     eval
 }
@@ -17,6 +17,15 @@ pub fn eval<const TRACE: bool>(game: &Game, trace: &mut Trace) -> PhasedEval {
 static mut ENEMY_PASSED_PAWN_MASKS: [[Bitboard; Square::N]; Player::N] =
     [[Bitboard::EMPTY; Square::N]; Player::N];
 
+// The squares in front of a pawn on its own file
+static mut FRONT_SPANS: [[Bitboard; Square::N]; Player::N] =
+    [[Bitboard::EMPTY; Square::N]; Player::N];
+
+// The squares on adjacent files, level with or behind a pawn, where our pawns could advance to
+// support it
+static mut PAWN_SUPPORT_MASKS: [[Bitboard; Square::N]; Player::N] =
+    [[Bitboard::EMPTY; Square::N]; Player::N];
+
 static mut PASSED_PAWN_PST: [[PhasedEval; Square::N]; Player::N] =
     [[PhasedEval::ZERO; Square::N]; Player::N];
 
@@ -66,6 +75,34 @@ fn generate_passed_pawn_mask(player: Player, square: Square) -> Bitboard {
     enemy_pawns_mask
 }
 
+fn ranks_in_front(player: Player, rank: Rank) -> Bitboard {
+    let back_rank_idx = match player {
+        Player::White => Rank::R1,
+        Player::Black => Rank::R8,
+    };
+
+    let distance_from_back_rank = back_rank_idx.array_idx().abs_diff(rank.array_idx());
+
+    let mut relevant_ranks = Bitboard::FULL;
+
+    for _ in 0..=distance_from_back_rank {
+        relevant_ranks = relevant_ranks.forward(player);
+    }
+
+    relevant_ranks
+}
+
+fn generate_front_span(player: Player, square: Square) -> Bitboard {
+    square.file().bitboard() & ranks_in_front(player, square.rank())
+}
+
+fn generate_pawn_support_mask(player: Player, square: Square) -> Bitboard {
+    let file = square.file().bitboard();
+    let adjacent_files = file.west() | file.east();
+
+    adjacent_files & !ranks_in_front(player, square.rank())
+}
+
 fn enemy_passed_pawn_mask(player: Player, square: Square) -> Bitboard {
     *unsafe {
         ENEMY_PASSED_PAWN_MASKS
@@ -74,6 +111,22 @@ fn enemy_passed_pawn_mask(player: Player, square: Square) -> Bitboard {
     }
 }
 
+fn front_span(player: Player, square: Square) -> Bitboard {
+    *unsafe {
+        FRONT_SPANS
+            .get_unchecked(player.array_idx())
+            .get_unchecked(square.array_idx())
+    }
+}
+
+fn pawn_support_mask(player: Player, square: Square) -> Bitboard {
+    *unsafe {
+        PAWN_SUPPORT_MASKS
+            .get_unchecked(player.array_idx())
+            .get_unchecked(square.array_idx())
+    }
+}
+
 fn pst_value(player: Player, square: Square) -> PhasedEval {
     *unsafe {
         PASSED_PAWN_PST
@@ -123,12 +176,133 @@ fn calculate_passed_pawn_bonus<const TRACE: bool>(
     bonus
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct PawnStructureTerms {
+    pub doubled: PhasedEval,
+    pub isolated: PhasedEval,
+    pub backward: PhasedEval,
+    pub phalanx: PhasedEval,
+    pub supported: PhasedEval,
+}
+
+impl PawnStructureTerms {
+    const ZERO: Self = Self {
+        doubled: PhasedEval::ZERO,
+        isolated: PhasedEval::ZERO,
+        backward: PhasedEval::ZERO,
+        phalanx: PhasedEval::ZERO,
+        supported: PhasedEval::ZERO,
+    };
+
+    fn total(&self) -> PhasedEval {
+        self.doubled + self.isolated + self.backward + self.phalanx + self.supported
+    }
+}
+
+pub fn eval_pawn_structure<const TRACE: bool>(game: &Game, trace: &mut Trace) -> PhasedEval {
+    let white_terms = calculate_pawn_structure_terms::<TRACE>(&game.board, Player::White, trace);
+    let black_terms = calculate_pawn_structure_terms::<TRACE>(&game.board, Player::Black, trace);
+
+    white_terms.total() + black_terms.total()
+}
+
+pub fn eval_pawn_structure_by_player(board: &Board) -> ByPlayer<PawnStructureTerms> {
+    let white_terms =
+        calculate_pawn_structure_terms::<false>(board, Player::White, &mut Trace::new());
+    let black_terms =
+        calculate_pawn_structure_terms::<false>(board, Player::Black, &mut Trace::new());
+
+    ByPlayer::new(white_terms, black_terms)
+}
+
+fn calculate_pawn_structure_terms<const TRACE: bool>(
+    board: &Board,
+    player: Player,
+    trace: &mut Trace,
+) -> PawnStructureTerms {
+    let mut terms = PawnStructureTerms::ZERO;
+
+    let our_pawns = board.pawns(player);
+    let their_pawns = board.pawns(player.other());
+
+    let our_pawn_attacks = our_pawns.forward(player).west() | our_pawns.forward(player).east();
+    let their_pawn_attacks =
+        their_pawns.forward(player.other()).west() | their_pawns.forward(player.other()).east();
+
+    let phalanx_pawns = our_pawns & (our_pawns.west() | our_pawns.east());
+    let supported_pawns = our_pawns & our_pawn_attacks;
+
+    for pawn in our_pawns {
+        let file = pawn.file().bitboard();
+        let adjacent_files = file.west() | file.east();
+        let relative_rank = pawn.relative_for(player).rank().array_idx();
+
+        // Only the rearmost of the doubled pawns is penalised
+        if (front_span(player, pawn) & our_pawns).any() {
+            terms.doubled += params::DOUBLED_PAWN_PENALTY;
+
+            if TRACE {
+                trace.doubled_pawns.incr(player);
+            }
+        }
+
+        if (adjacent_files & our_pawns).is_empty() {
+            terms.isolated += params::ISOLATED_PAWN_PENALTY;
+
+            if TRACE {
+                trace.isolated_pawns.incr(player);
+            }
+        } else if (pawn_support_mask(player, pawn) & our_pawns).is_empty()
+            && their_pawn_attacks.contains(pawn.forward(player))
+        {
+            // None of our pawns can come up to support this pawn, and it can't advance without
+            // being captured
+            terms.backward += params::BACKWARD_PAWN_PENALTY;
+
+            if TRACE {
+                trace.backward_pawns.incr(player);
+            }
+        }
+
+        if phalanx_pawns.contains(pawn) {
+            terms.phalanx += params::PHALANX_PAWNS[relative_rank];
+
+            if TRACE {
+                trace.phalanx_pawns[relative_rank].incr(player);
+            }
+        }
+
+        if supported_pawns.contains(pawn) {
+            terms.supported += params::SUPPORTED_PAWNS[relative_rank];
+
+            if TRACE {
+                trace.supported_pawns[relative_rank].incr(player);
+            }
+        }
+    }
+
+    if player == Player::Black {
+        terms.doubled = -terms.doubled;
+        terms.isolated = -terms.isolated;
+        terms.backward = -terms.backward;
+        terms.phalanx = -terms.phalanx;
+        terms.supported = -terms.supported;
+    }
+
+    terms
+}
+
 pub fn init() {
     for player in [Player::White, Player::Black] {
         for square in Bitboard::FULL {
             let mask = generate_passed_pawn_mask(player, square);
+            let front_span = generate_front_span(player, square);
+            let support_mask = generate_pawn_support_mask(player, square);
+
             unsafe {
                 ENEMY_PASSED_PAWN_MASKS[player.array_idx()][square.array_idx()] = mask;
+                FRONT_SPANS[player.array_idx()][square.array_idx()] = front_span;
+                PAWN_SUPPORT_MASKS[player.array_idx()][square.array_idx()] = support_mask;
             }
         }
     }
@@ -194,4 +368,52 @@ mod tests {
             game.board.pawns(Player::White)
         ));
     }
+
+    #[test]
+    pub fn test_doubled_and_isolated_pawns() {
+        crate::init();
+
+        // White has doubled, isolated pawns on the c file. Black's h pawn is isolated.
+        let game = Game::from_fen("4k3/p6p/1p6/8/2P5/2P5/8/4K3 w - - 0 1").unwrap();
+
+        let mut white_trace = Trace::new();
+        calculate_pawn_structure_terms::<true>(&game.board, Player::White, &mut white_trace);
+
+        assert_eq!(white_trace.doubled_pawns[0].0, 1);
+        assert_eq!(white_trace.isolated_pawns[0].0, 2);
+
+        let mut black_trace = Trace::new();
+        calculate_pawn_structure_terms::<true>(&game.board, Player::Black, &mut black_trace);
+
+        assert_eq!(black_trace.doubled_pawns[0].0, 0);
+        assert_eq!(black_trace.isolated_pawns[0].0, -1);
+    }
+
+    #[test]
+    pub fn test_backward_pawns() {
+        crate::init();
+
+        // The d3 pawn can't be supported by the c4 or e4 pawns, and d4 is covered by the c5 pawn
+        let game = Game::from_fen("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+        let mut trace = Trace::new();
+
+        calculate_pawn_structure_terms::<true>(&game.board, Player::White, &mut trace);
+
+        assert_eq!(trace.backward_pawns[0].0, 1);
+        assert_eq!(trace.supported_pawns.iter().map(|c| c.0).sum::<i32>(), 2);
+    }
+
+    #[test]
+    pub fn test_phalanx_pawns() {
+        crate::init();
+
+        let game = Game::from_fen("4k3/8/3pp3/8/8/8/5PP1/4K3 w - - 0 1").unwrap();
+        let mut trace = Trace::new();
+
+        calculate_pawn_structure_terms::<true>(&game.board, Player::White, &mut trace);
+        calculate_pawn_structure_terms::<true>(&game.board, Player::Black, &mut trace);
+
+        assert_eq!(trace.phalanx_pawns[Rank::R2.array_idx()].0, 2);
+        assert_eq!(trace.phalanx_pawns[Rank::R3.array_idx()].0, -2);
+    }
 }
@@ -1,12 +1,14 @@
+pub mod book;
 pub mod eval;
 pub mod options;
 pub mod uci;
 pub mod util;
+pub mod xboard;
 
 pub mod see;
 
 pub mod search;
-mod tablebases;
+pub mod tablebases;
 pub mod transposition_table;
 
 pub fn init() {
@@ -1,19 +1,44 @@
 pub mod defaults {
     pub const HASH_SIZE: usize = 256;
     pub const THREADS: usize = 1;
+    pub const MULTI_PV: usize = 1;
+    pub const PONDER: bool = false;
+    pub const CHESS960: bool = false;
     pub const MOVE_OVERHEAD: usize = 0;
     pub const SYZYGY_PATH: Option<String> = None;
+    pub const OWN_BOOK: bool = false;
+    pub const BOOK_FILE: Option<String> = None;
+    #[cfg(feature = "nnue")]
+    pub const EVAL_FILE: Option<String> = None;
 }
 
 #[derive(Debug, Clone)]
 pub struct EngineOptions {
     pub hash_size: usize,
     pub threads: usize,
+    pub multi_pv: usize,
+
+    // Whether the GUI will ask us to ponder. This doesn't change how we search, since the GUI
+    // tells us when to ponder using 'go ponder'.
+    pub ponder: bool,
+
+    // Whether the GUI expects castling moves in Chess960 notation (king takes rook). We can play
+    // Chess960 positions either way, since the castling rooks are given in the FEN.
+    pub chess960: bool,
 
     // Account for the possibility that there's some overhead making the move
     // e.g. sending the best move over the internet.
     pub move_overhead: usize,
     pub syzygy_path: Option<String>,
+
+    // Whether to play moves from the opening book in `book_file` when one is available, rather
+    // than searching.
+    pub own_book: bool,
+    pub book_file: Option<String>,
+
+    // A trained network for the search to use instead of the classical eval
+    #[cfg(feature = "nnue")]
+    pub eval_file: Option<String>,
 }
 
 impl Default for EngineOptions {
@@ -21,8 +46,15 @@ impl Default for EngineOptions {
         Self {
             hash_size: defaults::HASH_SIZE,
             threads: defaults::THREADS,
+            multi_pv: defaults::MULTI_PV,
+            ponder: defaults::PONDER,
+            chess960: defaults::CHESS960,
             move_overhead: defaults::MOVE_OVERHEAD,
             syzygy_path: defaults::SYZYGY_PATH,
+            own_book: defaults::OWN_BOOK,
+            book_file: defaults::BOOK_FILE,
+            #[cfg(feature = "nnue")]
+            eval_file: defaults::EVAL_FILE,
         }
     }
 }
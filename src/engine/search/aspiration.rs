@@ -10,12 +10,14 @@ struct Window {
     width: Eval,
 }
 
-fn clamp_alpha(eval: Eval) -> Eval {
-    std::cmp::max(Eval::MIN, eval)
+// Mate scores are close to the limits of an Eval, so the window saturates rather than overflowing
+// as it widens
+fn lower(eval: Eval, width: Eval) -> Eval {
+    Eval(eval.0.saturating_sub(width.0))
 }
 
-fn clamp_beta(eval: Eval) -> Eval {
-    std::cmp::min(Eval::MAX, eval)
+fn raise(eval: Eval, width: Eval) -> Eval {
+    Eval(eval.0.saturating_add(width.0))
 }
 
 impl Window {
@@ -30,8 +32,8 @@ impl Window {
 
     pub fn around(eval: Eval, width: Eval) -> Self {
         Self {
-            alpha: clamp_alpha(eval - width),
-            beta: clamp_beta(eval + width),
+            alpha: lower(eval, width),
+            beta: raise(eval, width),
 
             width,
         }
@@ -39,16 +41,16 @@ impl Window {
 
     pub fn widen_down(&mut self) {
         self.increase_window_widening_rate();
-        self.alpha = clamp_alpha(self.alpha - self.width);
+        self.alpha = lower(self.alpha, self.width);
     }
 
     pub fn widen_up(&mut self) {
         self.increase_window_widening_rate();
-        self.beta = clamp_beta(self.beta + self.width);
+        self.beta = raise(self.beta, self.width);
     }
 
     fn increase_window_widening_rate(&mut self) {
-        self.width = self.width + self.width / 2;
+        self.width = raise(self.width, self.width / 2);
     }
 }
 
@@ -59,10 +61,10 @@ pub fn aspiration_search(
     pv: &mut PrincipalVariation,
     ctx: &mut SearchContext<'_>,
 ) -> Result<Eval, ()> {
-    let mut window = if depth < params::ASPIRATION_MIN_DEPTH {
+    let mut window = if depth < params::aspiration_min_depth() {
         Window::no_window()
     } else {
-        Window::around(eval.unwrap(), params::ASPIRATION_WINDOW_SIZE)
+        Window::around(eval.unwrap(), params::aspiration_window_size())
     };
 
     loop {
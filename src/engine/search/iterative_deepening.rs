@@ -8,6 +8,11 @@ use crate::engine::search::{
 };
 use crate::engine::util;
 
+struct Line {
+    eval: Eval,
+    pv: PrincipalVariation,
+}
+
 pub fn search(
     game: &mut Game,
     ctx: &mut SearchContext<'_>,
@@ -15,48 +20,99 @@ pub fn search(
     reporter: &mut impl Reporter,
 ) -> Option<Move> {
     let mut best_move: Option<Move> = None;
-    let mut overall_eval: Option<Eval> = None;
+
+    // The evaluation of each line at the previous depth, used to centre the aspiration windows
+    let mut line_evals: Vec<Option<Eval>> = Vec::new();
 
     let max_search_depth = ctx.search_restrictions.depth.unwrap_or(MAX_SEARCH_DEPTH);
     ctx.max_depth_reached = 0;
 
-    for depth in 1..=max_search_depth {
-        if !ctx.time_control.should_start_new_search(depth) {
+    // We can't show more lines than there are moves we're allowed to search
+    let number_of_root_moves = game
+        .moves()
+        .iter()
+        .filter(|mv| ctx.search_restrictions.is_root_move_allowed(**mv))
+        .count();
+
+    let number_of_lines = ctx.options.multi_pv.clamp(1, number_of_root_moves.max(1));
+    line_evals.resize(number_of_lines, None);
+
+    'deepening: for depth in 1..=max_search_depth {
+        let best_eval = line_evals.first().copied().flatten();
+
+        if !ctx.time_control.should_start_new_search(
+            depth,
+            ctx.total_nodes_visited(),
+            best_eval,
+            ctx.search_restrictions,
+        ) {
             break;
         }
 
-        let Ok(eval) = aspiration_search(game, depth, overall_eval, pv, ctx) else {
-            break;
-        };
+        let mut lines: Vec<Line> = Vec::with_capacity(number_of_lines);
+        ctx.excluded_root_moves.clear();
+
+        for line_eval in &line_evals {
+            let mut line_pv = PrincipalVariation::new();
+
+            let Ok(eval) = aspiration_search(game, depth, *line_eval, &mut line_pv, ctx) else {
+                break 'deepening;
+            };
+
+            // Once we've searched the main line, we have a full search of the best move at this
+            // depth so we can use it even if we later run out of time searching the other lines.
+            if lines.is_empty() {
+                *pv = line_pv.clone();
+                best_move = Some(*pv.first().unwrap());
+            }
 
-        let score = if let Some(nmoves) = eval.is_mate_in_moves() {
-            SearchScore::Mate(nmoves)
-        } else {
-            SearchScore::Centipawns(eval.0)
-        };
+            // Search subsequent lines without the moves we've already found
+            ctx.excluded_root_moves.push(*line_pv.first().unwrap());
 
+            lines.push(Line { eval, pv: line_pv });
+        }
+
+        ctx.excluded_root_moves.clear();
+
+        // Fail-soft results for later lines may be better than earlier ones, so make sure we
+        // present the lines in order.
+        lines.sort_by_key(|line| std::cmp::Reverse(line.eval));
+
+        *pv = lines[0].pv.clone();
         best_move = Some(*pv.first().unwrap());
-        overall_eval = Some(eval);
-
-        reporter.report_search_progress(
-            game,
-            SearchInfo {
-                depth,
-                seldepth: ctx.max_depth_reached,
-                score,
-                pv: pv.clone(),
-                hashfull: ctx.tt.occupancy(),
-                stats: SearchStats {
-                    time: ctx.time_control.elapsed(),
-                    nodes: ctx.nodes_visited,
-                    nodes_per_second: util::metrics::nodes_per_second(
-                        ctx.nodes_visited,
-                        ctx.time_control.elapsed(),
-                    ),
-                    tbhits: ctx.tbhits,
+
+        for (line_eval, line) in line_evals.iter_mut().zip(&lines) {
+            *line_eval = Some(line.eval);
+        }
+
+        for (idx, line) in lines.into_iter().enumerate() {
+            let score = if let Some(nmoves) = line.eval.is_mate_in_moves() {
+                SearchScore::Mate(nmoves)
+            } else {
+                SearchScore::Centipawns(line.eval.0)
+            };
+
+            reporter.report_search_progress(
+                game,
+                SearchInfo {
+                    depth,
+                    seldepth: ctx.max_depth_reached,
+                    multipv: idx + 1,
+                    score,
+                    pv: line.pv,
+                    hashfull: ctx.tt.hashfull(),
+                    stats: SearchStats {
+                        time: ctx.time_control.elapsed(),
+                        nodes: ctx.total_nodes_visited(),
+                        nodes_per_second: util::metrics::nodes_per_second(
+                            ctx.total_nodes_visited(),
+                            ctx.time_control.elapsed(),
+                        ),
+                        tbhits: ctx.total_tbhits(),
+                    },
                 },
-            },
-        );
+            );
+        }
     }
 
     best_move
@@ -0,0 +1,54 @@
+// Declares search parameters which can be tuned with SPSA.
+//
+// Each parameter is accessed through a function of the same name. Normally, that function returns
+// a constant. With the 'spsa' feature, the value can be changed at runtime through a UCI option.
+//
+// Parameters are given as (name, type, default, min, max, step), where min, max and step are in
+// the integer representation of the value that is used for UCI options (see `TunableValue`).
+macro_rules! tunable {
+    (
+        $(
+            ($name:ident, $type:ty, $default:expr, $min:expr, $max:expr, $step:expr)
+        ),* $(,)?
+    ) => {
+        $(
+            #[cfg(not(feature = "spsa"))]
+            #[inline(always)]
+            pub const fn $name() -> $type {
+                $default
+            }
+
+            #[cfg(feature = "spsa")]
+            #[inline(always)]
+            pub fn $name() -> $type {
+                let value = VALUES[Tunable::$name as usize].load(std::sync::atomic::Ordering::Relaxed);
+                <$type as TunableValue>::from_raw(value)
+            }
+        )*
+
+        #[cfg(feature = "spsa")]
+        #[expect(non_camel_case_types, reason = "Variants are named after their parameters")]
+        enum Tunable {
+            $($name,)*
+        }
+
+        #[cfg(feature = "spsa")]
+        static VALUES: [std::sync::atomic::AtomicI32; [$(stringify!($name)),*].len()] =
+            [const { std::sync::atomic::AtomicI32::new(0) }; [$(stringify!($name)),*].len()];
+
+        #[cfg(feature = "spsa")]
+        pub fn tunables() -> Vec<TunableParameter> {
+            vec![
+                $(
+                    TunableParameter {
+                        name: stringify!($name),
+                        default: <$type as TunableValue>::to_raw($default),
+                        min: $min,
+                        max: $max,
+                        step: $step,
+                    },
+                )*
+            ]
+        }
+    };
+}
@@ -1,5 +1,7 @@
 use crate::chess::game::Game;
 use crate::chess::moves::Move;
+use crate::engine::book::OpeningBook;
+use crate::engine::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE_MB};
 use crate::engine::eval::Eval;
 use crate::engine::options::EngineOptions;
 use crate::engine::search::move_picker::MovePicker;
@@ -9,13 +11,18 @@ use crate::engine::search::time_control::TimeStrategy;
 use crate::engine::search::transposition::SearchTranspositionTable;
 use crate::engine::tablebases::{Tablebase, Wdl};
 use crate::engine::util;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+#[macro_use]
+mod macros;
+
 mod aspiration;
 mod iterative_deepening;
 mod move_ordering;
 pub mod move_picker;
 mod negamax;
+pub mod params;
 mod principal_variation;
 mod quiescence;
 mod tables;
@@ -25,89 +32,131 @@ pub mod transposition;
 const MAX_SEARCH_DEPTH: u8 = u8::MAX;
 const MAX_SEARCH_DEPTH_SIZE: usize = MAX_SEARCH_DEPTH as usize;
 
-mod params {
-    use crate::engine::eval::Eval;
-
-    pub const CHECK_TERMINATION_NODE_FREQUENCY: u64 = 10000;
-
-    pub const ASPIRATION_MIN_DEPTH: u8 = 5;
-    pub const ASPIRATION_WINDOW_SIZE: Eval = Eval::new(25);
-
-    pub const NULL_MOVE_PRUNING_DEPTH_LIMIT: u8 = 3;
-    pub const NULL_MOVE_PRUNING_DEPTH_REDUCTION: u8 = 2;
-
-    pub const FUTILITY_PRUNE_DEPTH: u8 = 1;
-    pub const FUTILITY_PRUNE_MAX_MOVE_VALUE: Eval = Eval::new(135);
-
-    pub const REVERSE_FUTILITY_PRUNE_DEPTH: u8 = 4;
-    pub const REVERSE_FUTILITY_PRUNE_MARGIN_PER_PLY: Eval = Eval::new(150);
-
-    pub const LMR_DEPTH: u8 = 3;
-    pub const LMR_MOVE_THRESHOLD: usize = 3;
-
-    pub const HISTORY_DECAY_FACTOR: i32 = 8;
-
-    pub const MAX_TIME_PER_MOVE: f32 = 0.5;
-    pub const INCREMENT_TO_USE: f32 = 0.5;
-    pub const BASE_TIME_PER_MOVE: f32 = 0.033;
-
-    pub const SOFT_TIME_MULTIPLIER: f32 = 0.75;
-    pub const HARD_TIME_MULTIPLIER: f32 = 3.00;
-}
-
 pub struct PersistentState {
     pub tt: SearchTranspositionTable,
+    pub pawn_hash_table: PawnHashTable,
     pub history_table: HistoryTable,
     pub tablebase: Tablebase,
+    pub book: OpeningBook,
+
+    thread_counters: Vec<ThreadCounters>,
 }
 
 impl PersistentState {
     pub fn new(tt_size_mb: usize) -> Self {
         Self {
             tt: SearchTranspositionTable::new(tt_size_mb),
+            pawn_hash_table: PawnHashTable::new(PAWN_HASH_TABLE_SIZE_MB),
             history_table: HistoryTable::new(),
             tablebase: Tablebase::new(),
+            book: OpeningBook::new(),
+
+            thread_counters: vec![ThreadCounters::default()],
         }
     }
 
     pub fn reset(&mut self) {
         self.tt.reset();
+        self.pawn_hash_table.reset();
         self.history_table.reset();
     }
+
+    // Split the state into the parts that are shared between all search threads, and the parts
+    // which only the main thread can use.
+    fn split(&mut self) -> (SharedState<'_>, &mut HistoryTable) {
+        (
+            SharedState {
+                tt: &self.tt,
+                pawn_hash_table: &self.pawn_hash_table,
+                tablebase: &self.tablebase,
+                thread_counters: &self.thread_counters,
+            },
+            &mut self.history_table,
+        )
+    }
+}
+
+// Each search thread publishes its node and tablebase hit counts on their own cache line, so that
+// the main thread can report the totals without the search threads contending over shared counters.
+#[derive(Default)]
+#[repr(align(64))]
+struct ThreadCounters {
+    nodes: AtomicU64,
+    tbhits: AtomicU64,
+}
+
+#[derive(Clone, Copy)]
+struct SharedState<'s> {
+    tt: &'s SearchTranspositionTable,
+    pawn_hash_table: &'s PawnHashTable,
+    tablebase: &'s Tablebase,
+    thread_counters: &'s [ThreadCounters],
 }
 
 pub struct SearchContext<'s> {
-    pub tt: &'s mut SearchTranspositionTable,
-    pub tablebase: &'s mut Tablebase,
+    pub tt: &'s SearchTranspositionTable,
+    pub pawn_hash_table: &'s PawnHashTable,
+    pub tablebase: &'s Tablebase,
 
     pub history_table: &'s mut HistoryTable,
 
     pub time_control: &'s mut TimeStrategy,
 
-    #[expect(unused, reason = "No options currently used in search")]
     pub options: &'s EngineOptions,
     pub search_restrictions: &'s SearchRestrictions,
 
     pub killer_moves: KillersTable,
     pub countermove_table: CountermoveTable,
 
+    // Moves at the root which have already been reported as a line in MultiPV mode
+    pub excluded_root_moves: Vec<Move>,
+
+    // The move to skip at each ply while verifying whether the TT move is singular
+    pub excluded_moves: [Option<Move>; MAX_SEARCH_DEPTH_SIZE],
+
+    thread_id: usize,
+    thread_counters: &'s [ThreadCounters],
+
     nodes_visited: u64,
     max_depth_reached: u8,
     tbhits: u64,
 }
 
 impl<'s> SearchContext<'s> {
-    pub const fn new(
+    // A single-threaded search context, for testing components of the search in isolation
+    #[cfg(test)]
+    pub fn new(
         persistent_state: &'s mut PersistentState,
         time_strategy: &'s mut TimeStrategy,
         options: &'s EngineOptions,
         search_restrictions: &'s SearchRestrictions,
+    ) -> Self {
+        let (shared_state, history_table) = persistent_state.split();
+
+        Self::for_thread(
+            0,
+            shared_state,
+            history_table,
+            time_strategy,
+            options,
+            search_restrictions,
+        )
+    }
+
+    fn for_thread(
+        thread_id: usize,
+        shared_state: SharedState<'s>,
+        history_table: &'s mut HistoryTable,
+        time_strategy: &'s mut TimeStrategy,
+        options: &'s EngineOptions,
+        search_restrictions: &'s SearchRestrictions,
     ) -> Self {
         Self {
-            tt: &mut persistent_state.tt,
-            tablebase: &mut persistent_state.tablebase,
+            tt: shared_state.tt,
+            pawn_hash_table: shared_state.pawn_hash_table,
+            tablebase: shared_state.tablebase,
 
-            history_table: &mut persistent_state.history_table,
+            history_table,
 
             time_control: time_strategy,
 
@@ -117,11 +166,60 @@ impl<'s> SearchContext<'s> {
             killer_moves: KillersTable::new(),
             countermove_table: CountermoveTable::new(),
 
+            excluded_root_moves: Vec::new(),
+            excluded_moves: [None; MAX_SEARCH_DEPTH_SIZE],
+
+            thread_id,
+            thread_counters: shared_state.thread_counters,
+
             max_depth_reached: 0,
             nodes_visited: 0,
             tbhits: 0,
         }
     }
+
+    fn visit_node(&mut self) {
+        self.nodes_visited += 1;
+        self.thread_counters[self.thread_id]
+            .nodes
+            .store(self.nodes_visited, Ordering::Relaxed);
+    }
+
+    fn visit_tablebase(&mut self) {
+        self.tbhits += 1;
+        self.thread_counters[self.thread_id]
+            .tbhits
+            .store(self.tbhits, Ordering::Relaxed);
+    }
+
+    // The number of nodes visited by all search threads
+    fn total_nodes_visited(&self) -> u64 {
+        self.thread_counters
+            .iter()
+            .map(|c| c.nodes.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    // The number of tablebase hits by all search threads
+    fn total_tbhits(&self) -> u64 {
+        self.thread_counters
+            .iter()
+            .map(|c| c.tbhits.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    // Node limits apply to the nodes searched by all threads together, so we only need to sum
+    // every thread's count if one has been set.
+    fn should_stop(&mut self) -> bool {
+        let nodes_visited = if self.search_restrictions.nodes.is_some() {
+            self.total_nodes_visited()
+        } else {
+            self.nodes_visited
+        };
+
+        self.time_control
+            .should_stop(nodes_visited, self.search_restrictions)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -133,6 +231,21 @@ pub enum SearchScore {
 #[derive(Default)]
 pub struct SearchRestrictions {
     pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+
+    // Stop searching once we find a mate in this many moves
+    pub mate: Option<u8>,
+
+    // If set, only these moves will be considered at the root
+    pub search_moves: Option<Vec<Move>>,
+}
+
+impl SearchRestrictions {
+    pub fn is_root_move_allowed(&self, mv: Move) -> bool {
+        self.search_moves
+            .as_ref()
+            .is_none_or(|search_moves| search_moves.contains(&mv))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -154,6 +267,7 @@ pub struct Clocks {
 pub struct SearchInfo {
     pub depth: u8,
     pub seldepth: u8,
+    pub multipv: usize,
     pub score: SearchScore,
     pub stats: SearchStats,
     pub pv: PrincipalVariation,
@@ -172,6 +286,8 @@ pub trait Reporter {
 
     fn report_search_progress(&mut self, game: &Game, progress: SearchInfo);
 
+    fn book_move(&self, game: &Game, mv: Move);
+
     fn best_move(&self, game: &Game, mv: Move);
 }
 
@@ -182,6 +298,8 @@ impl Reporter for NullReporter {
 
     fn report_search_progress(&mut self, _: &Game, _: SearchInfo) {}
 
+    fn book_move(&self, _: &Game, _: Move) {}
+
     fn best_move(&self, _: &Game, _: Move) {}
 }
 
@@ -203,10 +321,16 @@ impl Reporter for CapturingReporter {
     fn generic_report(&self, _: &str) {}
 
     fn report_search_progress(&mut self, _: &Game, stats: SearchInfo) {
+        if stats.multipv != 1 {
+            return;
+        }
+
         self.score = Some(stats.score);
         self.nodes = stats.stats.nodes;
     }
 
+    fn book_move(&self, _: &Game, _: Move) {}
+
     fn best_move(&self, _: &Game, _: Move) {}
 }
 
@@ -218,21 +342,38 @@ pub fn search(
     options: &EngineOptions,
     reporter: &mut impl Reporter,
 ) -> Move {
-    let mut ctx = SearchContext::new(
-        persistent_state,
-        time_strategy,
-        options,
-        search_restrictions,
-    );
+    // We only play book moves when we're expected to move by ourselves. When pondering or
+    // searching infinitely we can't report a move until we're told to, so we search as normal.
+    if options.own_book && time_strategy.is_time_limited() {
+        let book_move = persistent_state
+            .book
+            .pick_move(game)
+            .filter(|mv| search_restrictions.is_root_move_allowed(*mv));
+
+        if let Some(mv) = book_move {
+            reporter.book_move(game, mv);
+            return mv;
+        }
+    }
 
-    ctx.tt.new_generation();
-    ctx.history_table.decay(params::HISTORY_DECAY_FACTOR);
+    persistent_state.tt.new_generation();
+    persistent_state
+        .history_table
+        .decay(params::history_decay_factor());
 
-    let mut pv = PrincipalVariation::new();
+    persistent_state.thread_counters = (0..options.threads.max(1))
+        .map(|_| ThreadCounters::default())
+        .collect();
+
+    let (shared_state, history_table) = persistent_state.split();
+
+    let tablebase_result = shared_state
+        .tablebase
+        .best_move(game)
+        .filter(|mv| search_restrictions.is_root_move_allowed(*mv));
 
-    let tablebase_result = ctx.tablebase.best_move(game);
     if let Some(mv) = tablebase_result {
-        let (pv, score) = get_tablebase_pv(game, &ctx);
+        let (pv, score) = get_tablebase_pv(game, shared_state.tablebase);
 
         let depth = pv.len();
 
@@ -241,9 +382,10 @@ pub fn search(
             SearchInfo {
                 depth,
                 seldepth: depth,
+                multipv: 1,
                 score,
                 pv,
-                hashfull: persistent_state.tt.occupancy(),
+                hashfull: shared_state.tt.hashfull(),
                 stats: SearchStats {
                     time: time_strategy.elapsed(),
                     nodes: u64::from(depth),
@@ -259,22 +401,67 @@ pub fn search(
         return mv;
     }
 
-    iterative_deepening::search(
-        // Give the search its own copy of the game so we don't get one returned in a dirty state
-        // when the search aborts.
-        &mut game.clone(),
-        &mut ctx,
-        &mut pv,
-        reporter,
-    );
+    let mut pv = PrincipalVariation::new();
+
+    // Lazy SMP: helper threads run the same iterative deepening search as the main thread, and
+    // only communicate with it through the shared transposition table. The helpers' results are
+    // never used directly - they just fill the table with results that the main thread can use.
+    std::thread::scope(|s| {
+        for thread_id in 1..shared_state.thread_counters.len() {
+            let mut helper_time_strategy = time_strategy.clone();
+            let mut helper_history_table = history_table.clone();
+
+            s.spawn(move || {
+                let mut ctx = SearchContext::for_thread(
+                    thread_id,
+                    shared_state,
+                    &mut helper_history_table,
+                    &mut helper_time_strategy,
+                    options,
+                    search_restrictions,
+                );
+
+                iterative_deepening::search(
+                    &mut game.clone(),
+                    &mut ctx,
+                    &mut PrincipalVariation::new(),
+                    &mut NullReporter,
+                );
+            });
+        }
+
+        let mut ctx = SearchContext::for_thread(
+            0,
+            shared_state,
+            history_table,
+            time_strategy,
+            options,
+            search_restrictions,
+        );
 
-    let best_move = pv.first().copied();
+        iterative_deepening::search(
+            // Give the search its own copy of the game so we don't get one returned in a dirty state
+            // when the search aborts.
+            &mut game.clone(),
+            &mut ctx,
+            &mut pv,
+            reporter,
+        );
 
-    best_move.unwrap_or_else(|| panic_move(game, &ctx))
+        // Once the main thread has finished, the helpers should stop too.
+        ctx.time_control.stop();
+
+        let best_move = pv.first().copied();
+
+        best_move.unwrap_or_else(|| panic_move(game, &ctx))
+    })
 }
 
 pub fn init() {
     tables::init();
+
+    #[cfg(feature = "spsa")]
+    params::init();
 }
 
 // If we have so little time to search that we couldn't determine a best move, we'll need to spend
@@ -286,22 +473,20 @@ fn panic_move(game: &Game, ctx: &SearchContext<'_>) -> Move {
     move_picker.next(game, ctx, 0).unwrap()
 }
 
-fn get_tablebase_pv(game: &Game, ctx: &SearchContext<'_>) -> (PrincipalVariation, SearchScore) {
+fn get_tablebase_pv(game: &Game, tablebase: &Tablebase) -> (PrincipalVariation, SearchScore) {
     let mut game = game.clone();
     let player = game.player;
 
     let mut pv = PrincipalVariation::new();
 
-    let tb_score = ctx
-        .tablebase
+    let tb_score = tablebase
         .wdl(&game)
         .expect("In tablebase position, but unable to get tablebase score");
 
     let mut search_score = None;
 
     for _ in 0..MAX_SEARCH_DEPTH {
-        let tablebase_move = ctx
-            .tablebase
+        let tablebase_move = tablebase
             .best_move(&game)
             .expect("In tablebase position, but unable to get tablebase move");
 
@@ -70,6 +70,19 @@ impl MovePicker {
     }
 
     pub fn next(&mut self, game: &Game, ctx: &SearchContext<'_>, plies: u8) -> Option<Move> {
+        loop {
+            let mv = self.next_move(game, ctx, plies)?;
+
+            // At the root, we may have been asked to only consider specific moves
+            if plies == 0 && !ctx.search_restrictions.is_root_move_allowed(mv) {
+                continue;
+            }
+
+            return Some(mv);
+        }
+    }
+
+    fn next_move(&mut self, game: &Game, ctx: &SearchContext<'_>, plies: u8) -> Option<Move> {
         use GenStage::*;
 
         if self.stage == BestMove {
@@ -1,6 +1,6 @@
 use super::{params, SearchContext, MAX_SEARCH_DEPTH};
 use crate::chess::game::Game;
-use crate::chess::moves::Move;
+use crate::chess::movegen;
 use crate::engine::eval;
 use crate::engine::eval::Eval;
 use crate::engine::search::move_picker::MovePicker;
@@ -45,7 +45,7 @@ pub fn negamax(
 
     // Check periodically to see if we're out of time. If we are, we shouldn't continue the search
     // so we return Err to signal to the caller that the search did not complete.
-    if ctx.time_control.should_stop(ctx.nodes_visited) {
+    if ctx.should_stop() {
         return Err(());
     }
 
@@ -71,13 +71,23 @@ pub fn negamax(
     }
 
     if !is_root {
-        ctx.nodes_visited += 1;
+        ctx.visit_node();
     }
 
-    let mut previous_best_move: Option<Move> = None;
+    // If we're verifying whether a move is singular, we're searching this position without that
+    // move. The result isn't the true value of the position, so we mustn't use or store TT scores
+    // for it.
+    let excluded_move = ctx.excluded_moves[plies as usize];
 
-    if let Some(tt_entry) = ctx.tt.get(&game.zobrist) {
-        if !is_root && !is_pv && tt_entry.depth >= depth {
+    // Only part of the key is stored in each TT entry, so very occasionally we'll get an entry for a
+    // different position. Its move might not be legal here, and we can't play it.
+    let tt_entry = ctx.tt.get(&game.zobrist).map(|mut entry| {
+        entry.best_move = entry.best_move.filter(|mv| movegen::is_legal(game, *mv));
+        entry
+    });
+
+    if let Some(tt_entry) = &tt_entry {
+        if !is_root && !is_pv && excluded_move.is_none() && tt_entry.depth >= depth {
             let tt_score = tt_entry.eval.with_mate_distance_from_root(plies);
 
             match tt_entry.bound {
@@ -87,17 +97,17 @@ pub fn negamax(
                 _ => {}
             }
         }
-
-        previous_best_move = tt_entry.best_move;
     }
 
+    let previous_best_move = tt_entry.as_ref().and_then(|e| e.best_move);
+
     let tb_cardinality = ctx.tablebase.n_men();
-    if !is_root && tb_cardinality > 0 {
+    if !is_root && excluded_move.is_none() && tb_cardinality > 0 {
         let piece_count = game.board.occupancy().count();
 
         if piece_count < tb_cardinality || (piece_count <= tb_cardinality && depth >= 1) {
             if let Some(wdl) = ctx.tablebase.wdl(game) {
-                ctx.tbhits += 1;
+                ctx.visit_tablebase();
 
                 let score = match wdl {
                     Wdl::Win => Eval::mate_in(plies),
@@ -118,12 +128,13 @@ pub fn negamax(
                     let tt_data = SearchTranspositionTableData {
                         bound: tb_bound,
                         eval: score,
+                        static_eval: tt_entry.as_ref().and_then(|e| e.static_eval),
                         best_move: None,
                         age: ctx.tt.generation,
                         depth,
                     };
 
-                    ctx.tt.insert(&game.zobrist, tt_data);
+                    ctx.tt.insert(&game.zobrist, &tt_data);
 
                     return Ok(score);
                 }
@@ -135,18 +146,25 @@ pub fn negamax(
         }
     }
 
-    let eval = eval::eval(game);
+    let static_eval = tt_entry
+        .as_ref()
+        .and_then(|e| e.static_eval)
+        .unwrap_or_else(|| eval::eval(game, ctx.pawn_hash_table));
 
-    if !is_root && !is_pv && !in_check {
+    let eval = tt_entry
+        .as_ref()
+        .map_or(static_eval, |e| e.adjust_static_eval(static_eval));
+
+    if !is_root && !is_pv && !in_check && excluded_move.is_none() {
         // Reverse futility pruning
-        if depth <= params::REVERSE_FUTILITY_PRUNE_DEPTH
-            && eval - params::REVERSE_FUTILITY_PRUNE_MARGIN_PER_PLY * i16::from(depth) > beta
+        if depth <= params::reverse_futility_prune_depth()
+            && eval - params::reverse_futility_prune_margin_per_ply() * i16::from(depth) > beta
         {
             return Ok(beta);
         }
 
         // Null move pruning
-        if depth >= params::NULL_MOVE_PRUNING_DEPTH_LIMIT
+        if depth >= params::null_move_pruning_depth_limit()
             && eval >= beta
             // Don't let a player play a null move in response to a null move
             && game.history.last().map_or(true, |m| m.mv.is_some())
@@ -157,7 +175,7 @@ pub fn negamax(
                 game,
                 -beta,
                 -beta + Eval(1),
-                depth - 1 - params::NULL_MOVE_PRUNING_DEPTH_REDUCTION,
+                (depth - 1).saturating_sub(params::null_move_pruning_depth_reduction()),
                 plies + 1,
                 &mut PrincipalVariation::new(),
                 ctx,
@@ -171,6 +189,53 @@ pub fn negamax(
         }
     }
 
+    // Singular extensions: if the TT move is much better than any of the alternatives, the
+    // position likely depends on it so we search it more deeply. To check, we do a reduced depth
+    // search of every other move against a window just below the TT score.
+    let mut singular_move = None;
+
+    if let Some(tt_entry) = &tt_entry {
+        if let Some(tt_move) = tt_entry.best_move {
+            if !is_root
+                && excluded_move.is_none()
+                && depth >= params::singular_extension_depth()
+                && tt_entry.bound != NodeBound::Upper
+                && tt_entry
+                    .depth
+                    .saturating_add(params::singular_extension_tt_depth_margin())
+                    >= depth
+                && tt_entry.eval.is_mate_in_moves().is_none()
+            {
+                let singular_beta =
+                    tt_entry.eval - params::singular_extension_margin_per_ply() * i16::from(depth);
+
+                ctx.excluded_moves[plies as usize] = Some(tt_move);
+
+                let singular_result = negamax(
+                    game,
+                    singular_beta - Eval(1),
+                    singular_beta,
+                    (depth - 1) / 2,
+                    plies,
+                    &mut PrincipalVariation::new(),
+                    ctx,
+                );
+
+                ctx.excluded_moves[plies as usize] = None;
+
+                let singular_score = singular_result?;
+
+                if singular_score < singular_beta {
+                    singular_move = Some(tt_move);
+                } else if singular_beta >= beta {
+                    // Multi-cut: even without the TT move, another move beats beta so we can
+                    // assume this node will fail high.
+                    return Ok(singular_beta);
+                }
+            }
+        }
+    }
+
     let mut tt_node_bound = NodeBound::Upper;
     let mut best_move = None;
     let mut best_eval = Eval::MIN;
@@ -182,25 +247,35 @@ pub fn negamax(
     while let Some(mv) = moves.next(game, ctx, plies) {
         node_pv.clear();
 
+        if is_root && ctx.excluded_root_moves.contains(&mv) {
+            continue;
+        }
+
+        if excluded_move == Some(mv) {
+            continue;
+        }
+
         // Futility pruning
         if number_of_legal_moves > 0
             && !is_pv
             && !mv.is_capture()
             && !in_check
-            && depth <= params::FUTILITY_PRUNE_DEPTH
-            && eval + params::FUTILITY_PRUNE_MAX_MOVE_VALUE < alpha
+            && depth <= params::futility_prune_depth()
+            && eval + params::futility_prune_max_move_value() < alpha
         {
             continue;
         }
 
+        let new_depth = depth - 1 + u8::from(singular_move == Some(mv));
+
         game.make_move(mv);
         number_of_legal_moves += 1;
 
         let move_score = if number_of_legal_moves == 1 {
-            -negamax(game, -beta, -alpha, depth - 1, plies + 1, &mut node_pv, ctx)?
+            -negamax(game, -beta, -alpha, new_depth, plies + 1, &mut node_pv, ctx)?
         } else {
-            let reduction = if depth >= params::LMR_DEPTH
-                && number_of_legal_moves >= params::LMR_MOVE_THRESHOLD
+            let reduction = if depth >= params::lmr_depth()
+                && number_of_legal_moves >= params::lmr_move_threshold()
             {
                 let mut reduction = DepthReduction(lmr_reduction(depth, number_of_legal_moves));
 
@@ -218,7 +293,7 @@ pub fn negamax(
                 game,
                 -alpha - Eval(1),
                 -alpha,
-                depth.saturating_sub(reduction),
+                new_depth.saturating_sub(reduction - 1),
                 plies + 1,
                 &mut node_pv,
                 ctx,
@@ -227,7 +302,7 @@ pub fn negamax(
             // Turns out the move we just searched could be better than our current PV, so we re-search
             // with the normal alpha/beta bounds.
             if pvs_score > alpha && pvs_score < beta {
-                -negamax(game, -beta, -alpha, depth - 1, plies + 1, &mut node_pv, ctx)?
+                -negamax(game, -beta, -alpha, new_depth, plies + 1, &mut node_pv, ctx)?
             } else {
                 pvs_score
             }
@@ -254,6 +329,11 @@ pub fn negamax(
     }
 
     if number_of_legal_moves == 0 {
+        // The excluded move is legal, so this isn't checkmate or stalemate
+        if excluded_move.is_some() {
+            return Ok(alpha);
+        }
+
         return Ok(if game.is_king_in_check() {
             Eval::mated_in(plies)
         } else {
@@ -278,15 +358,148 @@ pub fn negamax(
         }
     }
 
+    // When searching secondary MultiPV lines or a restricted set of moves, the root result doesn't
+    // account for every move so we shouldn't store it.
+    if is_root
+        && (!ctx.excluded_root_moves.is_empty() || ctx.search_restrictions.search_moves.is_some())
+    {
+        return Ok(best_eval);
+    }
+
+    // Similarly, a search excluding a move doesn't tell us the value of the position
+    if excluded_move.is_some() {
+        return Ok(best_eval);
+    }
+
     let tt_data = SearchTranspositionTableData {
         bound: tt_node_bound,
         eval: best_eval.with_mate_distance_from_position(plies),
+        static_eval: Some(static_eval),
         best_move,
         age: ctx.tt.generation,
         depth,
     };
 
-    ctx.tt.insert(&game.zobrist, tt_data);
+    ctx.tt.insert(&game.zobrist, &tt_data);
 
     Ok(best_eval)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::moves::MoveListExt;
+    use crate::chess::square::squares::all::*;
+    use crate::chess::square::Square;
+    use crate::engine::options::EngineOptions;
+    use crate::engine::search::time_control::TimeStrategy;
+    use crate::engine::search::{PersistentState, SearchRestrictions, TimeControl};
+
+    // Searches the position as if it were a node below the root, which is where singular
+    // extensions verify moves, with the given move excluded. Returns the score, the PV and
+    // whether the position was stored in the TT.
+    fn search_excluding(
+        fen: &str,
+        excluded_move: Option<(Square, Square)>,
+        alpha: Eval,
+        beta: Eval,
+        depth: u8,
+    ) -> (Eval, PrincipalVariation, bool) {
+        crate::init();
+
+        let mut game = Game::from_fen(fen).unwrap();
+        let excluded_move =
+            excluded_move.map(|(src, dst)| game.moves().expect_matching(src, dst, None));
+
+        let mut persistent_state = PersistentState::new(16);
+        let options = EngineOptions::default();
+        let (mut time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
+        let search_restrictions = SearchRestrictions::default();
+        let mut ctx = SearchContext::new(
+            &mut persistent_state,
+            &mut time_strategy,
+            &options,
+            &search_restrictions,
+        );
+
+        let plies = 1;
+        ctx.excluded_moves[plies as usize] = excluded_move;
+
+        let mut pv = PrincipalVariation::new();
+        let score = negamax(&mut game, alpha, beta, depth, plies, &mut pv, &mut ctx).unwrap();
+
+        (score, pv, ctx.tt.get(&game.zobrist).is_some())
+    }
+
+    const WINS_QUEEN: &str = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+
+    #[test]
+    fn test_excluded_move_is_not_searched() {
+        let (score, pv, _) = search_excluding(WINS_QUEEN, None, Eval::MIN, Eval::MAX, 3);
+        assert_eq!(pv.first().map(|mv| (mv.src(), mv.dst())), Some((D2, D5)));
+
+        let (excluded_score, excluded_pv, _) =
+            search_excluding(WINS_QUEEN, Some((D2, D5)), Eval::MIN, Eval::MAX, 3);
+        assert!(excluded_pv
+            .first()
+            .is_some_and(|mv| (mv.src(), mv.dst()) != (D2, D5)));
+        assert!(excluded_score < score);
+    }
+
+    #[test]
+    fn test_excluded_move_is_not_stored_in_tt() {
+        let (_, _, stored) = search_excluding(WINS_QUEEN, None, Eval::MIN, Eval::MAX, 3);
+        assert!(stored);
+
+        let (_, _, stored) = search_excluding(WINS_QUEEN, Some((D2, D5)), Eval::MIN, Eval::MAX, 3);
+        assert!(!stored);
+    }
+
+    #[test]
+    fn test_excluding_only_legal_move_is_not_stalemate() {
+        // White's only legal move is a3
+        let fen = "7k/8/8/8/p7/8/P4q2/7K w - - 0 1";
+        let alpha = Eval(-51);
+        let beta = Eval(-50);
+
+        let (score, _, stored) = search_excluding(fen, Some((A2, A3)), alpha, beta, 1);
+
+        assert_eq!(score, alpha);
+        assert!(!stored);
+    }
+
+    #[test]
+    fn test_singular_verification_restores_excluded_moves() {
+        crate::init();
+
+        let mut game = Game::new();
+        let mut persistent_state = PersistentState::new(16);
+        let options = EngineOptions::default();
+        let (mut time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
+        let search_restrictions = SearchRestrictions::default();
+        let mut ctx = SearchContext::new(
+            &mut persistent_state,
+            &mut time_strategy,
+            &options,
+            &search_restrictions,
+        );
+
+        // Search deeply enough, with the TT filled by shallower searches, that TT moves get
+        // verified as singular somewhere in the tree
+        for depth in 1..=params::singular_extension_depth() + 2 {
+            negamax(
+                &mut game,
+                Eval::MIN,
+                Eval::MAX,
+                depth,
+                0,
+                &mut PrincipalVariation::new(),
+                &mut ctx,
+            )
+            .unwrap();
+        }
+
+        assert!(ctx.excluded_moves.iter().all(Option::is_none));
+        assert_eq!(game.zobrist, Game::new().zobrist);
+    }
+}
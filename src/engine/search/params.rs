@@ -0,0 +1,188 @@
+use crate::engine::eval::Eval;
+
+pub const CHECK_TERMINATION_NODE_FREQUENCY: u64 = 10000;
+
+tunable!(
+    (aspiration_min_depth, u8, 5, 1, 10, 1),
+    (aspiration_window_size, Eval, Eval::new(25), 5, 100, 5),
+    (null_move_pruning_depth_limit, u8, 3, 1, 6, 1),
+    (null_move_pruning_depth_reduction, u8, 2, 1, 5, 1),
+    (futility_prune_depth, u8, 1, 1, 4, 1),
+    (
+        futility_prune_max_move_value,
+        Eval,
+        Eval::new(135),
+        50,
+        300,
+        10
+    ),
+    (reverse_futility_prune_depth, u8, 4, 1, 10, 1),
+    (
+        reverse_futility_prune_margin_per_ply,
+        Eval,
+        Eval::new(150),
+        50,
+        300,
+        10
+    ),
+    (lmr_depth, u8, 3, 1, 6, 1),
+    (lmr_move_threshold, usize, 3, 1, 8, 1),
+    (singular_extension_depth, u8, 8, 4, 12, 1),
+    (singular_extension_tt_depth_margin, u8, 3, 1, 6, 1),
+    (
+        singular_extension_margin_per_ply,
+        Eval,
+        Eval::new(2),
+        1,
+        10,
+        1
+    ),
+    (history_decay_factor, i32, 8, 2, 16, 1),
+    (max_time_per_move, f32, 0.5, 100, 900, 50),
+    (increment_to_use, f32, 0.5, 100, 1000, 50),
+    (base_time_per_move, f32, 0.033, 10, 100, 5),
+    (soft_time_multiplier, f32, 0.75, 300, 1500, 50),
+    (hard_time_multiplier, f32, 3.00, 1000, 6000, 250),
+);
+
+#[cfg(feature = "spsa")]
+pub struct TunableParameter {
+    pub name: &'static str,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+}
+
+// UCI spin options are integers, so each parameter needs an integer representation
+#[cfg(feature = "spsa")]
+trait TunableValue {
+    fn to_raw(self) -> i32;
+    fn from_raw(value: i32) -> Self;
+}
+
+#[cfg(feature = "spsa")]
+impl TunableValue for Eval {
+    fn to_raw(self) -> i32 {
+        i32::from(self.0)
+    }
+
+    fn from_raw(value: i32) -> Self {
+        Self::new(i16::try_from(value).unwrap())
+    }
+}
+
+#[cfg(feature = "spsa")]
+impl TunableValue for u8 {
+    fn to_raw(self) -> i32 {
+        i32::from(self)
+    }
+
+    fn from_raw(value: i32) -> Self {
+        Self::try_from(value).unwrap()
+    }
+}
+
+#[cfg(feature = "spsa")]
+impl TunableValue for usize {
+    fn to_raw(self) -> i32 {
+        i32::try_from(self).unwrap()
+    }
+
+    fn from_raw(value: i32) -> Self {
+        Self::try_from(value).unwrap()
+    }
+}
+
+#[cfg(feature = "spsa")]
+impl TunableValue for i32 {
+    fn to_raw(self) -> i32 {
+        self
+    }
+
+    fn from_raw(value: i32) -> Self {
+        value
+    }
+}
+
+// Fractional parameters are represented in thousandths
+#[cfg(feature = "spsa")]
+#[expect(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    reason = "Parameters are small, and only need three decimal places"
+)]
+impl TunableValue for f32 {
+    fn to_raw(self) -> i32 {
+        (self * 1000.0).round() as i32
+    }
+
+    fn from_raw(value: i32) -> Self {
+        value as Self / 1000.0
+    }
+}
+
+#[cfg(feature = "spsa")]
+pub fn init() {
+    for (value, tunable) in VALUES.iter().zip(tunables()) {
+        value.store(tunable.default, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "spsa")]
+pub fn set(name: &str, value: &str) -> Result<(), String> {
+    let (idx, tunable) = tunables()
+        .into_iter()
+        .enumerate()
+        .find(|(_, t)| t.name == name)
+        .ok_or_else(|| format!("Unknown option: {name}"))?;
+
+    let value = value.parse::<i32>().map_err(|_| "Invalid value")?;
+
+    if !(tunable.min..=tunable.max).contains(&value) {
+        return Err("Value out of range".to_string());
+    }
+
+    VALUES[idx].store(value, std::sync::atomic::Ordering::Relaxed);
+    Ok(())
+}
+
+// The parameters in the format OpenBench expects for SPSA tuning:
+//   name, type, default, min, max, c_end, r_end
+#[cfg(feature = "spsa")]
+pub fn openbench_spsa_input() -> String {
+    use std::fmt::Write;
+
+    let mut input = String::new();
+
+    for t in tunables() {
+        writeln!(
+            input,
+            "{}, int, {}, {}, {}, {}, 0.002",
+            t.name, t.default, t.min, t.max, t.step
+        )
+        .unwrap();
+    }
+
+    input
+}
+
+#[cfg(all(test, feature = "spsa"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_tunable_parameters() {
+        crate::init();
+
+        assert_eq!(base_time_per_move().to_raw(), 33);
+        assert!((f32::from_raw(800) - 0.8).abs() < f32::EPSILON);
+
+        // Other tests are searching at the same time, so we only set parameters to their defaults
+        set("aspiration_window_size", "25").unwrap();
+        assert_eq!(aspiration_window_size(), Eval::new(25));
+
+        assert!(set("aspiration_window_size", "1000").is_err());
+        assert!(set("not_a_parameter", "1").is_err());
+    }
+}
@@ -35,6 +35,12 @@ impl PrincipalVariation {
         self.0.first()
     }
 
+    // The move we expect our opponent to reply with
+    #[inline]
+    pub fn ponder_move(&self) -> Option<&Move> {
+        self.0.get(1)
+    }
+
     pub fn len(&self) -> u8 {
         u8::try_from(self.0.len()).unwrap()
     }
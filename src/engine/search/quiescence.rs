@@ -2,6 +2,7 @@ use crate::chess::game::Game;
 use crate::engine::eval;
 use crate::engine::eval::Eval;
 use crate::engine::search::move_picker::MovePicker;
+use crate::engine::search::transposition::{NodeBound, SearchTranspositionTableData};
 
 use super::{SearchContext, MAX_SEARCH_DEPTH};
 
@@ -12,11 +13,17 @@ pub fn quiescence(
     plies: u8,
     ctx: &mut SearchContext<'_>,
 ) -> Result<Eval, ()> {
+    // Check periodically to see if we're out of time. If we are, we shouldn't continue the search
+    // so we return Err to signal to the caller that the search did not complete.
+    if ctx.should_stop() {
+        return Err(());
+    }
+
     ctx.max_depth_reached = ctx.max_depth_reached.max(plies);
-    ctx.nodes_visited += 1;
+    ctx.visit_node();
 
     if plies == MAX_SEARCH_DEPTH {
-        return Ok(eval::eval(game));
+        return Ok(eval::eval(game, ctx.pawn_hash_table));
     }
 
     if game.is_repeated_position()
@@ -26,45 +33,76 @@ pub fn quiescence(
         return Ok(Eval::DRAW);
     }
 
-    // Check periodically to see if we're out of time. If we are, we shouldn't continue the search
-    // so we return Err to signal to the caller that the search did not complete.
-    if ctx.time_control.should_stop(ctx.nodes_visited) {
-        return Err(());
-    }
+    // Any search result is at least as deep as a quiescence search, so we can always use it
+    let tt_entry = ctx.tt.get(&game.zobrist);
 
-    let eval = eval::eval(game);
+    if let Some(tt_entry) = &tt_entry {
+        let tt_score = tt_entry.eval.with_mate_distance_from_root(plies);
 
-    if eval >= beta {
-        return Ok(eval);
+        match tt_entry.bound {
+            NodeBound::Exact => return Ok(tt_score),
+            NodeBound::Upper if tt_entry.eval <= alpha => return Ok(tt_score),
+            NodeBound::Lower if tt_entry.eval >= beta => return Ok(tt_score),
+            _ => {}
+        }
     }
 
-    if eval > alpha {
-        alpha = eval;
-    }
+    let static_eval = tt_entry
+        .as_ref()
+        .and_then(|e| e.static_eval)
+        .unwrap_or_else(|| eval::eval(game, ctx.pawn_hash_table));
+
+    let eval = tt_entry
+        .as_ref()
+        .map_or(static_eval, |e| e.adjust_static_eval(static_eval));
 
+    let mut tt_node_bound = NodeBound::Upper;
+    let mut best_move = None;
     let mut best_eval = eval;
 
-    let mut moves = MovePicker::new_loud();
-    while let Some(mv) = moves.next(game, ctx, plies) {
-        game.make_move(mv);
+    if eval >= beta {
+        tt_node_bound = NodeBound::Lower;
+    } else {
+        if eval > alpha {
+            alpha = eval;
+        }
+
+        let mut moves = MovePicker::new_loud();
+        while let Some(mv) = moves.next(game, ctx, plies) {
+            game.make_move(mv);
 
-        let move_score = -quiescence(game, -beta, -alpha, plies + 1, ctx)?;
+            let move_score = -quiescence(game, -beta, -alpha, plies + 1, ctx)?;
 
-        game.undo_move();
+            game.undo_move();
 
-        if move_score > best_eval {
-            best_eval = move_score;
-        }
+            if move_score > best_eval {
+                best_move = Some(mv);
+                best_eval = move_score;
+            }
 
-        // Cutoff: This move is so good that our opponent won't let it be played.
-        if move_score >= beta {
-            break;
-        }
+            // Cutoff: This move is so good that our opponent won't let it be played.
+            if move_score >= beta {
+                tt_node_bound = NodeBound::Lower;
+                break;
+            }
 
-        if move_score > alpha {
-            alpha = move_score;
+            if move_score > alpha {
+                alpha = move_score;
+                tt_node_bound = NodeBound::Exact;
+            }
         }
     }
 
+    let tt_data = SearchTranspositionTableData {
+        bound: tt_node_bound,
+        eval: best_eval.with_mate_distance_from_position(plies),
+        static_eval: Some(static_eval),
+        best_move,
+        age: ctx.tt.generation,
+        depth: 0,
+    };
+
+    ctx.tt.insert(&game.zobrist, &tt_data);
+
     Ok(best_eval)
 }
@@ -41,6 +41,7 @@ impl KillersTable {
     }
 }
 
+#[derive(Clone)]
 pub struct HistoryTable([[[i32; Square::N]; Square::N]; Player::N]);
 
 impl HistoryTable {
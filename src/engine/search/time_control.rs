@@ -1,12 +1,20 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::chess::game::Game;
 use crate::chess::player::Player;
+use crate::engine::eval::Eval;
 use crate::engine::options::EngineOptions;
-use crate::engine::search::{params, TimeControl};
+use crate::engine::search::{params, SearchRestrictions, TimeControl};
+use crate::engine::util::sync::LockLatch;
 
+// While pondering, we search without a time limit until the opponent plays the move we were
+// pondering on. Our clock only starts once we receive 'ponderhit', so we store the time at which
+// that happened (as an offset from the start of the search) to measure our time usage from.
+const PONDERING: u64 = u64::MAX;
+
+#[derive(Clone)]
 pub struct TimeStrategy {
     time_control: TimeControl,
     started_at: Instant,
@@ -17,15 +25,40 @@ pub struct TimeStrategy {
     next_check_at: u64,
 
     force_stop: Arc<AtomicBool>,
+
+    clock_started_after_micros: Arc<AtomicU64>,
+    ponder_finished: Arc<LockLatch>,
 }
 
 pub struct Control {
+    started_at: Instant,
+
     force_stop: Arc<AtomicBool>,
+
+    clock_started_after_micros: Arc<AtomicU64>,
+    ponder_finished: Arc<LockLatch>,
 }
 
 impl Control {
     pub fn stop(&self) {
         self.force_stop.store(true, Ordering::Relaxed);
+        self.ponder_finished.set();
+    }
+
+    // The opponent played the move we were pondering on, so we should start using our clock
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "A search will never last long enough to overflow"
+    )]
+    pub fn ponderhit(&self) {
+        let _ = self.clock_started_after_micros.compare_exchange(
+            PONDERING,
+            self.started_at.elapsed().as_micros() as u64,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+
+        self.ponder_finished.set();
     }
 }
 
@@ -60,31 +93,40 @@ impl TimeStrategy {
                     .saturating_sub(move_overhead)
                     .max(move_overhead);
 
-                let max_time_per_move = time_remaining.mul_f32(params::MAX_TIME_PER_MOVE);
+                let max_time_per_move = time_remaining.mul_f32(params::max_time_per_move());
 
                 let base_time = if let Some(moves_to_go) = clocks.moves_to_go {
                     // Try to use a roughly even amount of time per move
                     time_remaining / moves_to_go
                 } else {
-                    time_remaining.mul_f32(params::BASE_TIME_PER_MOVE)
-                } + increment.mul_f32(params::INCREMENT_TO_USE);
+                    time_remaining.mul_f32(params::base_time_per_move())
+                } + increment.mul_f32(params::increment_to_use());
 
                 soft_stop = std::cmp::min(
-                    base_time.mul_f32(params::SOFT_TIME_MULTIPLIER),
+                    base_time.mul_f32(params::soft_time_multiplier()),
                     max_time_per_move,
                 );
 
                 hard_stop = std::cmp::min(
-                    base_time.mul_f32(params::HARD_TIME_MULTIPLIER),
+                    base_time.mul_f32(params::hard_time_multiplier()),
                     max_time_per_move,
                 );
             }
         };
 
         let force_stop = Arc::new(AtomicBool::new(false));
+        let clock_started_after_micros = Arc::new(AtomicU64::new(0));
+
+        let ponder_finished = Arc::new(LockLatch::new());
+        ponder_finished.set();
 
         let control = Control {
+            started_at: now,
+
             force_stop: force_stop.clone(),
+
+            clock_started_after_micros: clock_started_after_micros.clone(),
+            ponder_finished: ponder_finished.clone(),
         };
 
         let time_strategy = Self {
@@ -97,16 +139,58 @@ impl TimeStrategy {
             next_check_at: params::CHECK_TERMINATION_NODE_FREQUENCY,
 
             force_stop,
+
+            clock_started_after_micros,
+            ponder_finished,
         };
 
         (time_strategy, control)
     }
 
+    // Search without using our clock until we receive 'ponderhit' or 'stop'
+    pub fn start_pondering(&self) {
+        self.clock_started_after_micros
+            .store(PONDERING, Ordering::Relaxed);
+
+        self.ponder_finished.reset();
+    }
+
+    // We shouldn't report a best move while we're still pondering, even if the search has finished
+    pub fn wait_until_ponder_finished(&self) {
+        self.ponder_finished.wait();
+    }
+
+    // Whether the search will finish by itself when its time runs out. Infinite searches and
+    // pondering have to keep going until we're told to stop.
+    pub fn is_time_limited(&self) -> bool {
+        !matches!(self.time_control, TimeControl::Infinite) && self.clock_elapsed().is_some()
+    }
+
     pub fn elapsed(&self) -> Duration {
         self.started_at.elapsed()
     }
 
-    pub fn should_start_new_search(&self, depth: u8) -> bool {
+    // The time we've used on our own clock, or None if we're still pondering
+    fn clock_elapsed(&self) -> Option<Duration> {
+        let clock_started_after_micros = self.clock_started_after_micros.load(Ordering::Relaxed);
+
+        if clock_started_after_micros == PONDERING {
+            return None;
+        }
+
+        Some(
+            self.elapsed()
+                .saturating_sub(Duration::from_micros(clock_started_after_micros)),
+        )
+    }
+
+    pub fn should_start_new_search(
+        &self,
+        depth: u8,
+        nodes_visited: u64,
+        best_eval: Option<Eval>,
+        search_restrictions: &SearchRestrictions,
+    ) -> bool {
         if depth == 1 {
             return true;
         }
@@ -115,14 +199,49 @@ impl TimeStrategy {
             return false;
         }
 
+        if search_restrictions
+            .nodes
+            .is_some_and(|nodes| nodes_visited >= nodes)
+        {
+            return false;
+        }
+
+        // If we were asked to find a mate, we can stop once we've found one that is short enough
+        if let Some(mate_in_moves) = search_restrictions.mate {
+            let found_mate = best_eval
+                .and_then(Eval::is_mate_in_moves)
+                .is_some_and(|moves| moves > 0 && moves <= i16::from(mate_in_moves));
+
+            if found_mate {
+                return false;
+            }
+        }
+
+        let Some(elapsed) = self.clock_elapsed() else {
+            return true;
+        };
+
         match self.time_control {
-            TimeControl::Clocks(_) => self.elapsed() < self.soft_stop,
-            TimeControl::ExactTime(time) => self.elapsed() < time,
+            TimeControl::Clocks(_) => elapsed < self.soft_stop,
+            TimeControl::ExactTime(time) => elapsed < time,
             TimeControl::Infinite => true,
         }
     }
 
-    pub fn should_stop(&mut self, nodes_visited: u64) -> bool {
+    pub fn should_stop(
+        &mut self,
+        nodes_visited: u64,
+        search_restrictions: &SearchRestrictions,
+    ) -> bool {
+        // Node limits are checked on every node so that we stop at exactly the right node count,
+        // which makes node-limited searches deterministic.
+        if search_restrictions
+            .nodes
+            .is_some_and(|nodes| nodes_visited >= nodes)
+        {
+            return true;
+        }
+
         if nodes_visited < self.next_check_at {
             return false;
         }
@@ -133,14 +252,74 @@ impl TimeStrategy {
 
         self.next_check_at = nodes_visited + params::CHECK_TERMINATION_NODE_FREQUENCY;
 
+        let Some(elapsed) = self.clock_elapsed() else {
+            return false;
+        };
+
         match self.time_control {
-            TimeControl::Clocks(_) => self.elapsed() > self.hard_stop,
-            TimeControl::ExactTime(time) => self.elapsed() > time,
+            TimeControl::Clocks(_) => elapsed > self.hard_stop,
+            TimeControl::ExactTime(time) => elapsed > time,
             TimeControl::Infinite => false,
         }
     }
 
+    // Stops any other searches sharing this strategy (i.e. helper threads)
+    pub fn stop(&self) {
+        self.force_stop.store(true, Ordering::Relaxed);
+    }
+
     fn is_force_stopped(&self) -> bool {
         self.force_stop.load(Ordering::Relaxed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pondering_ignores_clock_until_ponderhit() {
+        let game = Game::new();
+        let search_restrictions = SearchRestrictions::default();
+
+        let (mut time_strategy, control) = TimeStrategy::new(
+            &game,
+            &TimeControl::ExactTime(Duration::ZERO),
+            &EngineOptions::default(),
+        );
+
+        time_strategy.start_pondering();
+
+        let nodes = params::CHECK_TERMINATION_NODE_FREQUENCY;
+        assert!(time_strategy.should_start_new_search(2, nodes, None, &search_restrictions));
+        assert!(!time_strategy.should_stop(nodes, &search_restrictions));
+
+        control.ponderhit();
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(!time_strategy.should_start_new_search(2, nodes, None, &search_restrictions));
+        assert!(time_strategy.should_stop(2 * nodes, &search_restrictions));
+    }
+
+    #[test]
+    fn test_time_limited_searches() {
+        let game = Game::new();
+        let options = EngineOptions::default();
+
+        let (time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
+        assert!(!time_strategy.is_time_limited());
+
+        let (time_strategy, control) = TimeStrategy::new(
+            &game,
+            &TimeControl::ExactTime(Duration::from_secs(1)),
+            &options,
+        );
+        assert!(time_strategy.is_time_limited());
+
+        time_strategy.start_pondering();
+        assert!(!time_strategy.is_time_limited());
+
+        control.ponderhit();
+        assert!(time_strategy.is_time_limited());
+    }
+}
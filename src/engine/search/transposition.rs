@@ -1,6 +1,8 @@
 use crate::chess::moves::Move;
 use crate::engine::eval::Eval;
-use crate::engine::transposition_table::{TTOverwriteable, TranspositionTable};
+use crate::engine::transposition_table::{
+    TTOverwriteable, TTPackable, TTSaveable, TranspositionTable, GENERATION_CYCLE,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum NodeBound {
@@ -13,6 +15,7 @@ pub enum NodeBound {
 pub struct SearchTranspositionTableData {
     pub bound: NodeBound,
     pub eval: Eval,
+    pub static_eval: Option<Eval>,
     pub depth: u8,
     pub age: u8,
     pub best_move: Option<Move>,
@@ -39,6 +42,123 @@ impl TTOverwriteable for SearchTranspositionTableData {
         // Don't overwrite exact nodes
         self.bound != NodeBound::Exact
     }
+
+    fn replacement_priority(&self, generation: u8) -> i32 {
+        // Prefer to keep deep results, but let results from old searches age out of the table
+        i32::from(self.depth) - 8 * i32::from(self.relative_age(generation))
+    }
+
+    fn is_current(&self, generation: u8) -> bool {
+        self.age == generation
+    }
+}
+
+impl SearchTranspositionTableData {
+    // The search score is a better estimate of the position than the static eval, so we use it
+    // instead when its bound tells us the position is at least (or at most) that good.
+    pub fn adjust_static_eval(&self, static_eval: Eval) -> Eval {
+        if self.eval.is_mate_in_moves().is_some() {
+            return static_eval;
+        }
+
+        match self.bound {
+            NodeBound::Exact => self.eval,
+            NodeBound::Lower if self.eval > static_eval => self.eval,
+            NodeBound::Upper if self.eval < static_eval => self.eval,
+            _ => static_eval,
+        }
+    }
+
+    // The number of searches since the entry was written
+    const fn relative_age(&self, generation: u8) -> u8 {
+        (generation + GENERATION_CYCLE - self.age) % GENERATION_CYCLE
+    }
+}
+
+//    Layout
+//
+//    ┌──────┐
+//  0 │      │ ─┐
+//    ~~~~~~~~  ├── Best move (0 if there is no best move)
+// 15 │      │ ─┘
+//    ├──────┤
+// 16 │      │ ─┐
+//    ~~~~~~~~  ├── Eval
+// 31 │      │ ─┘
+//    ├──────┤
+// 32 │      │ ─┐
+//    ~~~~~~~~  ├── Depth
+// 39 │      │ ─┘
+//    ├──────┤
+// 40 │      │ ─┐
+//    ~~~~~~~~  ├── Age
+// 45 │      │ ─┘
+//    ├──────┤
+// 46 │      │ ─┐
+//    ├──────┤  ├── Bound (never 0, so that a stored entry is never mistaken for an empty one)
+// 47 │      │ ─┘
+//    ├──────┤
+// 48 │      │ ─┐
+//    ~~~~~~~~  ├── Static eval (i16::MIN if there is no static eval)
+// 63 │      │ ─┘
+//    └──────┘
+const EVAL_SHIFT: usize = 16;
+const DEPTH_SHIFT: usize = 32;
+const AGE_SHIFT: usize = 40;
+const BOUND_SHIFT: usize = 46;
+
+const STATIC_EVAL_SHIFT: usize = 48;
+
+const AGE_MASK: u64 = 0b11_1111;
+const NO_STATIC_EVAL: i16 = i16::MIN;
+
+impl TTPackable for SearchTranspositionTableData {
+    #[expect(clippy::cast_sign_loss, reason = "We're storing the bits of the eval")]
+    fn pack(&self) -> u64 {
+        let best_move = self.best_move.map_or(0, Move::to_u16);
+        let bound: u64 = match self.bound {
+            NodeBound::Exact => 1,
+            NodeBound::Upper => 2,
+            NodeBound::Lower => 3,
+        };
+
+        u64::from(best_move)
+            | u64::from(self.eval.0 as u16) << EVAL_SHIFT
+            | u64::from(self.depth) << DEPTH_SHIFT
+            | (u64::from(self.age) & AGE_MASK) << AGE_SHIFT
+            | bound << BOUND_SHIFT
+            | u64::from(self.static_eval.map_or(NO_STATIC_EVAL, |e| e.0) as u16)
+                << STATIC_EVAL_SHIFT
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        reason = "We're extracting fields that were packed into the entry"
+    )]
+    fn unpack(data: u64) -> Self {
+        Self {
+            best_move: Move::from_u16(data as u16),
+            eval: Eval((data >> EVAL_SHIFT) as u16 as i16),
+            static_eval: match (data >> STATIC_EVAL_SHIFT) as u16 as i16 {
+                NO_STATIC_EVAL => None,
+                static_eval => Some(Eval(static_eval)),
+            },
+            depth: (data >> DEPTH_SHIFT) as u8,
+            age: ((data >> AGE_SHIFT) & AGE_MASK) as u8,
+            bound: match (data >> BOUND_SHIFT) & 0b11 {
+                1 => NodeBound::Exact,
+                2 => NodeBound::Upper,
+                3 => NodeBound::Lower,
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+impl TTSaveable for SearchTranspositionTableData {
+    // Change this whenever the layout above changes
+    const LAYOUT_VERSION: u32 = 1;
 }
 
 pub type SearchTranspositionTable = TranspositionTable<SearchTranspositionTableData>;
@@ -47,13 +167,13 @@ pub type SearchTranspositionTable = TranspositionTable<SearchTranspositionTableD
 mod tests {
     use super::*;
     use crate::engine::transposition_table;
-    use crate::engine::transposition_table::TranspositionTableEntry;
+    use crate::engine::transposition_table::Bucket;
 
     #[test]
-    fn assert_tt_size() {
+    fn assert_tt_bucket_size() {
         assert_eq!(
-            std::mem::size_of::<TranspositionTableEntry<SearchTranspositionTableData>>(),
-            16
+            std::mem::size_of::<Bucket<SearchTranspositionTableData>>(),
+            32
         );
     }
 
@@ -62,6 +182,204 @@ mod tests {
         let number_of_entries =
             transposition_table::calculate_number_of_entries::<SearchTranspositionTableData>(256);
 
-        assert_eq!(number_of_entries, 16_777_216);
+        assert_eq!(number_of_entries, 25_165_824);
+    }
+
+    #[test]
+    fn test_tt_data_round_trips_through_packing() {
+        use crate::chess::square::squares::all::*;
+
+        let data = SearchTranspositionTableData {
+            bound: NodeBound::Lower,
+            eval: Eval(-31_900),
+            static_eval: Some(Eval(-250)),
+            depth: 12,
+            age: GENERATION_CYCLE - 1,
+            best_move: Some(Move::quiet(G1, F3)),
+        };
+
+        let unpacked = SearchTranspositionTableData::unpack(data.pack());
+
+        assert_eq!(unpacked.bound, data.bound);
+        assert_eq!(unpacked.eval, data.eval);
+        assert_eq!(unpacked.static_eval, data.static_eval);
+        assert_eq!(unpacked.depth, data.depth);
+        assert_eq!(unpacked.age, data.age);
+        assert_eq!(unpacked.best_move, data.best_move);
+    }
+
+    #[test]
+    fn test_missing_static_eval_round_trips_through_packing() {
+        let data = SearchTranspositionTableData {
+            bound: NodeBound::Upper,
+            eval: Eval(35),
+            static_eval: None,
+            depth: 0,
+            age: 0,
+            best_move: None,
+        };
+
+        assert_eq!(
+            SearchTranspositionTableData::unpack(data.pack()).static_eval,
+            None
+        );
+    }
+
+    #[test]
+    fn test_static_eval_is_adjusted_by_bound() {
+        let entry = |bound, eval| SearchTranspositionTableData {
+            bound,
+            eval: Eval(eval),
+            static_eval: None,
+            depth: 4,
+            age: 0,
+            best_move: None,
+        };
+
+        let static_eval = Eval(50);
+
+        assert_eq!(
+            entry(NodeBound::Exact, -20).adjust_static_eval(static_eval),
+            Eval(-20)
+        );
+        assert_eq!(
+            entry(NodeBound::Lower, 80).adjust_static_eval(static_eval),
+            Eval(80)
+        );
+        assert_eq!(
+            entry(NodeBound::Lower, 20).adjust_static_eval(static_eval),
+            static_eval
+        );
+        assert_eq!(
+            entry(NodeBound::Upper, 20).adjust_static_eval(static_eval),
+            Eval(20)
+        );
+        assert_eq!(
+            entry(NodeBound::Upper, 80).adjust_static_eval(static_eval),
+            static_eval
+        );
+
+        // Mate scores are no use as an estimate
+        assert_eq!(
+            entry(NodeBound::Exact, Eval::mate_in(3).0).adjust_static_eval(static_eval),
+            static_eval
+        );
+    }
+
+    #[test]
+    fn test_old_entries_are_replaced_before_deep_entries() {
+        let entry = |depth, age| SearchTranspositionTableData {
+            bound: NodeBound::Exact,
+            eval: Eval(0),
+            static_eval: None,
+            depth,
+            age,
+            best_move: None,
+        };
+
+        let generation = 1;
+
+        assert!(
+            entry(2, 0).replacement_priority(generation)
+                < entry(2, 1).replacement_priority(generation)
+        );
+
+        // Much deeper results from the previous search are still worth keeping
+        assert!(
+            entry(2, 1).replacement_priority(generation)
+                < entry(12, 0).replacement_priority(generation)
+        );
+
+        // Ages wrap around along with the generation
+        assert!(
+            entry(2, GENERATION_CYCLE - 1).replacement_priority(0)
+                < entry(2, 0).replacement_priority(0)
+        );
+    }
+
+    #[test]
+    fn test_tt_lookup_and_replacement() {
+        let mut tt = SearchTranspositionTable::new(0);
+        tt.new_generation();
+
+        // A table of a single bucket, so every position competes for the same entries
+        let positions = [1, 2, 3, 4].map(|n| crate::chess::zobrist::ZobristHash(n << 32 | n));
+
+        for (depth, key) in [10, 2, 6].into_iter().zip(&positions) {
+            tt.insert(key, &entry_with_depth(depth, tt.generation));
+        }
+
+        for key in &positions[..3] {
+            assert!(tt.get(key).is_some());
+        }
+
+        // The shallowest entry makes room for the new position
+        tt.insert(&positions[3], &entry_with_depth(4, tt.generation));
+        assert!(tt.get(&positions[1]).is_none());
+        assert_eq!(tt.get(&positions[3]).unwrap().depth, 4);
+        assert_eq!(tt.get(&positions[0]).unwrap().depth, 10);
+    }
+
+    #[test]
+    fn test_tt_reset_clears_entries() {
+        let mut tt = SearchTranspositionTable::new(4);
+        let key = crate::chess::zobrist::ZobristHash(0x1234_5678_9ABC_DEF0);
+
+        tt.insert(&key, &entry_with_depth(5, tt.generation));
+        assert!(tt.get(&key).is_some());
+
+        tt.reset();
+        assert!(tt.get(&key).is_none());
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_tt_save_and_load() {
+        let path = std::env::temp_dir().join(format!("tt-save-{}.hash", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let tt = SearchTranspositionTable::new(1);
+        let positions = (1..1000_u64)
+            .map(|n| crate::chess::zobrist::ZobristHash(n.wrapping_mul(0x9E37_79B9_7F4A_7C15)));
+
+        for (depth, key) in (0..50).cycle().zip(positions.clone()) {
+            tt.insert(&key, &entry_with_depth(depth, tt.generation));
+        }
+
+        tt.save(path).unwrap();
+
+        let mut loaded = SearchTranspositionTable::new(2);
+        assert_eq!(loaded.load(path).unwrap(), 1);
+
+        for key in positions {
+            assert_eq!(
+                tt.get(&key).map(|e| e.pack()),
+                loaded.get(&key).map(|e| e.pack())
+            );
+        }
+
+        // A file saved with a different entry layout is refused
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[12] ^= 0xFF;
+        std::fs::write(path, &bytes).unwrap();
+        assert!(loaded.load(path).is_err());
+
+        // As is one which has been cut short
+        bytes[12] ^= 0xFF;
+        std::fs::write(path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(loaded.load(path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn entry_with_depth(depth: u8, age: u8) -> SearchTranspositionTableData {
+        SearchTranspositionTableData {
+            bound: NodeBound::Lower,
+            eval: Eval(0),
+            static_eval: None,
+            depth,
+            age,
+            best_move: None,
+        }
     }
 }
@@ -23,6 +23,9 @@ pub enum Wdl {
     Loss,
 }
 
+// The tablebase files are loaded into global state by Fathom, so a tablebase can be cloned to share
+// it between threads.
+#[derive(Clone)]
 pub struct Tablebase {
     is_enabled: bool,
 }
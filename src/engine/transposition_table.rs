@@ -1,34 +1,287 @@
+use std::alloc::Layout;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+
 use crate::chess::zobrist::ZobristHash;
 
 pub trait TTOverwriteable {
+    // Whether a new result for the same position should replace this one
     fn should_overwrite_with(&self, new: &Self) -> bool;
+
+    // When a bucket is full, the entry with the lowest priority is replaced by the new position
+    fn replacement_priority(&self, _generation: u8) -> i32 {
+        0
+    }
+
+    // Whether the entry was written during the search with the given generation, for hashfull
+    fn is_current(&self, _generation: u8) -> bool {
+        true
+    }
 }
 
-pub struct TranspositionTable<T: Clone + TTOverwriteable> {
-    data: Vec<Option<TranspositionTableEntry<T>>>,
+/// Data stored in the transposition table is packed into a single `u64`, so that each entry
+/// can be written with plain atomic stores and the table can be shared between search threads.
+///
+/// Entries whose data packs to 0 are treated as empty.
+pub trait TTPackable: Sized {
+    fn pack(&self) -> u64;
+    fn unpack(data: u64) -> Self;
+}
+
+/// Data which can be saved to a file along with the table.
+///
+/// The layout version has to change whenever the way the data is packed changes, so that entries
+/// saved by a different build are never loaded.
+pub trait TTSaveable {
+    const LAYOUT_VERSION: u32;
+}
+
+// Generations wrap around, so that they can be stored in a few bits of each entry
+pub const GENERATION_CYCLE: u8 = 64;
+
+const ENTRIES_PER_BUCKET: usize = 3;
+
+// Hashfull is estimated from the entries in the first buckets of the table, rather than counting
+// every entry.
+const HASHFULL_SAMPLE_SIZE: usize = 1000;
+
+// Tables at least this large are aligned to huge page boundaries
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+// Clearing is split between threads so that each one clears at least this much of the table
+const MIN_BYTES_CLEARED_PER_THREAD: usize = 64 * 1024 * 1024;
+
+pub struct TranspositionTable<T: TTOverwriteable + TTPackable> {
+    buckets: Buckets<T>,
     pub generation: u8,
-    pub occupied: usize,
     size: usize,
 }
 
-#[derive(Clone)]
-pub struct TranspositionTableEntry<T: Clone + TTOverwriteable> {
-    pub key: ZobristHash,
-    pub data: T,
+// Entries are grouped into buckets, two of which fit in a cache line, so that a probe can check
+// several entries with a single memory access.
+//
+// Each entry stores 16 bits of the key, which with the bits used to pick the bucket is enough to
+// make collisions rare. The key is stored using the 'lockless hashing' trick: rather than storing
+// it directly, we store it XORed with the data folded down to 16 bits. If another thread writes to
+// the same entry while we're reading it, we'll see a key and data from different writes, and the
+// key won't match the position we're looking up. We'll treat that as a miss rather than returning
+// corrupted data.
+#[repr(C, align(32))]
+pub struct Bucket<T: TTOverwriteable + TTPackable> {
+    keys: [AtomicU16; ENTRIES_PER_BUCKET],
+    data: [AtomicU64; ENTRIES_PER_BUCKET],
+    // The bucket only stores packed data, so it's safe to share between threads whatever T is
+    _data_type: PhantomData<fn() -> T>,
+}
+
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "We only keep the low bits of the key"
+)]
+const fn verification_key(key: &ZobristHash) -> u16 {
+    key.0 as u16
+}
+
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "We're folding the data down to 16 bits"
+)]
+const fn fold(data: u64) -> u16 {
+    (data ^ data >> 16 ^ data >> 32 ^ data >> 48) as u16
 }
 
-pub fn calculate_number_of_entries<T: Clone + TTOverwriteable>(size_mb: usize) -> usize {
-    let size_of_entry = std::mem::size_of::<TranspositionTableEntry<T>>();
+impl<T: TTOverwriteable + TTPackable> Bucket<T> {
+    const fn empty() -> Self {
+        Self {
+            keys: [const { AtomicU16::new(0) }; ENTRIES_PER_BUCKET],
+            data: [const { AtomicU64::new(0) }; ENTRIES_PER_BUCKET],
+            _data_type: PhantomData,
+        }
+    }
+
+    fn load(&self, idx: usize) -> Option<(u16, u64)> {
+        let key = self.keys[idx].load(Ordering::Relaxed);
+        let data = self.data[idx].load(Ordering::Relaxed);
+
+        if data == 0 {
+            return None;
+        }
+
+        Some((key ^ fold(data), data))
+    }
+
+    fn store(&self, idx: usize, key: u16, data: u64) {
+        self.keys[idx].store(key ^ fold(data), Ordering::Relaxed);
+        self.data[idx].store(data, Ordering::Relaxed);
+    }
+
+    fn clear(&mut self) {
+        for key in &mut self.keys {
+            *key.get_mut() = 0;
+        }
+
+        for data in &mut self.data {
+            *data.get_mut() = 0;
+        }
+    }
+}
+
+// The memory for a table's buckets. Large tables are aligned to huge page boundaries and, on
+// Linux, we ask the kernel to back them with transparent huge pages. A table covers far more
+// memory than the TLB can map with normal pages, so almost every probe would otherwise miss it.
+struct Buckets<T: TTOverwriteable + TTPackable> {
+    ptr: NonNull<Bucket<T>>,
+    len: usize,
+    huge_pages: bool,
+}
+
+// !: Buckets are only accessed through atomics, or through a mutable reference to the table.
+unsafe impl<T: TTOverwriteable + TTPackable> Send for Buckets<T> {}
+unsafe impl<T: TTOverwriteable + TTPackable> Sync for Buckets<T> {}
+
+impl<T: TTOverwriteable + TTPackable> Buckets<T> {
+    const fn empty() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            huge_pages: false,
+        }
+    }
+
+    #[expect(
+        clippy::cast_ptr_alignment,
+        reason = "The allocation uses the alignment of the layout, which is at least a bucket's"
+    )]
+    fn new(len: usize) -> Self {
+        let layout = Self::layout(len);
+
+        // !: The layout has a non-zero size, since we always allocate at least one bucket.
+        let Some(ptr) = NonNull::new(unsafe { std::alloc::alloc(layout) }.cast::<Bucket<T>>())
+        else {
+            std::alloc::handle_alloc_error(layout);
+        };
+
+        // The advice has to be given before the memory is first touched
+        let huge_pages = layout.align() == HUGE_PAGE_SIZE
+            && advise_huge_pages(ptr.as_ptr().cast(), layout.size());
+
+        // !: We've just allocated space for this many buckets, and initialise them all below.
+        let uninitialised = unsafe {
+            std::slice::from_raw_parts_mut(ptr.as_ptr().cast::<MaybeUninit<Bucket<T>>>(), len)
+        };
+
+        for_each_chunk_in_parallel(uninitialised, |chunk| {
+            for bucket in chunk {
+                bucket.write(Bucket::empty());
+            }
+        });
+
+        Self {
+            ptr,
+            len,
+            huge_pages,
+        }
+    }
+
+    fn layout(len: usize) -> Layout {
+        let size = len * std::mem::size_of::<Bucket<T>>();
+
+        let align = if size >= HUGE_PAGE_SIZE {
+            HUGE_PAGE_SIZE
+        } else {
+            std::mem::align_of::<Bucket<T>>()
+        };
+
+        Layout::from_size_align(size, align).unwrap()
+    }
+}
+
+impl<T: TTOverwriteable + TTPackable> Deref for Buckets<T> {
+    type Target = [Bucket<T>];
+
+    fn deref(&self) -> &Self::Target {
+        // !: The pointer is either dangling with a length of 0, or to `len` initialised buckets.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: TTOverwriteable + TTPackable> DerefMut for Buckets<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // !: The pointer is either dangling with a length of 0, or to `len` initialised buckets.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: TTOverwriteable + TTPackable> Drop for Buckets<T> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // !: We allocated the buckets with this layout, and buckets don't need dropping.
+            unsafe { std::alloc::dealloc(self.ptr.as_ptr().cast(), Self::layout(self.len)) }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(ptr: *mut u8, size: usize) -> bool {
+    // !: The range is exactly one allocation which we own.
+    let result = unsafe { libc::madvise(ptr.cast(), size, libc::MADV_HUGEPAGE) };
+
+    // The advice is accepted even when huge pages are turned off, so check they're allowed too.
+    // The active setting is in brackets, e.g. 'always [madvise] never'.
+    result == 0
+        && std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+            .is_ok_and(|setting| !setting.contains("[never]"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_: *mut u8, _: usize) -> bool {
+    false
+}
+
+// Clearing a large table a bucket at a time takes seconds, so we split it between threads
+fn for_each_chunk_in_parallel<B: Send>(items: &mut [B], f: impl Fn(&mut [B]) + Sync) {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, std::num::NonZero::get)
+        .min(std::mem::size_of_val(items) / MIN_BYTES_CLEARED_PER_THREAD)
+        .max(1);
+
+    if threads == 1 {
+        f(items);
+        return;
+    }
+
+    let chunk_size = items.len().div_ceil(threads);
+
+    std::thread::scope(|s| {
+        for chunk in items.chunks_mut(chunk_size) {
+            s.spawn(|| f(chunk));
+        }
+    });
+}
+
+#[cfg(test)]
+pub fn calculate_number_of_entries<T: TTOverwriteable + TTPackable>(size_mb: usize) -> usize {
+    calculate_number_of_buckets::<T>(size_mb) * ENTRIES_PER_BUCKET
+}
+
+fn calculate_number_of_buckets<T: TTOverwriteable + TTPackable>(size_mb: usize) -> usize {
+    let size_of_bucket = std::mem::size_of::<Bucket<T>>();
     let total_size_in_bytes = size_mb * 1024 * 1024;
-    total_size_in_bytes / size_of_entry
+
+    // We always need somewhere to store entries, even if we're asked for an empty table
+    (total_size_in_bytes / size_of_bucket).max(1)
 }
 
-impl<T: Clone + TTOverwriteable> TranspositionTable<T> {
+impl<T: TTOverwriteable + TTPackable> TranspositionTable<T> {
     pub fn new(size_mb: usize) -> Self {
         let mut tt = Self {
-            data: Vec::new(),
+            buckets: Buckets::empty(),
             size: 0,
-            occupied: 0,
             generation: 0,
         };
 
@@ -37,85 +290,119 @@ impl<T: Clone + TTOverwriteable> TranspositionTable<T> {
     }
 
     pub fn reset(&mut self) {
-        for i in 0..self.data.len() {
-            self.data[i] = None;
-        }
+        for_each_chunk_in_parallel(&mut self.buckets, |chunk| {
+            for bucket in chunk {
+                bucket.clear();
+            }
+        });
 
         self.generation = 0;
-        self.occupied = 0;
     }
 
     pub fn resize(&mut self, size_mb: usize) {
-        if self.size == size_mb {
+        if self.size == size_mb && !self.buckets.is_empty() {
             return;
         }
 
-        let number_of_entries = calculate_number_of_entries::<T>(size_mb);
+        let number_of_buckets = calculate_number_of_buckets::<T>(size_mb);
 
-        self.data.clear();
-        self.data.resize(number_of_entries, None);
-        self.data.shrink_to_fit();
+        // Free the old table first, so that we never need the memory for both
+        self.buckets = Buckets::empty();
+        self.buckets = Buckets::new(number_of_buckets);
         self.size = size_mb;
-        self.occupied = 0;
         self.generation = 0;
     }
 
+    pub fn uses_huge_pages(&self) -> bool {
+        self.buckets.huge_pages
+    }
+
     pub fn new_generation(&mut self) {
-        self.generation += 1;
+        self.generation = (self.generation + 1) % GENERATION_CYCLE;
     }
 
     #[expect(
         clippy::cast_possible_truncation,
-        reason = "The truncation is intended to get an index"
+        reason = "The result is always less than the number of buckets"
     )]
-    fn get_entry_idx(&self, key: &ZobristHash) -> usize {
-        // PERF: There's likely a more performant way to do this
-        key.0 as usize % self.data.len()
+    fn get_bucket(&self, key: &ZobristHash) -> &Bucket<T> {
+        // Map the key onto the range of buckets with a multiply and shift, which is much faster
+        // than taking the remainder.
+        let idx = ((u128::from(key.0) * self.buckets.len() as u128) >> 64) as usize;
+
+        // !: The index is always less than the number of buckets.
+        unsafe { self.buckets.get_unchecked(idx) }
     }
 
-    #[expect(
-        clippy::cast_precision_loss,
-        clippy::cast_possible_truncation,
-        clippy::cast_sign_loss,
-        reason = "This is just an approximation, so a loss of precision is fine"
-    )]
-    pub fn occupancy(&self) -> usize {
-        let decimal = self.occupied as f32 / self.data.len() as f32;
-        let permille = decimal * 1000.0;
-        permille as usize
-    }
-
-    pub fn insert(&mut self, key: &ZobristHash, data: T) {
-        let idx = self.get_entry_idx(key);
-
-        // !: We know the exact size of the table and will always access within the bounds.
-        unsafe {
-            if let Some(existing_data) = self.data.get_unchecked(idx) {
-                if existing_data.data.should_overwrite_with(&data) {
-                    self.data[idx] = Some(TranspositionTableEntry {
-                        key: key.clone(),
-                        data,
-                    });
+    // The permille of sampled entries that were written during the current search
+    pub fn hashfull(&self) -> usize {
+        let sample_buckets = HASHFULL_SAMPLE_SIZE.div_ceil(ENTRIES_PER_BUCKET);
+        let mut sampled = 0;
+        let mut current = 0;
+
+        for bucket in self.buckets.iter().take(sample_buckets) {
+            for idx in 0..ENTRIES_PER_BUCKET {
+                sampled += 1;
+
+                if let Some((_, data)) = bucket.load(idx) {
+                    if T::unpack(data).is_current(self.generation) {
+                        current += 1;
+                    }
+                }
+            }
+        }
+
+        current * 1000 / sampled
+    }
+
+    pub fn insert(&self, key: &ZobristHash, data: &T) {
+        let bucket = self.get_bucket(key);
+        let key = verification_key(key);
+
+        let mut replace_idx = 0;
+        let mut lowest_priority = i32::MAX;
+
+        for idx in 0..ENTRIES_PER_BUCKET {
+            let Some((entry_key, existing_data)) = bucket.load(idx) else {
+                // Empty entries are always the first to be filled, but we keep looking in case the
+                // position is already stored further along the bucket.
+                if lowest_priority != i32::MIN {
+                    replace_idx = idx;
+                    lowest_priority = i32::MIN;
                 }
-            } else {
-                self.occupied += 1;
 
-                self.data[idx] = Some(TranspositionTableEntry {
-                    key: key.clone(),
-                    data,
-                });
+                continue;
+            };
+
+            let existing = T::unpack(existing_data);
+
+            if entry_key == key {
+                if existing.should_overwrite_with(data) {
+                    bucket.store(idx, key, data.pack());
+                }
+
+                return;
+            }
+
+            let priority = existing.replacement_priority(self.generation);
+
+            if priority < lowest_priority {
+                replace_idx = idx;
+                lowest_priority = priority;
             }
         }
+
+        bucket.store(replace_idx, key, data.pack());
     }
 
-    pub fn get(&self, key: &ZobristHash) -> Option<&T> {
-        let idx = self.get_entry_idx(key);
+    pub fn get(&self, key: &ZobristHash) -> Option<T> {
+        let bucket = self.get_bucket(key);
+        let key = verification_key(key);
 
-        // !: We know the exact size of the table and will always access within the bounds.
-        unsafe {
-            if let Some(entry) = self.data.get_unchecked(idx) {
-                if entry.key == *key {
-                    return Some(&entry.data);
+        for idx in 0..ENTRIES_PER_BUCKET {
+            if let Some((entry_key, data)) = bucket.load(idx) {
+                if entry_key == key {
+                    return Some(T::unpack(data));
                 }
             }
         }
@@ -123,3 +410,135 @@ impl<T: Clone + TTOverwriteable> TranspositionTable<T> {
         None
     }
 }
+
+// Saved tables start with a header:
+//   magic (8 bytes), file version (u32), entry layout version (u32), entries per bucket (u32),
+//   size in MB (u64), number of buckets (u64), generation (u8)
+//
+// Followed by every entry in the table, all little-endian:
+//   key (u16), data (u64)
+const FILE_MAGIC: &[u8; 8] = b"HASHFILE";
+const FILE_VERSION: u32 = 1;
+const FILE_HEADER_SIZE: u64 = 8 + 4 + 4 + 4 + 8 + 8 + 1;
+const FILE_ENTRY_SIZE: u64 = 2 + 8;
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "There are only a few entries per bucket"
+)]
+const FILE_ENTRIES_PER_BUCKET: u32 = ENTRIES_PER_BUCKET as u32;
+
+impl<T: TTOverwriteable + TTPackable + TTSaveable> TranspositionTable<T> {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Unable to create {path}: {e}"))?;
+        let mut writer = BufWriter::new(file);
+
+        self.write_to(&mut writer)
+            .and_then(|()| writer.flush())
+            .map_err(|e| format!("Unable to write {path}: {e}"))
+    }
+
+    // Replaces the contents of the table with a saved table, resizing it to match. Returns the new
+    // size of the table in MB.
+    pub fn load(&mut self, path: &str) -> Result<usize, String> {
+        let file = File::open(path).map_err(|e| format!("Unable to read {path}: {e}"))?;
+        let file_size = file
+            .metadata()
+            .map_err(|e| format!("Unable to read {path}: {e}"))?
+            .len();
+        let mut reader = BufReader::new(file);
+
+        // Check everything we can before touching the table, so that a bad file leaves the
+        // current contents alone.
+        let (size_mb, generation) = Self::read_header(&mut reader, file_size)
+            .map_err(|e| format!("Unable to load {path}: {e}"))?;
+
+        self.resize(size_mb);
+
+        if let Err(e) = self.read_entries(&mut reader) {
+            self.reset();
+            return Err(format!("Unable to read {path}: {e}"));
+        }
+
+        self.generation = generation % GENERATION_CYCLE;
+        Ok(size_mb)
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&T::LAYOUT_VERSION.to_le_bytes())?;
+        writer.write_all(&FILE_ENTRIES_PER_BUCKET.to_le_bytes())?;
+        writer.write_all(&(self.size as u64).to_le_bytes())?;
+        writer.write_all(&(self.buckets.len() as u64).to_le_bytes())?;
+        writer.write_all(&[self.generation])?;
+
+        for bucket in self.buckets.iter() {
+            for idx in 0..ENTRIES_PER_BUCKET {
+                let key = bucket.keys[idx].load(Ordering::Relaxed);
+                let data = bucket.data[idx].load(Ordering::Relaxed);
+
+                writer.write_all(&key.to_le_bytes())?;
+                writer.write_all(&data.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_header(reader: &mut impl Read, file_size: u64) -> Result<(usize, u8), String> {
+        let read_error = |e: std::io::Error| e.to_string();
+
+        let magic: [u8; 8] = read_bytes(reader).map_err(read_error)?;
+
+        if &magic != FILE_MAGIC {
+            return Err("not a saved hash file".to_string());
+        }
+
+        let file_version = u32::from_le_bytes(read_bytes(reader).map_err(read_error)?);
+        let layout_version = u32::from_le_bytes(read_bytes(reader).map_err(read_error)?);
+        let entries_per_bucket = u32::from_le_bytes(read_bytes(reader).map_err(read_error)?);
+
+        if file_version != FILE_VERSION
+            || layout_version != T::LAYOUT_VERSION
+            || entries_per_bucket != FILE_ENTRIES_PER_BUCKET
+        {
+            return Err("the hash file was saved by an incompatible version".to_string());
+        }
+
+        let size_mb = u64::from_le_bytes(read_bytes(reader).map_err(read_error)?);
+        let number_of_buckets = u64::from_le_bytes(read_bytes(reader).map_err(read_error)?);
+        let [generation] = read_bytes(reader).map_err(read_error)?;
+
+        let size_mb = usize::try_from(size_mb).map_err(|_| "the hash file is too large")?;
+
+        if calculate_number_of_buckets::<T>(size_mb) as u64 != number_of_buckets {
+            return Err("the hash file was saved by an incompatible version".to_string());
+        }
+
+        let expected_file_size =
+            FILE_HEADER_SIZE + number_of_buckets * ENTRIES_PER_BUCKET as u64 * FILE_ENTRY_SIZE;
+
+        if file_size != expected_file_size {
+            return Err("the hash file is incomplete".to_string());
+        }
+
+        Ok((size_mb, generation))
+    }
+
+    fn read_entries(&mut self, reader: &mut impl Read) -> std::io::Result<()> {
+        for bucket in self.buckets.iter_mut() {
+            for idx in 0..ENTRIES_PER_BUCKET {
+                *bucket.keys[idx].get_mut() = u16::from_le_bytes(read_bytes(reader)?);
+                *bucket.data[idx].get_mut() = u64::from_le_bytes(read_bytes(reader)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
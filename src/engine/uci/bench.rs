@@ -109,7 +109,10 @@ pub fn bench(depth: u8) -> u64 {
         let options = EngineOptions::default();
 
         let (mut time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
-        let search_restrictions = SearchRestrictions { depth: Some(depth) };
+        let search_restrictions = SearchRestrictions {
+            depth: Some(depth),
+            ..SearchRestrictions::default()
+        };
 
         let _ = search::search(
             &game,
@@ -9,6 +9,7 @@ pub enum Position {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct GoCmdArguments {
+    pub searchmoves: Vec<UciMove>,
     pub ponder: bool,
     pub wtime: Option<Duration>,
     pub btime: Option<Duration>,
@@ -16,7 +17,8 @@ pub struct GoCmdArguments {
     pub binc: Option<Duration>,
     pub movestogo: Option<u32>,
     pub depth: Option<u8>,
-    pub nodes: Option<u32>,
+    pub nodes: Option<u64>,
+    pub mate: Option<u8>,
     pub movetime: Option<Duration>,
     pub infinite: bool,
 }
@@ -50,5 +52,12 @@ pub enum UciCommand {
     Stop,
     PonderHit,
     Bench,
+    Spsa,
+    SaveHash {
+        path: String,
+    },
+    LoadHash {
+        path: String,
+    },
     Quit,
 }
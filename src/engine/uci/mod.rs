@@ -4,11 +4,11 @@ use std::io::{BufRead, IsTerminal};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-use crate::chess::moves::{Move, MoveListExt};
+use crate::chess::moves::Move;
 use crate::chess::{perft, san};
 
 use crate::engine::options::EngineOptions;
-use crate::engine::{eval, search, uci, util};
+use crate::engine::{eval, search, uci, util, xboard};
 use crate::uci::commands::DebugCommand;
 use crate::uci::options::UciOption;
 use crate::ENGINE_NAME;
@@ -39,10 +39,16 @@ pub use r#move::UciMove;
 #[derive(Clone)]
 pub struct UciReporter {
     pub pretty_output: bool,
+
+    // Whether to send castling moves as the king capturing its own rook
+    pub chess960: bool,
+
+    // The first two moves of the latest main line, so we can suggest a move to ponder on
+    pub ponder_line: Option<(Move, Move)>,
 }
 
 impl UciReporter {
-    fn uci_report_search_progress(progress: &search::SearchInfo) {
+    fn uci_report_search_progress(&self, game: &Game, progress: &search::SearchInfo) {
         let score = match progress.score {
             SearchScore::Centipawns(cp) => InfoScore::Centipawns(cp),
             SearchScore::Mate(moves) => InfoScore::Mate(moves),
@@ -51,15 +57,9 @@ impl UciReporter {
         send_response(&UciResponse::Info(InfoFields {
             depth: Some(progress.depth),
             seldepth: Some(progress.seldepth),
+            multipv: Some(progress.multipv),
             score: Some(score),
-            pv: Some(
-                progress
-                    .pv
-                    .clone()
-                    .into_iter()
-                    .map(std::convert::Into::into)
-                    .collect(),
-            ),
+            pv: Some(self.uci_moves(game, progress.pv.clone())),
             time: Some(progress.stats.time),
             nodes: Some(progress.stats.nodes),
             nps: Some(progress.stats.nodes_per_second),
@@ -79,8 +79,13 @@ impl UciReporter {
 
         let mut game = game.clone();
 
-        print!(" {:>3}", progress.depth);
-        print!("{}", format!("/{:<3}", progress.seldepth).bright_black());
+        // Stack MultiPV lines under the main line, rather than repeating the depth for each one
+        if progress.multipv == 1 {
+            print!(" {:>3}", progress.depth);
+            print!("{}", format!("/{:<3}", progress.seldepth).bright_black());
+        } else {
+            print!(" {:>7}", format!("#{}", progress.multipv).bright_black());
+        }
 
         print!(
             " {:>7}",
@@ -152,10 +157,32 @@ impl UciReporter {
         println!();
     }
 
-    fn uci_best_move(mv: Move) {
+    // Converting castling moves to Chess960 notation depends on the position, so we need to
+    // play through the moves as we convert them.
+    fn uci_moves(&self, game: &Game, moves: impl IntoIterator<Item = Move>) -> Vec<UciMove> {
+        let mut game = game.clone();
+
+        moves
+            .into_iter()
+            .map(|mv| {
+                let uci_move = UciMove::from_move(mv, &game, self.chess960);
+                game.make_move(mv);
+                uci_move
+            })
+            .collect()
+    }
+
+    fn uci_best_move(&self, game: &Game, mv: Move) {
+        let ponder_line = match self.ponder_line {
+            Some((best_move, ponder_move)) if best_move == mv => vec![mv, ponder_move],
+            _ => vec![mv],
+        };
+
+        let mut moves = self.uci_moves(game, ponder_line).into_iter();
+
         send_response(&UciResponse::BestMove {
-            mv: mv.into(),
-            ponder: None,
+            mv: moves.next().unwrap(),
+            ponder: moves.next(),
         });
     }
 
@@ -170,22 +197,44 @@ impl Reporter for UciReporter {
     }
 
     fn report_search_progress(&mut self, game: &Game, progress: search::SearchInfo) {
+        if progress.multipv == 1 {
+            if let (Some(best_move), Some(ponder_move)) =
+                (progress.pv.first(), progress.pv.ponder_move())
+            {
+                self.ponder_line = Some((*best_move, *ponder_move));
+            }
+        }
+
         if self.pretty_output {
             Self::pretty_report_search_progress(game, &progress);
         } else {
-            Self::uci_report_search_progress(&progress);
+            self.uci_report_search_progress(game, &progress);
         }
     }
 
+    fn book_move(&self, game: &Game, mv: Move) {
+        let mv = UciMove::from_move(mv, game, self.chess960);
+        println!("info string Playing book move {mv}");
+    }
+
     fn best_move(&self, game: &Game, mv: Move) {
         if self.pretty_output {
             Self::pretty_best_move(game, mv);
         } else {
-            Self::uci_best_move(mv);
+            self.uci_best_move(game, mv);
         }
     }
 }
 
+#[rustfmt::skip]
+fn print_eval_component(name: &str, component: &eval::EvalComponent) {
+    println!("{name}:");
+    println!("  White:                 {}       {}         {}", component.phased_player_eval.white().midgame(), component.phased_player_eval.white().endgame(), component.player_eval.white());
+    println!("  Black:                 {}       {}         {}", component.phased_player_eval.black().midgame(), component.phased_player_eval.black().endgame(), component.player_eval.black());
+    println!("  Total:                                        {}", component.eval);
+    println!();
+}
+
 pub struct Uci {
     control: Option<Control>,
     is_stopped: Arc<LockLatch>,
@@ -217,8 +266,27 @@ impl Uci {
                 // Options
                 send_response(&UciResponse::option::<uci::options::HashOption>());
                 send_response(&UciResponse::option::<uci::options::ThreadsOption>());
+                send_response(&UciResponse::option::<uci::options::MultiPvOption>());
+                send_response(&UciResponse::option::<uci::options::PonderOption>());
+                send_response(&UciResponse::option::<uci::options::Chess960Option>());
                 send_response(&UciResponse::option::<uci::options::MoveOverheadOption>());
                 send_response(&UciResponse::option::<uci::options::SyzygyPath>());
+                send_response(&UciResponse::option::<uci::options::OwnBookOption>());
+                send_response(&UciResponse::option::<uci::options::BookFile>());
+                #[cfg(feature = "nnue")]
+                send_response(&UciResponse::option::<uci::options::EvalFile>());
+
+                #[cfg(feature = "spsa")]
+                for tunable in search::params::tunables() {
+                    send_response(&UciResponse::Option {
+                        name: tunable.name,
+                        def: uci::options::UciOptionType::Spin {
+                            default: usize::try_from(tunable.default).unwrap(),
+                            min: usize::try_from(tunable.min).unwrap(),
+                            max: usize::try_from(tunable.max).unwrap(),
+                        },
+                    });
+                }
 
                 send_response(&UciResponse::UciOk);
             }
@@ -233,6 +301,16 @@ impl Uci {
 
                         if let Ok(mut tt_handle) = self.persistent_state.try_lock() {
                             tt_handle.tt.resize(new_size);
+
+                            let huge_pages = if tt_handle.tt.uses_huge_pages() {
+                                "with"
+                            } else {
+                                "without"
+                            };
+
+                            self.reporter.generic_report(&format!(
+                                "info string Allocated {new_size} MB hash {huge_pages} huge pages"
+                            ));
                         } else {
                             self.reporter
                                 .generic_report("error: Unable to change TT size during search");
@@ -243,6 +321,16 @@ impl Uci {
                     options::ThreadsOption::NAME => {
                         options::ThreadsOption::set(&mut self.options, value)
                     }
+                    options::MultiPvOption::NAME => {
+                        options::MultiPvOption::set(&mut self.options, value)
+                    }
+                    options::PonderOption::NAME => {
+                        options::PonderOption::set(&mut self.options, value)
+                    }
+                    options::Chess960Option::NAME => {
+                        options::Chess960Option::set(&mut self.options, value)
+                            .map(|chess960| self.reporter.chess960 = chess960)
+                    }
                     options::MoveOverheadOption::NAME => {
                         options::MoveOverheadOption::set(&mut self.options, value)
                     }
@@ -258,6 +346,43 @@ impl Uci {
 
                         Ok(())
                     }
+                    options::OwnBookOption::NAME => {
+                        options::OwnBookOption::set(&mut self.options, value)
+                    }
+                    options::BookFile::NAME => {
+                        let book_file = options::BookFile::set(&mut self.options, value);
+
+                        if let Ok(mut state_handle) = self.persistent_state.try_lock() {
+                            state_handle.book.load(&book_file)
+                        } else {
+                            self.reporter
+                                .generic_report("error: Unable to change BookFile during search");
+                            Ok(())
+                        }
+                    }
+                    #[cfg(feature = "nnue")]
+                    options::EvalFile::NAME => {
+                        let eval_file = options::EvalFile::set(&mut self.options, value);
+
+                        // Holding the lock makes sure no search switches networks part way
+                        // through, with accumulators built by the old one
+                        if let Ok(_state_handle) = self.persistent_state.try_lock() {
+                            eval::nnue::load(&eval_file).map(|()| {
+                                // The current position's accumulators were built with the old
+                                // network
+                                self.game.incremental_eval =
+                                    eval::IncrementalEvalFields::init(&self.game.board);
+                            })
+                        } else {
+                            self.reporter
+                                .generic_report("error: Unable to change EvalFile during search");
+                            Ok(())
+                        }
+                    }
+                    #[cfg(feature = "spsa")]
+                    _ if search::params::tunables().iter().any(|t| t.name == name) => {
+                        search::params::set(name, value)
+                    }
                     _ => return Err(format!("Unknown option: {name}")),
                 }
                 .map_err(|e| format!("Unable to set {name}: {e:?}"))?;
@@ -276,21 +401,25 @@ impl Uci {
                 };
 
                 for mv in moves {
-                    let matching_move = game.moves().expect_matching(mv.src, mv.dst, mv.promotion);
+                    let matching_move = mv
+                        .to_move(&game)
+                        .ok_or_else(|| format!("Illegal move: {mv}"))?;
                     game.make_move(matching_move);
                 }
 
                 self.game = game;
             }
             UciCommand::Go(GoCmdArguments {
-                ponder: _,
+                searchmoves,
+                ponder,
                 wtime,
                 btime,
                 winc,
                 binc,
                 movestogo,
                 depth,
-                nodes: _,
+                nodes,
+                mate,
                 movetime,
                 infinite: _,
             }) => {
@@ -319,9 +448,25 @@ impl Uci {
                 let (mut time_strategy, control) =
                     TimeStrategy::new(&self.game, &time_control, &options);
 
+                if *ponder {
+                    time_strategy.start_pondering();
+                }
+
                 self.control = Some(control);
 
-                let search_restrictions = SearchRestrictions { depth: *depth };
+                // Ignore any moves that aren't legal in this position. If none of the moves are
+                // legal, search all moves so that we still return a move.
+                let search_moves = searchmoves
+                    .iter()
+                    .filter_map(|mv| mv.to_move(&self.game))
+                    .collect::<Vec<_>>();
+
+                let search_restrictions = SearchRestrictions {
+                    depth: *depth,
+                    nodes: *nodes,
+                    mate: *mate,
+                    search_moves: (!search_moves.is_empty()).then_some(search_moves),
+                };
 
                 let persistent_state = self.persistent_state.clone();
                 let is_stopped = self.is_stopped.clone();
@@ -338,6 +483,9 @@ impl Uci {
                         &mut reporter,
                     );
 
+                    // We're not allowed to report a move until we've stopped pondering
+                    time_strategy.wait_until_ponder_finished();
+
                     reporter.best_move(&game, best_move);
                     is_stopped.set();
                 });
@@ -373,10 +521,9 @@ impl Uci {
                 },
                 DebugCommand::Move { moves } => {
                     for mv in moves {
-                        let matching_move =
-                            self.game
-                                .moves()
-                                .expect_matching(mv.src, mv.dst, mv.promotion);
+                        let matching_move = mv
+                            .to_move(&self.game)
+                            .ok_or_else(|| format!("Illegal move: {mv}"))?;
 
                         self.game.make_move(matching_move);
                     }
@@ -410,7 +557,6 @@ impl Uci {
                     println!("total: {total}");
                     println!();
                 }
-                #[rustfmt::skip]
                 DebugCommand::Eval => {
                     let eval_components = eval::eval_components(&self.game);
 
@@ -419,24 +565,28 @@ impl Uci {
 
                     println!("                         Midgame     Endgame    Actual");
 
-                    let pst = eval_components.piece_square;
-                    println!("Piece square tables:");
-                    println!("  White:                 {}       {}         {}", pst.phased_player_eval.white().midgame(), pst.phased_player_eval.white().endgame(), pst.player_eval.white());
-                    println!("  Black:                 {}       {}         {}", pst.phased_player_eval.black().midgame(), pst.phased_player_eval.black().endgame(), pst.player_eval.black());
-                    println!("  Total:                                        {}", pst.eval);
-                    println!();
-
-                    let passed_pawns = eval_components.passed_pawns;
-                    println!("Passed pawns:");
-                    println!("  White:                 {}       {}         {}", passed_pawns.phased_player_eval.white().midgame(), passed_pawns.phased_player_eval.white().endgame(), passed_pawns.player_eval.white());
-                    println!("  Black:                 {}       {}         {}", passed_pawns.phased_player_eval.black().midgame(), passed_pawns.phased_player_eval.black().endgame(), passed_pawns.player_eval.black());
-                    println!("  Total:                                        {}", passed_pawns.eval);
-                    println!();
+                    print_eval_component("Piece square tables", &eval_components.piece_square);
+                    print_eval_component("Passed pawns", &eval_components.passed_pawns);
+                    print_eval_component("Doubled pawns", &eval_components.doubled_pawns);
+                    print_eval_component("Isolated pawns", &eval_components.isolated_pawns);
+                    print_eval_component("Backward pawns", &eval_components.backward_pawns);
+                    print_eval_component("Phalanx pawns", &eval_components.phalanx_pawns);
+                    print_eval_component("Supported pawns", &eval_components.supported_pawns);
+
+                    #[cfg(feature = "nnue")]
+                    {
+                        println!("NNUE eval: {}", eval::nnue::absolute_eval(&self.game));
+                        println!();
+                    }
 
                     println!("Eval: {}", eval_components.eval);
                 }
             },
-            UciCommand::PonderHit => {}
+            UciCommand::PonderHit => {
+                if let Some(c) = self.control.as_ref() {
+                    c.ponderhit();
+                }
+            }
             // For OpenBench to understand NPS values for different workers
             UciCommand::Bench => {
                 let started_at = Instant::now();
@@ -447,6 +597,42 @@ impl Uci {
 
                 println!("{nodes} nodes {nps} nps");
             }
+            #[cfg(feature = "spsa")]
+            UciCommand::Spsa => print!("{}", search::params::openbench_spsa_input()),
+            #[cfg(not(feature = "spsa"))]
+            UciCommand::Spsa => {
+                println!("Tunable search parameters require the 'spsa' feature to be enabled");
+            }
+            UciCommand::SaveHash { path } => {
+                if let Ok(state_handle) = self.persistent_state.try_lock() {
+                    match state_handle.tt.save(path) {
+                        Ok(()) => self
+                            .reporter
+                            .generic_report(&format!("info string Saved hash to {path}")),
+                        Err(e) => self.reporter.generic_report(&format!("error: {e}")),
+                    }
+                } else {
+                    self.reporter
+                        .generic_report("error: Unable to save hash during search");
+                }
+            }
+            UciCommand::LoadHash { path } => {
+                if let Ok(mut state_handle) = self.persistent_state.try_lock() {
+                    match state_handle.tt.load(path) {
+                        Ok(size_mb) => {
+                            // The table takes the size it was saved with
+                            self.options.hash_size = size_mb;
+                            self.reporter.generic_report(&format!(
+                                "info string Loaded hash from {path} ({size_mb} MB)"
+                            ));
+                        }
+                        Err(e) => self.reporter.generic_report(&format!("error: {e}")),
+                    }
+                } else {
+                    self.reporter
+                        .generic_report("error: Unable to load hash during search");
+                }
+            }
             UciCommand::Quit => return Ok(ExecuteResult::Exit),
         }
 
@@ -531,6 +717,25 @@ pub enum UciInputMode {
 }
 
 pub fn uci(uci_input_mode: UciInputMode) -> Result<(), String> {
+    // Interfaces using the XBoard protocol announce themselves with 'xboard' as their first
+    // command, so we need to look at it before committing to UCI
+    let first_line = match uci_input_mode {
+        UciInputMode::Stdin => {
+            let mut line = String::new();
+            std::io::stdin()
+                .read_line(&mut line)
+                .map_err(|e| format!("Error: {e}"))?;
+
+            if line.trim() == "xboard" {
+                xboard::xboard();
+                return Ok(());
+            }
+
+            Some(line)
+        }
+        UciInputMode::Commands(_) => None,
+    };
+
     let options = EngineOptions::default();
 
     let mut uci = Uci {
@@ -538,6 +743,8 @@ pub fn uci(uci_input_mode: UciInputMode) -> Result<(), String> {
         is_stopped: Arc::new(LockLatch::new()),
         reporter: UciReporter {
             pretty_output: std::io::stdin().is_terminal(),
+            chess960: options.chess960,
+            ponder_line: None,
         },
         debug: false,
         persistent_state: Arc::new(Mutex::new(PersistentState::new(options.hash_size))),
@@ -551,5 +758,14 @@ pub fn uci(uci_input_mode: UciInputMode) -> Result<(), String> {
         },
     };
 
+    if let Some(line) = first_line.filter(|line| !line.trim().is_empty()) {
+        if !uci
+            .run_line(line.trim_end())
+            .map_err(|e| format!("Error: {e}"))?
+        {
+            return Ok(());
+        }
+    }
+
     uci.main_loop(uci_input_mode)
 }
@@ -1,6 +1,7 @@
+use crate::chess::game::{CastleRightsSide, Game};
 use crate::chess::moves::Move;
 use crate::chess::piece::PromotionPieceKind;
-use crate::chess::square::Square;
+use crate::chess::square::{squares, Square};
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub struct UciMove {
@@ -10,6 +11,47 @@ pub struct UciMove {
 }
 
 impl UciMove {
+    /// In Chess960 mode, castling moves are sent as the king capturing its own rook since the
+    /// king's destination alone may be ambiguous.
+    pub fn from_move(mv: Move, game: &Game, chess960: bool) -> Self {
+        if chess960 && mv.is_castling() {
+            let side = CastleRightsSide::from_castle_dest(mv.dst());
+
+            if let Some(rook) = game.castle_rook_square(game.player, side) {
+                return Self {
+                    src: mv.src(),
+                    dst: rook,
+                    promotion: None,
+                };
+            }
+        }
+
+        mv.into()
+    }
+
+    /// Find the legal move in the position matching this move, if there is one. We accept
+    /// castling as either the king moving to its destination or capturing its own rook.
+    pub fn to_move(self, game: &Game) -> Option<Move> {
+        let is_king_move = game.board.king(game.player).contains(self.src);
+
+        let king_takes_rook_side = CastleRightsSide::ALL.into_iter().find(|side| {
+            is_king_move && game.castle_rook_square(game.player, *side) == Some(self.dst)
+        });
+
+        if let Some(side) = king_takes_rook_side {
+            let castle_dest = squares::castle_dest(game.player, side);
+
+            return game
+                .moves()
+                .into_iter()
+                .find(|mv| mv.is_castling() && mv.dst() == castle_dest);
+        }
+
+        game.moves().into_iter().find(|mv| {
+            mv.src() == self.src && mv.dst() == self.dst && mv.promotion() == self.promotion
+        })
+    }
+
     pub fn notation(self) -> String {
         format!(
             "{}{}{}",
@@ -33,7 +33,7 @@ impl UciOption for HashOption {
     const DEF: UciOptionType = UciOptionType::Spin {
         default: crate::engine::options::defaults::HASH_SIZE,
         min: 0,
-        max: 1024,
+        max: 1_048_576,
     };
 }
 
@@ -53,7 +53,7 @@ impl UciOption for ThreadsOption {
     const DEF: UciOptionType = UciOptionType::Spin {
         default: crate::engine::options::defaults::THREADS,
         min: 1,
-        max: 1,
+        max: 1024,
     };
 }
 
@@ -66,6 +66,62 @@ impl ThreadsOption {
     }
 }
 
+pub struct MultiPvOption;
+
+impl UciOption for MultiPvOption {
+    const NAME: &'static str = "MultiPV";
+    const DEF: UciOptionType = UciOptionType::Spin {
+        default: crate::engine::options::defaults::MULTI_PV,
+        min: 1,
+        max: 256,
+    };
+}
+
+impl MultiPvOption {
+    pub fn set(options: &mut EngineOptions, value: &str) -> Result<(), String> {
+        let multi_pv = value.parse::<usize>().map_err(|_| "Invalid value")?;
+
+        options.multi_pv = multi_pv;
+        Ok(())
+    }
+}
+
+pub struct PonderOption;
+
+impl UciOption for PonderOption {
+    const NAME: &'static str = "Ponder";
+    const DEF: UciOptionType = UciOptionType::Check {
+        default: crate::engine::options::defaults::PONDER,
+    };
+}
+
+impl PonderOption {
+    pub fn set(options: &mut EngineOptions, value: &str) -> Result<(), String> {
+        let ponder = value.parse::<bool>().map_err(|_| "Invalid value")?;
+
+        options.ponder = ponder;
+        Ok(())
+    }
+}
+
+pub struct Chess960Option;
+
+impl UciOption for Chess960Option {
+    const NAME: &'static str = "UCI_Chess960";
+    const DEF: UciOptionType = UciOptionType::Check {
+        default: crate::engine::options::defaults::CHESS960,
+    };
+}
+
+impl Chess960Option {
+    pub fn set(options: &mut EngineOptions, value: &str) -> Result<bool, String> {
+        let chess960 = value.parse::<bool>().map_err(|_| "Invalid value")?;
+
+        options.chess960 = chess960;
+        Ok(chess960)
+    }
+}
+
 pub struct MoveOverheadOption;
 
 impl UciOption for MoveOverheadOption {
@@ -100,3 +156,54 @@ impl SyzygyPath {
         path
     }
 }
+
+pub struct OwnBookOption;
+
+impl UciOption for OwnBookOption {
+    const NAME: &'static str = "OwnBook";
+    const DEF: UciOptionType = UciOptionType::Check {
+        default: crate::engine::options::defaults::OWN_BOOK,
+    };
+}
+
+impl OwnBookOption {
+    pub fn set(options: &mut EngineOptions, value: &str) -> Result<(), String> {
+        let own_book = value.parse::<bool>().map_err(|_| "Invalid value")?;
+
+        options.own_book = own_book;
+        Ok(())
+    }
+}
+
+pub struct BookFile;
+
+impl UciOption for BookFile {
+    const NAME: &'static str = "BookFile";
+    const DEF: UciOptionType = UciOptionType::String { default: "" };
+}
+
+impl BookFile {
+    pub fn set(options: &mut EngineOptions, value: &str) -> String {
+        let path = value.to_string();
+        options.book_file = Some(path.clone());
+        path
+    }
+}
+
+#[cfg(feature = "nnue")]
+pub struct EvalFile;
+
+#[cfg(feature = "nnue")]
+impl UciOption for EvalFile {
+    const NAME: &'static str = "EvalFile";
+    const DEF: UciOptionType = UciOptionType::String { default: "" };
+}
+
+#[cfg(feature = "nnue")]
+impl EvalFile {
+    pub fn set(options: &mut EngineOptions, value: &str) -> String {
+        let path = value.to_string();
+        options.eval_file = Some(path.clone());
+        path
+    }
+}
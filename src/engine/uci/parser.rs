@@ -216,6 +216,11 @@ fn cmd_go(input: &str) -> IResult<&str, UciCommand> {
         preceded(
             space1,
             alt((
+                command_with_argument("searchmoves", uci_moves, |searchmoves| {
+                    GoCmdArgumentsModifyFn::new(move |acc: &mut GoCmdArguments| {
+                        acc.searchmoves = searchmoves;
+                    })
+                }),
                 command_without_arguments("ponder", |_| {
                     GoCmdArgumentsModifyFn::new(move |acc: &mut GoCmdArguments| {
                         acc.ponder = true;
@@ -251,11 +256,16 @@ fn cmd_go(input: &str) -> IResult<&str, UciCommand> {
                         acc.depth = Some(depth);
                     })
                 }),
-                command_with_argument("nodes", nom::character::complete::u32, |nodes| {
+                command_with_argument("nodes", nom::character::complete::u64, |nodes| {
                     GoCmdArgumentsModifyFn::new(move |acc: &mut GoCmdArguments| {
                         acc.nodes = Some(nodes);
                     })
                 }),
+                command_with_argument("mate", nom::character::complete::u8, |mate| {
+                    GoCmdArgumentsModifyFn::new(move |acc: &mut GoCmdArguments| {
+                        acc.mate = Some(mate);
+                    })
+                }),
                 command_with_argument("movetime", nom::character::complete::i64, |movetime| {
                     GoCmdArgumentsModifyFn::new(move |acc: &mut GoCmdArguments| {
                         acc.movetime = Some(parse_duration(movetime));
@@ -269,6 +279,7 @@ fn cmd_go(input: &str) -> IResult<&str, UciCommand> {
             )),
         ),
         || GoCmdArguments {
+            searchmoves: Vec::new(),
             ponder: false,
             wtime: None,
             btime: None,
@@ -277,6 +288,7 @@ fn cmd_go(input: &str) -> IResult<&str, UciCommand> {
             movestogo: None,
             depth: None,
             nodes: None,
+            mate: None,
             movetime: None,
             infinite: false,
         },
@@ -363,6 +375,22 @@ fn cmd_bench(input: &str) -> IResult<&str, UciCommand> {
     value(UciCommand::Bench, tag("bench"))(input)
 }
 
+fn cmd_spsa(input: &str) -> IResult<&str, UciCommand> {
+    value(UciCommand::Spsa, tag("spsa"))(input)
+}
+
+fn cmd_savehash(input: &str) -> IResult<&str, UciCommand> {
+    command_with_argument("savehash", rest, |path: &str| UciCommand::SaveHash {
+        path: path.trim_end().to_string(),
+    })(input)
+}
+
+fn cmd_loadhash(input: &str) -> IResult<&str, UciCommand> {
+    command_with_argument("loadhash", rest, |path: &str| UciCommand::LoadHash {
+        path: path.trim_end().to_string(),
+    })(input)
+}
+
 fn cmd_quit(input: &str) -> IResult<&str, UciCommand> {
     value(UciCommand::Quit, tag("quit"))(input)
 }
@@ -381,6 +409,9 @@ pub(super) fn any_uci_command(input: &str) -> IResult<&str, UciCommand> {
         cmd_stop,
         cmd_ponderhit,
         cmd_bench,
+        cmd_spsa,
+        cmd_savehash,
+        cmd_loadhash,
         cmd_d,
         cmd_quit,
     ))(input)?;
@@ -409,6 +440,66 @@ mod tests {
         assert!(parse("go infinite").is_ok());
     }
 
+    #[test]
+    fn parse_go_searchmoves() {
+        use crate::chess::square::squares::all::*;
+
+        let cmd = parse("go depth 5 searchmoves e2e4 d2d4 movetime 1500").unwrap();
+
+        let UciCommand::Go(args) = cmd else {
+            panic!("Expected a go command");
+        };
+
+        assert_eq!(args.depth, Some(5));
+        assert_eq!(args.movetime, Some(Duration::from_millis(1500)));
+        assert_eq!(
+            args.searchmoves,
+            vec![
+                UciMove {
+                    src: E2,
+                    dst: E4,
+                    promotion: None
+                },
+                UciMove {
+                    src: D2,
+                    dst: D4,
+                    promotion: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_go_nodes_and_mate() {
+        let cmd = parse("go nodes 5000000000 mate 3").unwrap();
+
+        let UciCommand::Go(args) = cmd else {
+            panic!("Expected a go command");
+        };
+
+        assert_eq!(args.nodes, Some(5_000_000_000));
+        assert_eq!(args.mate, Some(3));
+    }
+
+    #[test]
+    fn parse_savehash_and_loadhash() {
+        assert_eq!(
+            parse("savehash /tmp/analysis.hash").unwrap(),
+            UciCommand::SaveHash {
+                path: "/tmp/analysis.hash".to_string()
+            }
+        );
+
+        assert_eq!(
+            parse("loadhash my analysis.hash").unwrap(),
+            UciCommand::LoadHash {
+                path: "my analysis.hash".to_string()
+            }
+        );
+
+        assert!(parse("loadhash").is_err());
+    }
+
     #[test]
     fn test_uci() {
         let ml = parse("uci").unwrap();
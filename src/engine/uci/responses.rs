@@ -20,6 +20,7 @@ pub(super) enum IdParam {
 pub struct InfoFields {
     pub(super) depth: Option<u8>,
     pub(super) seldepth: Option<u8>,
+    pub(super) multipv: Option<usize>,
     pub(super) time: Option<Duration>,
     pub(super) nodes: Option<u64>,
     pub(super) pv: Option<Vec<UciMove>>,
@@ -74,6 +75,7 @@ impl std::fmt::Display for UciResponse {
             Self::Info(InfoFields {
                 depth,
                 seldepth,
+                multipv,
                 time,
                 nodes,
                 pv,
@@ -93,6 +95,10 @@ impl std::fmt::Display for UciResponse {
                     write!(f, " seldepth {seldepth}")?;
                 }
 
+                if let Some(multipv) = multipv {
+                    write!(f, " multipv {multipv}")?;
+                }
+
                 if let Some(score) = score {
                     match score {
                         InfoScore::Centipawns(centipawns) => {
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum XboardCommand {
+    Xboard,
+    Protover(u32),
+    New,
+    Force,
+    Go,
+    UserMove(String),
+    Level {
+        // Zero for a single session covering the whole game
+        moves_per_session: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    St(Duration),
+    Sd(u8),
+    Time(Duration),
+    Otim(Duration),
+    Post,
+    NoPost,
+    Undo,
+    Remove,
+    SetBoard(String),
+    Ping(u32),
+    MoveNow,
+    Result,
+    Quit,
+
+    // Commands which we understand but which have no effect on us, e.g. 'random' or 'accepted'
+    Ignored,
+}
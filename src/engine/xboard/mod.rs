@@ -0,0 +1,590 @@
+//! Implementation of the Chess Engine Communication Protocol (CECP), also known as the `XBoard`
+//! protocol
+
+use std::io::BufRead;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::chess::game::Game;
+use crate::chess::moves::Move;
+use crate::chess::player::Player;
+use crate::chess::san;
+use crate::engine::options::EngineOptions;
+use crate::engine::search::time_control::{Control, TimeStrategy};
+use crate::engine::search::{
+    self, Clocks, PersistentState, Reporter, SearchRestrictions, SearchScore, TimeControl,
+};
+use crate::engine::uci::parser::uci_moves;
+use crate::engine::uci::UciMove;
+use crate::ENGINE_NAME;
+
+use self::commands::XboardCommand;
+
+pub mod commands;
+pub mod parser;
+
+// XBoard reports mate scores as a large score plus the number of moves to mate
+const MATE_SCORE: i32 = 100_000;
+
+struct XboardReporter {
+    post: bool,
+}
+
+impl Reporter for XboardReporter {
+    fn generic_report(&self, s: &str) {
+        // Lines starting with '#' are ignored by the interface
+        println!("# {s}");
+    }
+
+    fn report_search_progress(&mut self, game: &Game, progress: search::SearchInfo) {
+        if !self.post || progress.multipv != 1 {
+            return;
+        }
+
+        let score = match progress.score {
+            SearchScore::Centipawns(cp) => i32::from(cp),
+            SearchScore::Mate(moves) => MATE_SCORE * i32::from(moves.signum()) + i32::from(moves),
+        };
+
+        let mut game = game.clone();
+        let pv = progress
+            .pv
+            .clone()
+            .into_iter()
+            .map(|mv| {
+                let notation = UciMove::from_move(mv, &game, false).notation();
+                game.make_move(mv);
+                notation
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        println!(
+            "{} {score} {} {} {pv}",
+            progress.depth,
+            progress.stats.time.as_millis() / 10,
+            progress.stats.nodes
+        );
+    }
+
+    fn book_move(&self, game: &Game, mv: Move) {
+        let notation = UciMove::from_move(mv, game, false).notation();
+        self.generic_report(&format!("Playing book move {notation}"));
+    }
+
+    // Moves are sent back to the main thread to be played, rather than reported directly
+    fn best_move(&self, _game: &Game, _mv: Move) {}
+}
+
+enum Event {
+    Input(String),
+    InputClosed,
+    SearchFinished { id: u64, mv: Move },
+}
+
+enum TimeSetting {
+    Level {
+        moves_per_session: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    MoveTime(Duration),
+}
+
+struct RunningSearch {
+    id: u64,
+    control: Control,
+}
+
+pub struct Xboard {
+    game: Game,
+    options: EngineOptions,
+    persistent_state: Arc<Mutex<PersistentState>>,
+
+    // The side the engine is playing, or None if we're in force mode
+    engine_player: Option<Player>,
+
+    time_setting: TimeSetting,
+    depth: Option<u8>,
+    engine_clock: Duration,
+    opponent_clock: Duration,
+    post: bool,
+
+    // Results from searches which have since been abandoned (e.g. by 'force' or 'undo') are
+    // ignored by checking the search ID
+    search: Option<RunningSearch>,
+    searches_started: u64,
+    events: Sender<Event>,
+}
+
+impl Xboard {
+    fn new(options: EngineOptions, events: Sender<Event>) -> Self {
+        Self {
+            game: Game::new(),
+            persistent_state: Arc::new(Mutex::new(PersistentState::new(options.hash_size))),
+            options,
+            engine_player: Some(Player::Black),
+
+            // XBoard's default time control
+            time_setting: TimeSetting::Level {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::ZERO,
+            },
+            depth: None,
+            engine_clock: Duration::from_secs(300),
+            opponent_clock: Duration::from_secs(300),
+            post: false,
+
+            search: None,
+            searches_started: 0,
+            events,
+        }
+    }
+
+    fn execute(&mut self, cmd: XboardCommand) -> ExecuteResult {
+        match cmd {
+            XboardCommand::Xboard | XboardCommand::Ignored => {}
+            XboardCommand::Protover(_) => {
+                let version = crate::engine_version();
+                println!(
+                    "feature myname=\"{ENGINE_NAME} {version}\" ping=1 setboard=1 usermove=1 \
+                     playother=0 san=0 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 \
+                     colors=0 done=1"
+                );
+            }
+            XboardCommand::New => {
+                self.abandon_search();
+
+                self.game = Game::new();
+                self.engine_player = Some(Player::Black);
+                self.depth = None;
+                self.reset_clocks();
+
+                self.persistent_state.lock().unwrap().reset();
+            }
+            XboardCommand::Force | XboardCommand::Result => {
+                self.abandon_search();
+                self.engine_player = None;
+            }
+            XboardCommand::Go => {
+                self.abandon_search();
+                self.engine_player = Some(self.game.player);
+                self.start_search();
+            }
+            XboardCommand::UserMove(mv) => {
+                self.abandon_search();
+
+                let Some(mv) = parse_move(&self.game, &mv) else {
+                    println!("Illegal move: {mv}");
+                    return ExecuteResult::KeepGoing;
+                };
+
+                self.game.make_move(mv);
+
+                if let Some(result) = game_result(&self.game) {
+                    println!("{result}");
+                } else if self.engine_player == Some(self.game.player) {
+                    self.start_search();
+                }
+            }
+            XboardCommand::Level {
+                moves_per_session,
+                base,
+                increment,
+            } => {
+                self.time_setting = TimeSetting::Level {
+                    moves_per_session,
+                    base,
+                    increment,
+                };
+                self.reset_clocks();
+            }
+            XboardCommand::St(move_time) => self.time_setting = TimeSetting::MoveTime(move_time),
+            XboardCommand::Sd(depth) => self.depth = Some(depth),
+            XboardCommand::Time(time) => self.engine_clock = time,
+            XboardCommand::Otim(time) => self.opponent_clock = time,
+            XboardCommand::Post => self.post = true,
+            XboardCommand::NoPost => self.post = false,
+            XboardCommand::Undo => {
+                self.abandon_search();
+                self.undo_moves(1);
+            }
+            XboardCommand::Remove => {
+                self.abandon_search();
+                self.undo_moves(2);
+            }
+            XboardCommand::SetBoard(fen) => {
+                self.abandon_search();
+
+                match Game::from_fen(&fen) {
+                    Ok(game) => self.game = game,
+                    Err(_) => println!("tellusererror Illegal position"),
+                }
+            }
+            XboardCommand::Ping(n) => println!("pong {n}"),
+            XboardCommand::MoveNow => {
+                // The search will finish and its move will be played as normal
+                if let Some(search) = &self.search {
+                    search.control.stop();
+                }
+            }
+            XboardCommand::Quit => {
+                self.abandon_search();
+                return ExecuteResult::Exit;
+            }
+        }
+
+        ExecuteResult::KeepGoing
+    }
+
+    fn reset_clocks(&mut self) {
+        let base = match self.time_setting {
+            TimeSetting::Level { base, .. } => base,
+            TimeSetting::MoveTime(_) => Duration::ZERO,
+        };
+
+        self.engine_clock = base;
+        self.opponent_clock = base;
+    }
+
+    fn undo_moves(&mut self, count: usize) {
+        for _ in 0..count.min(self.game.history.len()) {
+            self.game.undo_move();
+        }
+    }
+
+    fn time_control(&self) -> TimeControl {
+        match self.time_setting {
+            TimeSetting::MoveTime(move_time) => TimeControl::ExactTime(move_time),
+            TimeSetting::Level {
+                moves_per_session,
+                increment,
+                ..
+            } => {
+                let (white_clock, black_clock) = match self.game.player {
+                    Player::White => (self.engine_clock, self.opponent_clock),
+                    Player::Black => (self.opponent_clock, self.engine_clock),
+                };
+
+                // Each side has made this many moves since the game started. We count from the
+                // move number rather than our own history, which doesn't include the moves before
+                // a position given with 'setboard'.
+                let moves_made = self.game.turn() - 1;
+
+                TimeControl::Clocks(Clocks {
+                    white_clock: Some(white_clock),
+                    black_clock: Some(black_clock),
+                    white_increment: Some(increment),
+                    black_increment: Some(increment),
+                    moves_to_go: (moves_per_session > 0)
+                        .then(|| moves_per_session - moves_made % moves_per_session),
+                })
+            }
+        }
+    }
+
+    fn start_search(&mut self) {
+        if let Some(result) = game_result(&self.game) {
+            println!("{result}");
+            return;
+        }
+
+        let game = self.game.clone();
+        let options = self.options.clone();
+        let persistent_state = self.persistent_state.clone();
+        let events = self.events.clone();
+        let mut reporter = XboardReporter { post: self.post };
+
+        let (mut time_strategy, control) =
+            TimeStrategy::new(&self.game, &self.time_control(), &options);
+
+        let search_restrictions = SearchRestrictions {
+            depth: self.depth,
+            nodes: None,
+            mate: None,
+            search_moves: None,
+        };
+
+        self.searches_started += 1;
+        let id = self.searches_started;
+
+        std::thread::spawn(move || {
+            let mut persistent_state_handle = persistent_state.lock().unwrap();
+
+            let mv = search::search(
+                &game,
+                &mut persistent_state_handle,
+                &mut time_strategy,
+                &search_restrictions,
+                &options,
+                &mut reporter,
+            );
+
+            // The main loop may already have exited if we were told to quit
+            let _result = events.send(Event::SearchFinished { id, mv });
+        });
+
+        self.search = Some(RunningSearch { id, control });
+    }
+
+    fn abandon_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.control.stop();
+        }
+    }
+
+    fn search_finished(&mut self, id: u64, mv: Move) {
+        if self.search.as_ref().is_none_or(|search| search.id != id) {
+            return;
+        }
+
+        self.search = None;
+
+        println!("move {}", UciMove::from_move(mv, &self.game, false));
+        self.game.make_move(mv);
+
+        if let Some(result) = game_result(&self.game) {
+            println!("{result}");
+        }
+    }
+
+    fn run_line(&mut self, line: &str) -> bool {
+        if line.trim().is_empty() {
+            return true;
+        }
+
+        match parser::parse(line) {
+            Ok(cmd) => self.execute(cmd) == ExecuteResult::KeepGoing,
+            Err(e) => {
+                println!("{e}");
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ExecuteResult {
+    KeepGoing,
+    Exit,
+}
+
+// Accept moves in coordinate notation as requested by our features, but fall back to SAN for
+// interfaces which ignore them
+fn parse_move(game: &Game, mv: &str) -> Option<Move> {
+    uci_moves(mv)
+        .ok()
+        .and_then(|(_, moves)| moves.first().and_then(|uci_move| uci_move.to_move(game)))
+        .or_else(|| san::parse_move(game, mv).ok())
+}
+
+fn game_result(game: &Game) -> Option<&'static str> {
+    if game.moves().is_empty() {
+        return Some(if !game.is_king_in_check() {
+            "1/2-1/2 {Stalemate}"
+        } else if game.player == Player::White {
+            "0-1 {Black mates}"
+        } else {
+            "1-0 {White mates}"
+        });
+    }
+
+    if game.is_threefold_repetition() {
+        Some("1/2-1/2 {Draw by repetition}")
+    } else if game.halfmove_clock >= 100 {
+        Some("1/2-1/2 {Draw by fifty move rule}")
+    } else if game.is_insufficient_material() {
+        Some("1/2-1/2 {Draw by insufficient material}")
+    } else {
+        None
+    }
+}
+
+pub fn xboard() {
+    let options = EngineOptions::default();
+    let (events, receiver) = mpsc::channel();
+
+    // Read stdin on another thread so that we can handle input and finished searches in order
+    let input = events.clone();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+
+            if input.send(Event::Input(line)).is_err() {
+                return;
+            }
+        }
+
+        let _result = input.send(Event::InputClosed);
+    });
+
+    let mut xboard = Xboard::new(options, events);
+
+    for event in receiver {
+        match event {
+            Event::Input(line) => {
+                if !xboard.run_line(&line) {
+                    break;
+                }
+            }
+            Event::SearchFinished { id, mv } => xboard.search_finished(id, mv),
+            Event::InputClosed => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    fn xboard() -> (Xboard, Receiver<Event>) {
+        crate::init();
+
+        let (events, receiver) = mpsc::channel();
+        let options = EngineOptions {
+            hash_size: 1,
+            ..EngineOptions::default()
+        };
+
+        (Xboard::new(options, events), receiver)
+    }
+
+    fn run(xboard: &mut Xboard, lines: &[&str]) {
+        for line in lines {
+            assert!(xboard.run_line(line), "{line}");
+        }
+    }
+
+    // Waits for the next search to finish, returning its ID
+    fn next_search_result(receiver: &Receiver<Event>) -> (u64, Move) {
+        match receiver.recv_timeout(Duration::from_secs(10)).unwrap() {
+            Event::SearchFinished { id, mv } => (id, mv),
+            _ => panic!("Expected a search to finish"),
+        }
+    }
+
+    #[test]
+    fn test_new_force_and_go() {
+        let (mut xboard, receiver) = xboard();
+
+        run(&mut xboard, &["new", "sd 1", "force", "usermove e2e4"]);
+        assert_eq!(xboard.engine_player, None);
+        assert_eq!(xboard.game.history.len(), 1);
+        assert!(xboard.search.is_none());
+
+        // 'go' makes the engine play the side to move
+        run(&mut xboard, &["go"]);
+        assert_eq!(xboard.engine_player, Some(Player::Black));
+        assert!(xboard.search.is_some());
+
+        let (id, mv) = next_search_result(&receiver);
+        xboard.search_finished(id, mv);
+        assert_eq!(xboard.game.history.len(), 2);
+        assert!(xboard.search.is_none());
+
+        // 'new' resets the game, with the engine playing black
+        run(&mut xboard, &["new"]);
+        assert_eq!(xboard.engine_player, Some(Player::Black));
+        assert!(xboard.game.history.is_empty());
+        assert!(xboard.search.is_none());
+    }
+
+    #[test]
+    fn test_usermove_starts_search() {
+        let (mut xboard, receiver) = xboard();
+
+        run(&mut xboard, &["new", "sd 1", "usermove e2e4"]);
+        assert!(xboard.search.is_some());
+
+        let (id, mv) = next_search_result(&receiver);
+        xboard.search_finished(id, mv);
+        assert_eq!(xboard.game.player, Player::White);
+        assert_eq!(xboard.game.history.len(), 2);
+
+        // Illegal moves are rejected without starting a search
+        run(&mut xboard, &["usermove e2e5"]);
+        assert_eq!(xboard.game.history.len(), 2);
+        assert!(xboard.search.is_none());
+    }
+
+    #[test]
+    fn test_undo_and_remove() {
+        let (mut xboard, _receiver) = xboard();
+
+        run(
+            &mut xboard,
+            &[
+                "new",
+                "force",
+                "usermove e2e4",
+                "usermove e7e5",
+                "usermove g1f3",
+            ],
+        );
+        assert_eq!(xboard.game.history.len(), 3);
+
+        run(&mut xboard, &["undo"]);
+        assert_eq!(xboard.game.history.len(), 2);
+        assert_eq!(xboard.game.player, Player::White);
+
+        run(&mut xboard, &["remove"]);
+        assert!(xboard.game.history.is_empty());
+        assert_eq!(xboard.game.to_fen(), Game::new().to_fen());
+
+        // There's nothing left to take back
+        run(&mut xboard, &["remove", "undo"]);
+        assert!(xboard.game.history.is_empty());
+    }
+
+    #[test]
+    fn test_stale_search_results_are_ignored() {
+        let (mut xboard, receiver) = xboard();
+
+        // The search is abandoned when the user takes their move back
+        run(&mut xboard, &["new", "sd 1", "usermove e2e4", "undo"]);
+        let (stale_id, stale_move) = next_search_result(&receiver);
+        xboard.search_finished(stale_id, stale_move);
+        assert!(xboard.game.history.is_empty());
+
+        run(&mut xboard, &["usermove d2d4"]);
+        let (id, mv) = next_search_result(&receiver);
+        assert_ne!(id, stale_id);
+
+        // A result from the abandoned search arriving late is still ignored
+        xboard.search_finished(stale_id, stale_move);
+        assert_eq!(xboard.game.history.len(), 1);
+
+        xboard.search_finished(id, mv);
+        assert_eq!(xboard.game.history.len(), 2);
+    }
+
+    #[test]
+    fn test_moves_to_go_uses_move_number() {
+        let (mut xboard, _receiver) = xboard();
+
+        run(
+            &mut xboard,
+            &[
+                "new",
+                "force",
+                "level 40 5 0",
+                "setboard rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 39",
+            ],
+        );
+
+        let TimeControl::Clocks(clocks) = xboard.time_control() else {
+            panic!("Expected clocks");
+        };
+        assert_eq!(clocks.moves_to_go, Some(2));
+
+        run(&mut xboard, &["usermove e2e4", "usermove e7e5"]);
+
+        let TimeControl::Clocks(clocks) = xboard.time_control() else {
+            panic!("Expected clocks");
+        };
+        assert_eq!(clocks.moves_to_go, Some(1));
+    }
+}
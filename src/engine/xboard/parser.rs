@@ -0,0 +1,264 @@
+use crate::engine::xboard::commands::XboardCommand;
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{char, space0, space1},
+    combinator::{eof, map, opt, peek, rest, value},
+    number::complete::double,
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+use std::time::Duration;
+
+// Commands which are sent to every engine but don't need a reply from us
+const IGNORED_COMMANDS: [&str; 10] = [
+    "accepted", "rejected", "random", "hard", "easy", "computer", "name", "rating", "ics",
+    "variant",
+];
+
+fn command_without_arguments<'a>(
+    cmd: &'static str,
+    command: XboardCommand,
+) -> impl FnMut(&'a str) -> IResult<&'a str, XboardCommand> {
+    value(command, terminated(tag(cmd), peek(pair(space0, eof))))
+}
+
+fn command_with_argument<'a, O>(
+    cmd: &'static str,
+    argument: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    preceded(pair(tag(cmd), space1), argument)
+}
+
+fn seconds(input: &str) -> IResult<&str, Duration> {
+    map(double, |seconds| {
+        Duration::try_from_secs_f64(seconds).unwrap_or_default()
+    })(input)
+}
+
+// Times from 'time' and 'otim' are in centiseconds, and can be negative if the engine has
+// overrun its clock
+fn centiseconds(input: &str) -> IResult<&str, Duration> {
+    map(nom::character::complete::i64, |centiseconds| {
+        Duration::from_millis(u64::try_from(centiseconds).unwrap_or_default() * 10)
+    })(input)
+}
+
+// The base time of 'level' is in minutes, or minutes and seconds as in '0:30'
+fn level_base(input: &str) -> IResult<&str, Duration> {
+    map(
+        pair(
+            nom::character::complete::u64,
+            opt(preceded(char(':'), nom::character::complete::u64)),
+        ),
+        |(minutes, seconds)| Duration::from_secs(minutes * 60 + seconds.unwrap_or_default()),
+    )(input)
+}
+
+fn cmd_level(input: &str) -> IResult<&str, XboardCommand> {
+    map(
+        command_with_argument(
+            "level",
+            tuple((
+                nom::character::complete::u32,
+                preceded(space1, level_base),
+                preceded(space1, seconds),
+            )),
+        ),
+        |(moves_per_session, base, increment)| XboardCommand::Level {
+            moves_per_session,
+            base,
+            increment,
+        },
+    )(input)
+}
+
+fn cmd_usermove(input: &str) -> IResult<&str, XboardCommand> {
+    map(
+        command_with_argument("usermove", is_not(" \t")),
+        |mv: &str| XboardCommand::UserMove(mv.to_string()),
+    )(input)
+}
+
+fn cmd_setboard(input: &str) -> IResult<&str, XboardCommand> {
+    map(command_with_argument("setboard", rest), |fen: &str| {
+        XboardCommand::SetBoard(fen.trim_end().to_string())
+    })(input)
+}
+
+fn cmd_ignored(input: &str) -> IResult<&str, XboardCommand> {
+    let (input, cmd) = is_not(" \t")(input)?;
+
+    if !IGNORED_COMMANDS.contains(&cmd) {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )));
+    }
+
+    value(XboardCommand::Ignored, rest)(input)
+}
+
+// Protocol version 1 interfaces send moves without 'usermove', so anything that looks like a
+// move is treated as one
+fn cmd_bare_move(input: &str) -> IResult<&str, XboardCommand> {
+    map(
+        terminated(
+            crate::engine::uci::parser::uci_moves,
+            peek(pair(space0, eof)),
+        ),
+        |moves| XboardCommand::UserMove(moves[0].notation()),
+    )(input)
+}
+
+fn any_xboard_command(input: &str) -> IResult<&str, XboardCommand> {
+    let (input, _) = space0(input)?;
+
+    let (input, cmd) = alt((
+        alt((
+            command_without_arguments("xboard", XboardCommand::Xboard),
+            map(
+                command_with_argument("protover", nom::character::complete::u32),
+                XboardCommand::Protover,
+            ),
+            command_without_arguments("new", XboardCommand::New),
+            command_without_arguments("force", XboardCommand::Force),
+            command_without_arguments("go", XboardCommand::Go),
+            cmd_usermove,
+            cmd_level,
+            map(command_with_argument("st", seconds), XboardCommand::St),
+            map(
+                command_with_argument("sd", nom::character::complete::u8),
+                XboardCommand::Sd,
+            ),
+            map(
+                command_with_argument("time", centiseconds),
+                XboardCommand::Time,
+            ),
+            map(
+                command_with_argument("otim", centiseconds),
+                XboardCommand::Otim,
+            ),
+        )),
+        alt((
+            command_without_arguments("post", XboardCommand::Post),
+            command_without_arguments("nopost", XboardCommand::NoPost),
+            command_without_arguments("undo", XboardCommand::Undo),
+            command_without_arguments("remove", XboardCommand::Remove),
+            cmd_setboard,
+            map(
+                command_with_argument("ping", nom::character::complete::u32),
+                XboardCommand::Ping,
+            ),
+            command_without_arguments("?", XboardCommand::MoveNow),
+            map(command_with_argument("result", rest), |_| {
+                XboardCommand::Result
+            }),
+            command_without_arguments("quit", XboardCommand::Quit),
+            cmd_ignored,
+            cmd_bare_move,
+        )),
+    ))(input)?;
+
+    let (input, _) = space0(input)?;
+    let (input, _) = eof(input)?;
+
+    Ok((input, cmd))
+}
+
+pub fn parse(input: &str) -> Result<XboardCommand, String> {
+    match any_xboard_command(input) {
+        Ok((_, cmd)) => Ok(cmd),
+        Err(_) => Err(format!("Error (unknown command): {}", input.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands_without_arguments() {
+        assert_eq!(parse("xboard").unwrap(), XboardCommand::Xboard);
+        assert_eq!(parse("new").unwrap(), XboardCommand::New);
+        assert_eq!(parse(" force ").unwrap(), XboardCommand::Force);
+        assert_eq!(parse("?").unwrap(), XboardCommand::MoveNow);
+        assert_eq!(parse("nopost").unwrap(), XboardCommand::NoPost);
+
+        assert!(parse("newgame").is_err());
+        assert!(parse("go now").is_err());
+    }
+
+    #[test]
+    fn parse_level() {
+        assert_eq!(
+            parse("level 40 5 0").unwrap(),
+            XboardCommand::Level {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::ZERO,
+            }
+        );
+
+        assert_eq!(
+            parse("level 0 2:30 1.5").unwrap(),
+            XboardCommand::Level {
+                moves_per_session: 0,
+                base: Duration::from_secs(150),
+                increment: Duration::from_millis(1500),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(
+            parse("time 12345").unwrap(),
+            XboardCommand::Time(Duration::from_millis(123_450))
+        );
+        assert_eq!(
+            parse("otim -20").unwrap(),
+            XboardCommand::Otim(Duration::ZERO)
+        );
+        assert_eq!(
+            parse("st 10").unwrap(),
+            XboardCommand::St(Duration::from_secs(10))
+        );
+        assert_eq!(parse("sd 8").unwrap(), XboardCommand::Sd(8));
+    }
+
+    #[test]
+    fn parse_moves() {
+        assert_eq!(
+            parse("usermove e7e8q").unwrap(),
+            XboardCommand::UserMove("e7e8q".to_string())
+        );
+        assert_eq!(
+            parse("usermove Nf3").unwrap(),
+            XboardCommand::UserMove("Nf3".to_string())
+        );
+        assert_eq!(
+            parse("e2e4").unwrap(),
+            XboardCommand::UserMove("e2e4".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_commands_with_arguments() {
+        assert_eq!(parse("ping 7").unwrap(), XboardCommand::Ping(7));
+        assert_eq!(parse("protover 2").unwrap(), XboardCommand::Protover(2));
+        assert_eq!(
+            parse("setboard 4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(),
+            XboardCommand::SetBoard("4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string())
+        );
+        assert_eq!(
+            parse("result 1-0 {White mates}").unwrap(),
+            XboardCommand::Result
+        );
+        assert_eq!(parse("accepted usermove").unwrap(), XboardCommand::Ignored);
+        assert_eq!(parse("random").unwrap(), XboardCommand::Ignored);
+
+        assert!(parse("ping").is_err());
+        assert!(parse("unknown").is_err());
+    }
+}
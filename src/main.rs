@@ -70,7 +70,7 @@ fn run() -> ExitCode {
         _ => {
             let binary_name = args[0].clone();
             eprintln!("usage:");
-            eprintln!("  {binary_name}                  - run in UCI mode");
+            eprintln!("  {binary_name}                  - run in UCI or XBoard mode");
             eprintln!(
                 "  {binary_name} \"<uci commands>\" - run specific UCI commands and then exit"
             );
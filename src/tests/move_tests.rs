@@ -5,7 +5,8 @@ use crate::chess::square::Square;
 use crate::engine::options::EngineOptions;
 use crate::engine::search::time_control::TimeStrategy;
 use crate::engine::search::{
-    search, CapturingReporter, PersistentState, SearchRestrictions, SearchScore, TimeControl,
+    search, CapturingReporter, PersistentState, Reporter, SearchInfo, SearchRestrictions,
+    SearchScore, TimeControl,
 };
 
 fn test_expected_move(fen: &str, depth: u8, mv: (Square, Square)) -> (Move, SearchScore) {
@@ -21,7 +22,10 @@ fn test_expected_move(fen: &str, depth: u8, mv: (Square, Square)) -> (Move, Sear
         &game,
         &mut persistent_state,
         &mut time_strategy,
-        &SearchRestrictions { depth: Some(depth) },
+        &SearchRestrictions {
+            depth: Some(depth),
+            ..SearchRestrictions::default()
+        },
         &EngineOptions::default(),
         &mut capturing_reporter,
     );
@@ -40,3 +44,183 @@ fn test_mate_on_100th_halfmove_detected() {
 
     assert_eq!(eval, SearchScore::Mate(1));
 }
+
+#[derive(Default)]
+struct MultiPvReporter {
+    lines: Vec<(u8, usize, Move)>,
+}
+
+impl Reporter for MultiPvReporter {
+    fn generic_report(&self, _: &str) {}
+
+    fn report_search_progress(&mut self, _: &Game, progress: SearchInfo) {
+        self.lines.push((
+            progress.depth,
+            progress.multipv,
+            *progress.pv.first().unwrap(),
+        ));
+    }
+
+    fn book_move(&self, _: &Game, _: Move) {}
+
+    fn best_move(&self, _: &Game, _: Move) {}
+}
+
+#[test]
+fn test_multipv_reports_distinct_lines() {
+    crate::init();
+    let game = Game::new();
+    let mut persistent_state = PersistentState::new(16);
+
+    let options = EngineOptions {
+        multi_pv: 3,
+        ..EngineOptions::default()
+    };
+
+    let mut reporter = MultiPvReporter::default();
+    let (mut time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
+
+    search(
+        &game,
+        &mut persistent_state,
+        &mut time_strategy,
+        &SearchRestrictions {
+            depth: Some(4),
+            ..SearchRestrictions::default()
+        },
+        &options,
+        &mut reporter,
+    );
+
+    let final_depth_lines = reporter
+        .lines
+        .iter()
+        .filter(|(depth, _, _)| *depth == 4)
+        .collect::<Vec<_>>();
+
+    assert_eq!(final_depth_lines.len(), 3);
+
+    for (idx, (_, multipv, mv)) in final_depth_lines.iter().enumerate() {
+        assert_eq!(*multipv, idx + 1);
+        assert!(!final_depth_lines[..idx].iter().any(|(_, _, m)| m == mv));
+    }
+}
+
+#[test]
+fn test_search_moves_restricts_root_moves() {
+    crate::init();
+    let game = Game::from_fen("4Q3/8/1p4pk/1PbB1p1p/7P/p3P1PK/P3qP2/8 w - - 99 88").unwrap();
+    let mut persistent_state = PersistentState::new(16);
+
+    let mut capturing_reporter = CapturingReporter::new();
+    let (mut time_strategy, _) =
+        TimeStrategy::new(&game, &TimeControl::Infinite, &EngineOptions::default());
+
+    let restricted_move = game
+        .moves()
+        .into_iter()
+        .find(|mv| mv.src() == D5 && mv.dst() == C4)
+        .unwrap();
+
+    let best_move = search(
+        &game,
+        &mut persistent_state,
+        &mut time_strategy,
+        &SearchRestrictions {
+            depth: Some(5),
+            search_moves: Some(vec![restricted_move]),
+            ..SearchRestrictions::default()
+        },
+        &EngineOptions::default(),
+        &mut capturing_reporter,
+    );
+
+    assert_eq!(best_move, restricted_move);
+}
+
+#[test]
+fn test_mate_search_stops_once_mate_is_found() {
+    crate::init();
+    let game = Game::from_fen("4Q3/8/1p4pk/1PbB1p1p/7P/p3P1PK/P3qP2/8 w - - 99 88").unwrap();
+    let mut persistent_state = PersistentState::new(16);
+
+    let mut capturing_reporter = CapturingReporter::new();
+    let (mut time_strategy, _) =
+        TimeStrategy::new(&game, &TimeControl::Infinite, &EngineOptions::default());
+
+    // With no depth or time limit, this will only finish if we stop once we find the mate
+    let best_move = search(
+        &game,
+        &mut persistent_state,
+        &mut time_strategy,
+        &SearchRestrictions {
+            mate: Some(1),
+            ..SearchRestrictions::default()
+        },
+        &EngineOptions::default(),
+        &mut capturing_reporter,
+    );
+
+    assert_eq!((best_move.src(), best_move.dst()), (E8, H8));
+    assert_eq!(capturing_reporter.score, Some(SearchScore::Mate(1)));
+}
+
+#[test]
+fn test_node_limited_search_stops_at_node_limit() {
+    crate::init();
+    let game = Game::new();
+    let mut persistent_state = PersistentState::new(16);
+
+    let mut capturing_reporter = CapturingReporter::new();
+    let (mut time_strategy, _) =
+        TimeStrategy::new(&game, &TimeControl::Infinite, &EngineOptions::default());
+
+    search(
+        &game,
+        &mut persistent_state,
+        &mut time_strategy,
+        &SearchRestrictions {
+            nodes: Some(20_000),
+            ..SearchRestrictions::default()
+        },
+        &EngineOptions::default(),
+        &mut capturing_reporter,
+    );
+
+    assert!(capturing_reporter.nodes > 0);
+    assert!(capturing_reporter.nodes <= 20_000);
+}
+
+#[test]
+fn test_node_limit_applies_to_all_threads() {
+    crate::init();
+    let game = Game::new();
+    let mut persistent_state = PersistentState::new(16);
+
+    let options = EngineOptions {
+        threads: 4,
+        ..EngineOptions::default()
+    };
+
+    let mut capturing_reporter = CapturingReporter::new();
+    let (mut time_strategy, _) = TimeStrategy::new(&game, &TimeControl::Infinite, &options);
+
+    search(
+        &game,
+        &mut persistent_state,
+        &mut time_strategy,
+        &SearchRestrictions {
+            nodes: Some(20_000),
+            ..SearchRestrictions::default()
+        },
+        &options,
+        &mut capturing_reporter,
+    );
+
+    // Each thread checks the shared node count independently, so the threads which were
+    // mid-node when the limit was reached can each overshoot it by a single node
+    let max_nodes = 20_000 + u64::try_from(options.threads).unwrap() - 1;
+
+    assert!(capturing_reporter.nodes > 0);
+    assert!(capturing_reporter.nodes <= max_nodes);
+}
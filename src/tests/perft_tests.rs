@@ -4,11 +4,12 @@ use crate::chess::movegen;
 use crate::chess::movegen::MovegenCache;
 use crate::chess::moves::MoveList;
 use crate::chess::perft::perft;
+use crate::chess::zobrist::ZobristHash;
 use crate::engine::options::EngineOptions;
 use crate::engine::search::move_picker::MovePicker;
 use crate::engine::search::time_control::TimeStrategy;
 use crate::engine::search::{PersistentState, SearchContext, SearchRestrictions, TimeControl};
-use crate::engine::transposition_table::{TTOverwriteable, TranspositionTable};
+use crate::engine::transposition_table::{TTOverwriteable, TTPackable, TranspositionTable};
 use paste::paste;
 
 fn test_perft(fen: &str, depth: u8, expected_positions: usize) {
@@ -19,10 +20,19 @@ fn test_perft(fen: &str, depth: u8, expected_positions: usize) {
     assert_eq!(expected_positions, actual_positions);
 }
 
+// The table only verifies 16 bits of the key, and a single collision would give the wrong node
+// count, so we store more of the key alongside the data.
 #[derive(Debug, Clone)]
 struct PerftTranspositionTableData {
     nodes: usize,
     depth: u8,
+    key: u32,
+}
+
+const PERFT_KEY_MASK: u64 = 0xFF_FFFF;
+
+const fn perft_key(zobrist: &ZobristHash) -> u32 {
+    ((zobrist.0 >> 16) & PERFT_KEY_MASK) as u32
 }
 
 impl TTOverwriteable for PerftTranspositionTableData {
@@ -31,15 +41,30 @@ impl TTOverwriteable for PerftTranspositionTableData {
     }
 }
 
+impl TTPackable for PerftTranspositionTableData {
+    fn pack(&self) -> u64 {
+        u64::from(self.key) << 40 | (self.nodes as u64) << 8 | u64::from(self.depth)
+    }
+
+    #[expect(clippy::cast_possible_truncation, reason = "Unpacking fields")]
+    fn unpack(data: u64) -> Self {
+        Self {
+            nodes: ((data >> 8) & 0xFFFF_FFFF) as usize,
+            depth: data as u8,
+            key: (data >> 40) as u32,
+        }
+    }
+}
+
 type PerftTranspositionTable = TranspositionTable<PerftTranspositionTableData>;
 
-fn tt_perft(depth: u8, game: &mut Game, tt: &mut PerftTranspositionTable) -> usize {
+fn tt_perft(depth: u8, game: &mut Game, tt: &PerftTranspositionTable) -> usize {
     if depth == 1 {
         return game.moves().len();
     }
 
     if let Some(tt_data) = tt.get(&game.zobrist) {
-        if tt_data.depth == depth {
+        if tt_data.depth == depth && tt_data.key == perft_key(&game.zobrist) {
             return tt_data.nodes;
         }
     }
@@ -58,9 +83,10 @@ fn tt_perft(depth: u8, game: &mut Game, tt: &mut PerftTranspositionTable) -> usi
 
     tt.insert(
         &game.zobrist,
-        PerftTranspositionTableData {
+        &PerftTranspositionTableData {
             nodes: result,
             depth,
+            key: perft_key(&game.zobrist),
         },
     );
 
@@ -70,10 +96,10 @@ fn tt_perft(depth: u8, game: &mut Game, tt: &mut PerftTranspositionTable) -> usi
 fn test_perft_with_tt(fen: &str, depth: u8, expected_positions: usize) {
     crate::init();
 
-    let mut tt = PerftTranspositionTable::new(256);
+    let tt = PerftTranspositionTable::new(256);
 
     let mut game = Game::from_fen(fen).unwrap();
-    let actual_positions = tt_perft(depth, &mut game, &mut tt);
+    let actual_positions = tt_perft(depth, &mut game, &tt);
 
     assert_eq!(expected_positions, actual_positions);
 }
@@ -169,6 +195,48 @@ fn test_perft_with_movepicker(fen: &str, depth: u8, expected_positions: usize) {
     assert_eq!(expected_positions, actual_positions);
 }
 
+// Walk the tree checking that `is_legal` agrees with movegen, both for the legal moves in each
+// position and for the moves from its parent, which are mostly illegal after the move was made.
+fn check_is_legal(depth: u8, game: &mut Game, parent_moves: &MoveList) {
+    let moves = game.moves();
+
+    for mv in moves.iter().chain(parent_moves) {
+        assert_eq!(
+            movegen::is_legal(game, *mv),
+            moves.contains(mv),
+            "At fen {}, move {mv:?}",
+            game.to_fen()
+        );
+    }
+
+    if depth == 0 {
+        return;
+    }
+
+    for mv in &moves {
+        game.make_move(*mv);
+        check_is_legal(depth - 1, game, &moves);
+        game.undo_move();
+    }
+}
+
+#[test]
+fn test_is_legal_agrees_with_movegen() {
+    crate::init();
+
+    for fen in [
+        START_POS,
+        KIWIPETE_POS,
+        CHESSPROGRAMMING_WIKI_POS3,
+        CHESSPROGRAMMING_WIKI_POS4,
+        CHESSPROGRAMMING_WIKI_POS5,
+        CHESS960_POS6,
+    ] {
+        let mut game = Game::from_fen(fen).unwrap();
+        check_is_legal(3, &mut game, &MoveList::new());
+    }
+}
+
 macro_rules! perft_position {
     ($name:ident, $pos:expr, $depth:expr, $nodes:expr) => {
         paste! {
@@ -326,3 +394,68 @@ perft_position!(gist_20, "8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92683);
 perft_position!(gist_21, "K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2217);
 perft_position!(gist_22, "8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7, 567_584);
 perft_position!(gist_23, "8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23527);
+
+// Chess960 positions, taken from https://www.chessprogramming.org/Chess960_Perft_Results
+const CHESS960_POS1: &str = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+perft_position!(chess960_pos1_1, CHESS960_POS1, 1, 21);
+perft_position!(chess960_pos1_2, CHESS960_POS1, 2, 528);
+perft_position!(chess960_pos1_3, CHESS960_POS1, 3, 12189);
+perft_position!(chess960_pos1_4, CHESS960_POS1, 4, 326_672);
+perft_position!(chess960_pos1_5, CHESS960_POS1, 5, 8_146_062);
+
+const CHESS960_POS2: &str = "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9";
+perft_position!(chess960_pos2_1, CHESS960_POS2, 1, 21);
+perft_position!(chess960_pos2_2, CHESS960_POS2, 2, 807);
+perft_position!(chess960_pos2_3, CHESS960_POS2, 3, 18002);
+perft_position!(chess960_pos2_4, CHESS960_POS2, 4, 667_366);
+perft_position!(chess960_pos2_5, CHESS960_POS2, 5, 16_253_601);
+
+const CHESS960_POS3: &str = "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9";
+perft_position!(chess960_pos3_1, CHESS960_POS3, 1, 20);
+perft_position!(chess960_pos3_2, CHESS960_POS3, 2, 479);
+perft_position!(chess960_pos3_3, CHESS960_POS3, 3, 10471);
+perft_position!(chess960_pos3_4, CHESS960_POS3, 4, 273_318);
+perft_position!(chess960_pos3_5, CHESS960_POS3, 5, 6_417_013);
+
+const CHESS960_POS4: &str = "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9";
+perft_position!(chess960_pos4_1, CHESS960_POS4, 1, 22);
+perft_position!(chess960_pos4_2, CHESS960_POS4, 2, 593);
+perft_position!(chess960_pos4_3, CHESS960_POS4, 3, 13440);
+perft_position!(chess960_pos4_4, CHESS960_POS4, 4, 382_958);
+perft_position!(chess960_pos4_5, CHESS960_POS4, 5, 9_183_776);
+
+const CHESS960_POS5: &str = "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9";
+perft_position!(chess960_pos5_1, CHESS960_POS5, 1, 28);
+perft_position!(chess960_pos5_2, CHESS960_POS5, 2, 1120);
+perft_position!(chess960_pos5_3, CHESS960_POS5, 3, 31058);
+perft_position!(chess960_pos5_4, CHESS960_POS5, 4, 1_171_749);
+
+const CHESS960_POS6: &str = "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9";
+perft_position!(chess960_pos6_1, CHESS960_POS6, 1, 29);
+perft_position!(chess960_pos6_2, CHESS960_POS6, 2, 899);
+perft_position!(chess960_pos6_3, CHESS960_POS6, 3, 26578);
+perft_position!(chess960_pos6_4, CHESS960_POS6, 4, 824_055);
+
+const CHESS960_POS7: &str = "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9";
+perft_position!(chess960_pos7_1, CHESS960_POS7, 1, 30);
+perft_position!(chess960_pos7_2, CHESS960_POS7, 2, 860);
+perft_position!(chess960_pos7_3, CHESS960_POS7, 3, 24566);
+perft_position!(chess960_pos7_4, CHESS960_POS7, 4, 732_757);
+
+const CHESS960_POS8: &str = "qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9";
+perft_position!(chess960_pos8_1, CHESS960_POS8, 1, 25);
+perft_position!(chess960_pos8_2, CHESS960_POS8, 2, 635);
+perft_position!(chess960_pos8_3, CHESS960_POS8, 3, 17054);
+perft_position!(chess960_pos8_4, CHESS960_POS8, 4, 465_806);
+
+const CHESS960_POS9: &str = "qnnbbrkr/1p2ppp1/2pp3p/p7/1P5P/2NP4/P1P1PPP1/Q1NBBRKR w HFhf - 0 9";
+perft_position!(chess960_pos9_1, CHESS960_POS9, 1, 24);
+perft_position!(chess960_pos9_2, CHESS960_POS9, 2, 572);
+perft_position!(chess960_pos9_3, CHESS960_POS9, 3, 15243);
+perft_position!(chess960_pos9_4, CHESS960_POS9, 4, 384_260);
+
+const CHESS960_POS10: &str = "qn1rbbkr/ppp2p1p/1n1pp1p1/8/3P4/P6P/1PP1PPPK/QNNRBB1R w hd - 2 9";
+perft_position!(chess960_pos10_1, CHESS960_POS10, 1, 28);
+perft_position!(chess960_pos10_2, CHESS960_POS10, 2, 811);
+perft_position!(chess960_pos10_3, CHESS960_POS10, 3, 23175);
+perft_position!(chess960_pos10_4, CHESS960_POS10, 4, 679_699);
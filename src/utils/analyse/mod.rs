@@ -0,0 +1,545 @@
+// Annotates the games in a PGN with the engine's opinion of each move.
+//
+// Every position in each game's mainline is searched, and each move is given an [%eval] comment
+// with the evaluation after it (from white's perspective, as lichess does). Moves which lose
+// enough winning chances compared to the engine's best move are marked as inaccuracies, mistakes
+// or blunders, and the engine's preferred line is added as a variation.
+//
+// Moves are judged by how much they reduce the mover's expected winning chances rather than by
+// raw centipawns, so that giving up a few pawns in a position which is already won or lost isn't
+// flagged as a blunder.
+
+use crate::chess::game::Game;
+use crate::chess::movegen;
+use crate::chess::moves::Move;
+use crate::chess::pgn::{self, Line, PgnGame, PgnMove};
+use crate::chess::player::Player;
+use crate::chess::san;
+use crate::engine::options::EngineOptions;
+use crate::engine::search;
+use crate::engine::search::time_control::TimeStrategy;
+use crate::engine::search::{
+    PersistentState, Reporter, SearchInfo, SearchRestrictions, SearchScore, TimeControl,
+};
+use std::path::Path;
+use std::time::Duration;
+
+// Used when no search limit is given
+const DEFAULT_DEPTH: u8 = 12;
+
+// How many moves of the engine's preferred line to show in a variation
+const MAX_VARIATION_PLIES: usize = 8;
+
+// Drops in the mover's winning chances (in percentage points) needed for each classification
+const INACCURACY_THRESHOLD: f64 = 10.0;
+const MISTAKE_THRESHOLD: f64 = 20.0;
+const BLUNDER_THRESHOLD: f64 = 30.0;
+
+const NAG_MISTAKE: u8 = 2;
+const NAG_BLUNDER: u8 = 4;
+const NAG_INACCURACY: u8 = 6;
+
+// NAGs 1 to 6 assess the move itself, and are replaced by our own assessment
+const MOVE_ASSESSMENT_NAGS: std::ops::RangeInclusive<u8> = 1..=6;
+
+const EVAL_COMMAND: &str = "[%eval";
+
+pub struct AnalyseOptions {
+    pub time: Option<Duration>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub hash_size: usize,
+    pub threads: usize,
+}
+
+// The result of searching a position. Scores are from the perspective of the side to move.
+struct Analysis {
+    score: SearchScore,
+    pv: Vec<Move>,
+}
+
+struct AnalysisReporter {
+    score: Option<SearchScore>,
+    pv: Vec<Move>,
+}
+
+impl Reporter for AnalysisReporter {
+    fn generic_report(&self, _: &str) {}
+
+    fn report_search_progress(&mut self, _: &Game, progress: SearchInfo) {
+        if progress.multipv != 1 {
+            return;
+        }
+
+        self.score = Some(progress.score);
+        self.pv = progress.pv.into_iter().collect();
+    }
+
+    fn book_move(&self, _: &Game, _: Move) {}
+
+    fn best_move(&self, _: &Game, _: Move) {}
+}
+
+struct Analyser {
+    persistent_state: PersistentState,
+    engine_options: EngineOptions,
+    search_restrictions: SearchRestrictions,
+    time_control: TimeControl,
+}
+
+impl Analyser {
+    fn analyse(&mut self, game: &Game) -> Analysis {
+        if game.moves().is_empty() {
+            return Analysis {
+                score: if game.is_king_in_check() {
+                    SearchScore::Mate(0)
+                } else {
+                    SearchScore::Centipawns(0)
+                },
+                pv: Vec::new(),
+            };
+        }
+
+        let mut reporter = AnalysisReporter {
+            score: None,
+            pv: Vec::new(),
+        };
+
+        let (mut time_strategy, _) =
+            TimeStrategy::new(game, &self.time_control, &self.engine_options);
+
+        let best_move = search::search(
+            game,
+            &mut self.persistent_state,
+            &mut time_strategy,
+            &self.search_restrictions,
+            &self.engine_options,
+            &mut reporter,
+        );
+
+        // The search may have been stopped before reporting a line starting with its best move
+        if reporter.pv.first() != Some(&best_move) {
+            reporter.pv = vec![best_move];
+        }
+
+        Analysis {
+            score: reporter.score.unwrap_or(SearchScore::Centipawns(0)),
+            pv: reporter.pv,
+        }
+    }
+}
+
+// The side to move's expected winning chances as a percentage, using lichess' model
+fn winning_chances(score: &SearchScore) -> f64 {
+    match *score {
+        SearchScore::Centipawns(cp) => {
+            let cp = f64::from(cp.clamp(-1000, 1000));
+            100.0 / (1.0 + (-0.003_682_08 * cp).exp())
+        }
+        SearchScore::Mate(moves) if moves > 0 => 100.0,
+        SearchScore::Mate(_) => 0.0,
+    }
+}
+
+fn format_eval(score: &SearchScore, player: Player) -> String {
+    let sign = match player {
+        Player::White => 1,
+        Player::Black => -1,
+    };
+
+    match *score {
+        SearchScore::Centipawns(cp) => format!("{:.2}", f64::from(sign * cp) / 100.0),
+        SearchScore::Mate(moves) => format!("#{}", sign * moves),
+    }
+}
+
+fn classify(winning_chances_lost: f64) -> Option<(u8, &'static str)> {
+    if winning_chances_lost >= BLUNDER_THRESHOLD {
+        Some((NAG_BLUNDER, "Blunder"))
+    } else if winning_chances_lost >= MISTAKE_THRESHOLD {
+        Some((NAG_MISTAKE, "Mistake"))
+    } else if winning_chances_lost >= INACCURACY_THRESHOLD {
+        Some((NAG_INACCURACY, "Inaccuracy"))
+    } else {
+        None
+    }
+}
+
+// Remove any evaluations left by a previous analysis, e.g. in games exported from lichess
+fn strip_eval_commands(comment: &str) -> String {
+    let mut comment = comment.to_string();
+
+    while let Some(start) = comment.find(EVAL_COMMAND) {
+        let end = comment[start..]
+            .find(']')
+            .map_or(comment.len(), |end| start + end + 1);
+
+        comment.replace_range(start..end, "");
+    }
+
+    comment.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The engine's line as a variation, stopping early if the PV contains a move which isn't legal
+// (which can happen with TT collisions)
+fn variation(game: &Game, pv: &[Move]) -> Line {
+    let mut game = game.clone();
+    let mut moves = Vec::new();
+
+    for mv in pv.iter().take(MAX_VARIATION_PLIES) {
+        if !movegen::is_legal(&game, *mv) {
+            break;
+        }
+
+        moves.push(PgnMove::new(*mv));
+        game.make_move(*mv);
+    }
+
+    Line {
+        comment: None,
+        moves,
+    }
+}
+
+fn annotate_move(game: &Game, pgn_move: &mut PgnMove, before: &Analysis, after: &Analysis) {
+    let mut comment = Vec::new();
+
+    let is_checkmate = matches!(after.score, SearchScore::Mate(0));
+    if !is_checkmate {
+        comment.push(format!(
+            "{EVAL_COMMAND} {}]",
+            format_eval(&after.score, game.player.other())
+        ));
+    }
+
+    if let Some(existing) = pgn_move.comment.as_deref().map(strip_eval_commands) {
+        if !existing.is_empty() {
+            comment.push(existing);
+        }
+    }
+
+    pgn_move
+        .nags
+        .retain(|nag| !MOVE_ASSESSMENT_NAGS.contains(nag));
+
+    let best_move = before.pv.first().copied();
+    let winning_chances_lost =
+        winning_chances(&before.score) - (100.0 - winning_chances(&after.score));
+
+    if let Some(best_move) = best_move.filter(|mv| *mv != pgn_move.mv) {
+        if let Some((nag, classification)) = classify(winning_chances_lost) {
+            pgn_move.nags.push(nag);
+            comment.push(format!(
+                "{classification}. {} was best.",
+                san::format_move(game, best_move)
+            ));
+            pgn_move.variations.push(variation(game, &before.pv));
+        }
+    }
+
+    pgn_move.comment = Some(comment.join(" ")).filter(|comment| !comment.is_empty());
+}
+
+fn analyse_game(analyser: &mut Analyser, pgn_game: &mut PgnGame, game_number: usize) {
+    let total_moves = pgn_game.mainline.moves.len();
+
+    let mut game = pgn_game.start.clone();
+    let mut analysis_by_ply = vec![analyser.analyse(&game)];
+
+    for (i, pgn_move) in pgn_game.mainline.moves.iter().enumerate() {
+        eprintln!("Game {game_number}: analysing move {}/{total_moves}", i + 1);
+
+        game.make_move(pgn_move.mv);
+        analysis_by_ply.push(analyser.analyse(&game));
+    }
+
+    let mut game = pgn_game.start.clone();
+
+    for (pgn_move, analysis) in pgn_game
+        .mainline
+        .moves
+        .iter_mut()
+        .zip(analysis_by_ply.windows(2))
+    {
+        annotate_move(&game, pgn_move, &analysis[0], &analysis[1]);
+        game.make_move(pgn_move.mv);
+    }
+
+    pgn_game.set_tag(
+        "Annotator",
+        &format!("{} {}", crate::ENGINE_NAME, crate::engine_version()),
+    );
+}
+
+pub fn analyse(
+    input: &Path,
+    output: Option<&Path>,
+    options: &AnalyseOptions,
+) -> Result<(), String> {
+    let pgn = std::fs::read_to_string(input)
+        .map_err(|e| format!("Unable to read {}: {e}", input.display()))?;
+
+    let mut games = pgn::parse(&pgn)?;
+
+    let engine_options = EngineOptions {
+        hash_size: options.hash_size,
+        threads: options.threads,
+        ..EngineOptions::default()
+    };
+
+    let depth = if options.time.is_none() && options.depth.is_none() && options.nodes.is_none() {
+        Some(DEFAULT_DEPTH)
+    } else {
+        options.depth
+    };
+
+    let mut analyser = Analyser {
+        persistent_state: PersistentState::new(options.hash_size),
+        engine_options,
+        search_restrictions: SearchRestrictions {
+            depth,
+            nodes: options.nodes,
+            ..SearchRestrictions::default()
+        },
+        time_control: options
+            .time
+            .map_or(TimeControl::Infinite, TimeControl::ExactTime),
+    };
+
+    for (i, game) in games.iter_mut().enumerate() {
+        analyser.persistent_state.reset();
+        analyse_game(&mut analyser, game, i + 1);
+    }
+
+    let annotated = games.iter().map(pgn::write).collect::<Vec<_>>().join("\n");
+
+    if let Some(output) = output {
+        return std::fs::write(output, annotated)
+            .map_err(|e| format!("Unable to write {}: {e}", output.display()));
+    }
+
+    print!("{annotated}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOOLS_MATE: &str = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+
+    fn analysis(game: &Game, score: SearchScore, pv: &[&str]) -> Analysis {
+        let mut game = game.clone();
+
+        let pv = pv
+            .iter()
+            .map(|mv| {
+                let mv = san::parse_move(&game, mv).unwrap();
+                game.make_move(mv);
+                mv
+            })
+            .collect();
+
+        Analysis { score, pv }
+    }
+
+    fn played(game: &Game, mv: &str) -> PgnMove {
+        PgnMove::new(san::parse_move(game, mv).unwrap())
+    }
+
+    #[test]
+    fn test_winning_chances() {
+        assert!((winning_chances(&SearchScore::Centipawns(0)) - 50.0).abs() < 1e-9);
+
+        for cp in [25, 150, 600] {
+            let total = winning_chances(&SearchScore::Centipawns(cp))
+                + winning_chances(&SearchScore::Centipawns(-cp));
+            assert!((total - 100.0).abs() < 1e-9);
+        }
+
+        assert!(
+            winning_chances(&SearchScore::Centipawns(100))
+                > winning_chances(&SearchScore::Centipawns(50))
+        );
+        assert!(
+            (winning_chances(&SearchScore::Centipawns(5000))
+                - winning_chances(&SearchScore::Centipawns(1000)))
+            .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_winning_chances_for_mates() {
+        assert!((winning_chances(&SearchScore::Mate(3)) - 100.0).abs() < 1e-9);
+        assert!(winning_chances(&SearchScore::Mate(-3)).abs() < 1e-9);
+
+        // Being checkmated is the worst possible outcome
+        assert!(winning_chances(&SearchScore::Mate(0)).abs() < 1e-9);
+
+        // Finding a mate is always better than any centipawn score, and being mated is worse
+        assert!(
+            winning_chances(&SearchScore::Mate(20))
+                > winning_chances(&SearchScore::Centipawns(1000))
+        );
+        assert!(
+            winning_chances(&SearchScore::Mate(-20))
+                < winning_chances(&SearchScore::Centipawns(-1000))
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(-15.0), None);
+        assert_eq!(classify(0.0), None);
+        assert_eq!(classify(9.9), None);
+        assert_eq!(classify(10.0), Some((NAG_INACCURACY, "Inaccuracy")));
+        assert_eq!(classify(19.9), Some((NAG_INACCURACY, "Inaccuracy")));
+        assert_eq!(classify(20.0), Some((NAG_MISTAKE, "Mistake")));
+        assert_eq!(classify(30.0), Some((NAG_BLUNDER, "Blunder")));
+        assert_eq!(classify(100.0), Some((NAG_BLUNDER, "Blunder")));
+    }
+
+    #[test]
+    fn test_format_eval() {
+        assert_eq!(
+            format_eval(&SearchScore::Centipawns(35), Player::White),
+            "0.35"
+        );
+        assert_eq!(
+            format_eval(&SearchScore::Centipawns(35), Player::Black),
+            "-0.35"
+        );
+        assert_eq!(
+            format_eval(&SearchScore::Centipawns(-250), Player::Black),
+            "2.50"
+        );
+        assert_eq!(
+            format_eval(&SearchScore::Centipawns(0), Player::Black),
+            "0.00"
+        );
+
+        assert_eq!(format_eval(&SearchScore::Mate(3), Player::White), "#3");
+        assert_eq!(format_eval(&SearchScore::Mate(3), Player::Black), "#-3");
+        assert_eq!(format_eval(&SearchScore::Mate(-2), Player::White), "#-2");
+        assert_eq!(format_eval(&SearchScore::Mate(-2), Player::Black), "#2");
+    }
+
+    #[test]
+    fn test_strip_eval_commands() {
+        assert_eq!(strip_eval_commands("[%eval 0.17]"), "");
+        assert_eq!(
+            strip_eval_commands("[%eval 0.17] [%clk 0:03:00] Good move"),
+            "[%clk 0:03:00] Good move"
+        );
+        assert_eq!(
+            strip_eval_commands("Sharp [%eval #-4]  line [%eval -1.5]"),
+            "Sharp line"
+        );
+        assert_eq!(
+            strip_eval_commands("An unfinished [%eval 1.2"),
+            "An unfinished"
+        );
+        assert_eq!(strip_eval_commands("No evals here"), "No evals here");
+    }
+
+    #[test]
+    fn test_annotate_best_move() {
+        crate::init();
+
+        let game = Game::new();
+        let mut pgn_move = played(&game, "e4");
+        pgn_move.comment = Some("[%eval 0.30] Main line".to_string());
+
+        // Even if the score drops, the engine's own move is never marked as a mistake
+        let before = analysis(&game, SearchScore::Centipawns(30), &["e4", "e5"]);
+        let after = analysis(&game, SearchScore::Centipawns(500), &[]);
+
+        annotate_move(&game, &mut pgn_move, &before, &after);
+
+        assert_eq!(pgn_move.comment.as_deref(), Some("[%eval -5.00] Main line"));
+        assert!(pgn_move.nags.is_empty());
+        assert!(pgn_move.variations.is_empty());
+    }
+
+    #[test]
+    fn test_annotate_inaccuracy() {
+        crate::init();
+
+        let game = Game::new();
+        let mut pgn_move = played(&game, "a4");
+        pgn_move.nags.push(1);
+
+        let before = analysis(&game, SearchScore::Centipawns(30), &["e4", "e5", "Nf3"]);
+        let after = analysis(&game, SearchScore::Centipawns(80), &[]);
+
+        annotate_move(&game, &mut pgn_move, &before, &after);
+
+        assert_eq!(
+            pgn_move.comment.as_deref(),
+            Some("[%eval -0.80] Inaccuracy. e4 was best.")
+        );
+        assert_eq!(pgn_move.nags, vec![NAG_INACCURACY]);
+        assert_eq!(pgn_move.variations.len(), 1);
+        assert_eq!(pgn_move.variations[0].moves.len(), 3);
+        assert_eq!(pgn_move.variations[0].moves[0].mv, before.pv[0]);
+    }
+
+    #[test]
+    fn test_annotate_missed_mate() {
+        crate::init();
+
+        let game = Game::from_fen(FOOLS_MATE).unwrap();
+        let mut pgn_move = played(&game, "Nc6");
+
+        let before = analysis(&game, SearchScore::Mate(1), &["Qh4#"]);
+        let after = analysis(&game, SearchScore::Centipawns(-200), &[]);
+
+        annotate_move(&game, &mut pgn_move, &before, &after);
+
+        assert_eq!(
+            pgn_move.comment.as_deref(),
+            Some("[%eval -2.00] Blunder. Qh4# was best.")
+        );
+        assert_eq!(pgn_move.nags, vec![NAG_BLUNDER]);
+    }
+
+    #[test]
+    fn test_annotate_move_allowing_mate() {
+        crate::init();
+
+        let game = Game::new();
+        let mut pgn_move = played(&game, "f3");
+
+        let before = analysis(&game, SearchScore::Centipawns(30), &["e4"]);
+        let after = analysis(&game, SearchScore::Mate(3), &[]);
+
+        annotate_move(&game, &mut pgn_move, &before, &after);
+
+        assert_eq!(
+            pgn_move.comment.as_deref(),
+            Some("[%eval #-3] Blunder. e4 was best.")
+        );
+        assert_eq!(pgn_move.nags, vec![NAG_BLUNDER]);
+    }
+
+    #[test]
+    fn test_annotate_checkmate() {
+        crate::init();
+
+        let game = Game::from_fen(FOOLS_MATE).unwrap();
+        let mut pgn_move = played(&game, "Qh4#");
+        pgn_move.comment = Some("[%eval #-1]".to_string());
+        pgn_move.nags.push(3);
+
+        // The engine preferring a different mate doesn't make the checkmate a mistake
+        let before = analysis(&game, SearchScore::Mate(1), &["Nc6"]);
+        let after = analysis(&game, SearchScore::Mate(0), &[]);
+
+        annotate_move(&game, &mut pgn_move, &before, &after);
+
+        assert_eq!(pgn_move.comment, None);
+        assert!(pgn_move.nags.is_empty());
+        assert!(pgn_move.variations.is_empty());
+    }
+}
@@ -1,8 +1,16 @@
 use crate::engine::uci;
 use crate::engine::uci::UciInputMode;
+use crate::engine::xboard;
+use crate::utils::analyse;
+use crate::utils::datagen;
+use crate::utils::r#match;
+use crate::utils::suite;
+#[cfg(feature = "tuner")]
+use crate::utils::tuner;
 use clap::{Parser, Subcommand};
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 struct Cli {
@@ -14,11 +22,211 @@ struct Cli {
 enum Command {
     Uci,
 
+    /// Use the Chess Engine Communication Protocol (CECP) rather than UCI. This is also selected if
+    /// the first command received is 'xboard'
+    Xboard,
+
     Tune {
         file: PathBuf,
 
         #[clap(default_value_t = 5000)]
         epochs: usize,
+
+        /// How much to fit to the positions' search scores rather than the game outcomes
+        #[clap(long, default_value_t = 0.0)]
+        lambda: f32,
+
+        /// The fraction of positions to hold out for validation
+        #[clap(long, default_value_t = 0.1)]
+        validation_split: f32,
+
+        #[clap(long, default_value = "tuner.checkpoint")]
+        checkpoint: PathBuf,
+
+        /// How many epochs to run between saving checkpoints
+        #[clap(long, default_value_t = 100)]
+        checkpoint_every: usize,
+
+        /// Continue the run saved in the checkpoint file
+        #[clap(long)]
+        resume: bool,
+
+        /// Write the tuned parameters to this file (e.g. src/engine/eval/params.rs) instead of
+        /// printing them
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert a text tuner dataset into the binary format
+    ConvertDataset {
+        input: PathBuf,
+        output: PathBuf,
+    },
+
+    Datagen {
+        output: PathBuf,
+
+        #[clap(long, default_value_t = 1000)]
+        games: usize,
+
+        #[clap(long, default_value_t = 1)]
+        threads: usize,
+
+        #[clap(long, default_value_t = 5000)]
+        nodes: u64,
+
+        #[clap(long, default_value_t = 8)]
+        random_plies: usize,
+
+        #[clap(long)]
+        book: Option<String>,
+
+        #[clap(long)]
+        syzygy_path: Option<String>,
+
+        /// Write the search score alongside each position's result
+        #[clap(long)]
+        scores: bool,
+
+        /// Skip positions where the side to move is in check
+        #[clap(long)]
+        skip_in_check: bool,
+
+        /// Skip positions where the best move is a capture
+        #[clap(long)]
+        skip_captures: bool,
+
+        /// Skip positions where the search found a mate
+        #[clap(long)]
+        skip_mates: bool,
+    },
+
+    /// Run an EPD test suite, checking the engine's moves against each position's bm, am and dm
+    /// operations. Searches for one second per position if no limit is given.
+    Suite {
+        file: PathBuf,
+
+        /// Search time per position, in milliseconds
+        #[clap(long)]
+        time: Option<u64>,
+
+        #[clap(long)]
+        depth: Option<u8>,
+
+        #[clap(long)]
+        nodes: Option<u64>,
+
+        /// Transposition table size in MB
+        #[clap(long, default_value_t = 16)]
+        hash: usize,
+
+        #[clap(long, default_value_t = 1)]
+        threads: usize,
+    },
+
+    /// Annotate the games in a PGN with evaluations, mistakes and the engine's preferred lines.
+    /// Searches to depth 12 in each position if no limit is given.
+    Analyse {
+        input: PathBuf,
+
+        /// Write the annotated PGN to this file instead of printing it
+        #[clap(long)]
+        output: Option<PathBuf>,
+
+        /// Search time per position, in milliseconds
+        #[clap(long)]
+        time: Option<u64>,
+
+        #[clap(long)]
+        depth: Option<u8>,
+
+        #[clap(long)]
+        nodes: Option<u64>,
+
+        /// Transposition table size in MB
+        #[clap(long, default_value_t = 16)]
+        hash: usize,
+
+        #[clap(long, default_value_t = 1)]
+        threads: usize,
+    },
+
+    /// Play a match between two UCI engines, or the built-in engine against one, in pairs of
+    /// games from each opening with colours reversed. Reports the Elo difference as it goes.
+    Match {
+        /// Engine commands. With a single command, the built-in engine plays against it.
+        #[clap(num_args = 1..=2, required = true)]
+        engines: Vec<String>,
+
+        /// An EPD or PGN file of openings. Games start from the standard position if not given.
+        #[clap(long)]
+        openings: Option<PathBuf>,
+
+        /// Play the openings in a random order
+        #[clap(long)]
+        random_order: bool,
+
+        /// The number of game pairs to play. Defaults to 100, or no limit with --sprt.
+        #[clap(long)]
+        rounds: Option<usize>,
+
+        /// How many games to play at once
+        #[clap(long, default_value_t = 1)]
+        concurrency: usize,
+
+        /// Time control in seconds, as base+increment
+        #[clap(long, default_value = "8+0.08", value_parser = r#match::TimeControl::parse)]
+        tc: r#match::TimeControl,
+
+        /// How long an engine may overrun its clock before losing on time, in milliseconds
+        #[clap(long, default_value_t = 0)]
+        time_margin: u64,
+
+        /// Transposition table size in MB, for each engine
+        #[clap(long, default_value_t = 16)]
+        hash: usize,
+
+        #[clap(long, default_value_t = 1)]
+        threads: usize,
+
+        /// Adjudicate draws from this move number, once both engines' scores have been within
+        /// --draw-score centipawns of zero for --draw-moves moves
+        #[clap(long)]
+        draw_after: Option<u32>,
+
+        #[clap(long, default_value_t = 8)]
+        draw_moves: u32,
+
+        #[clap(long, default_value_t = 10)]
+        draw_score: i32,
+
+        /// Adjudicate a win once both engines' scores have agreed that one side is winning by
+        /// --resign-score centipawns for this many moves
+        #[clap(long)]
+        resign_moves: Option<u32>,
+
+        #[clap(long, default_value_t = 400)]
+        resign_score: i32,
+
+        /// Stop the match once an SPRT of --elo0 against --elo1 accepts either hypothesis
+        #[clap(long)]
+        sprt: bool,
+
+        #[clap(long, default_value_t = 0.0)]
+        elo0: f64,
+
+        #[clap(long, default_value_t = 5.0)]
+        elo1: f64,
+
+        #[clap(long, default_value_t = 0.05)]
+        alpha: f64,
+
+        #[clap(long, default_value_t = 0.05)]
+        beta: f64,
+
+        /// Write the games to this PGN file
+        #[clap(long)]
+        pgn: Option<PathBuf>,
     },
 }
 
@@ -34,25 +242,224 @@ pub fn uci_command() -> ExitCode {
     }
 }
 
+pub fn xboard_command() -> ExitCode {
+    xboard::xboard();
+    ExitCode::SUCCESS
+}
+
 #[cfg(feature = "tuner")]
-pub fn tune_command(file: &Path, epochs: usize) -> ExitCode {
-    crate::utils::tuner::tune(file, epochs);
+pub fn tune_command(file: &Path, options: &tuner::TuneOptions) -> ExitCode {
+    tuner::tune(file, options);
     ExitCode::SUCCESS
 }
 
 #[cfg(not(feature = "tuner"))]
-pub fn tune_command(_file: &Path, _epochs: usize) -> ExitCode {
+pub fn tune_command() -> ExitCode {
     eprintln!("Tuning requires the 'tuner' feature to be enabled");
     ExitCode::FAILURE
 }
 
+#[cfg(feature = "tuner")]
+pub fn convert_dataset_command(input: &Path, output: &Path) -> ExitCode {
+    tuner::convert(input, output);
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(feature = "tuner"))]
+pub fn convert_dataset_command(_input: &Path, _output: &Path) -> ExitCode {
+    eprintln!("Converting datasets requires the 'tuner' feature to be enabled");
+    ExitCode::FAILURE
+}
+
+pub fn datagen_command(output: &Path, options: &datagen::DatagenOptions) -> ExitCode {
+    match datagen::datagen(output, options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn suite_command(file: &Path, options: &suite::SuiteOptions) -> ExitCode {
+    match suite::suite(file, options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn analyse_command(
+    input: &Path,
+    output: Option<&Path>,
+    options: &analyse::AnalyseOptions,
+) -> ExitCode {
+    match analyse::analyse(input, output, options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn match_command(engines: &[String], options: &r#match::MatchOptions) -> ExitCode {
+    match r#match::play_match(engines, options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
 pub fn run() -> ExitCode {
     let cli = Cli::parse();
 
     match cli.command {
         Some(c) => match c {
             Command::Uci => uci_command(),
-            Command::Tune { file, epochs } => tune_command(&file, epochs),
+            Command::Xboard => xboard_command(),
+            #[cfg(feature = "tuner")]
+            Command::Tune {
+                file,
+                epochs,
+                lambda,
+                validation_split,
+                checkpoint,
+                checkpoint_every,
+                resume,
+                output,
+            } => tune_command(
+                &file,
+                &tuner::TuneOptions {
+                    epochs,
+                    lambda,
+                    validation_split,
+                    checkpoint,
+                    checkpoint_every,
+                    resume,
+                    output,
+                },
+            ),
+            #[cfg(not(feature = "tuner"))]
+            Command::Tune { .. } => tune_command(),
+            Command::ConvertDataset { input, output } => convert_dataset_command(&input, &output),
+            Command::Datagen {
+                output,
+                games,
+                threads,
+                nodes,
+                random_plies,
+                book,
+                syzygy_path,
+                scores,
+                skip_in_check,
+                skip_captures,
+                skip_mates,
+            } => datagen_command(
+                &output,
+                &datagen::DatagenOptions {
+                    games,
+                    threads,
+                    nodes,
+                    random_plies,
+                    book,
+                    syzygy_path,
+                    write_scores: scores,
+                    skip_in_check,
+                    skip_captures,
+                    skip_mate_scores: skip_mates,
+                },
+            ),
+            Command::Suite {
+                file,
+                time,
+                depth,
+                nodes,
+                hash,
+                threads,
+            } => suite_command(
+                &file,
+                &suite::SuiteOptions {
+                    time: time.map(Duration::from_millis),
+                    depth,
+                    nodes,
+                    hash_size: hash,
+                    threads,
+                },
+            ),
+            Command::Analyse {
+                input,
+                output,
+                time,
+                depth,
+                nodes,
+                hash,
+                threads,
+            } => analyse_command(
+                &input,
+                output.as_deref(),
+                &analyse::AnalyseOptions {
+                    time: time.map(Duration::from_millis),
+                    depth,
+                    nodes,
+                    hash_size: hash,
+                    threads,
+                },
+            ),
+            Command::Match {
+                engines,
+                openings,
+                random_order,
+                rounds,
+                concurrency,
+                tc,
+                time_margin,
+                hash,
+                threads,
+                draw_after,
+                draw_moves,
+                draw_score,
+                resign_moves,
+                resign_score,
+                sprt,
+                elo0,
+                elo1,
+                alpha,
+                beta,
+                pgn,
+            } => match_command(
+                &engines,
+                &r#match::MatchOptions {
+                    openings,
+                    random_order,
+                    rounds: rounds.unwrap_or(if sprt { usize::MAX } else { 100 }),
+                    concurrency,
+                    time_control: tc,
+                    time_margin: Duration::from_millis(time_margin),
+                    hash_size: hash,
+                    threads,
+                    draw: draw_after.map(|move_number| r#match::DrawAdjudication {
+                        move_number,
+                        move_count: draw_moves,
+                        score: draw_score,
+                    }),
+                    resign: resign_moves.map(|move_count| r#match::ResignAdjudication {
+                        move_count,
+                        score: resign_score,
+                    }),
+                    sprt: sprt.then_some(r#match::SprtBounds {
+                        elo0,
+                        elo1,
+                        alpha,
+                        beta,
+                    }),
+                    pgn_output: pgn,
+                },
+            ),
         },
         _ => uci_command(),
     }
@@ -0,0 +1,362 @@
+// Generates training data for the tuner by playing games of the engine against itself.
+//
+// Each game starts from a book or random opening, then both sides play the move found by a
+// node-limited search. Once the game is over, every recorded position is written out along with
+// the game's result (and optionally the search's score) in the format the tuner reads:
+//
+//   <fen> [1.0] <score>
+//
+// Both the result and score are from white's perspective.
+
+use crate::chess::game::Game;
+use crate::chess::player::Player;
+use crate::engine::book::OpeningBook;
+use crate::engine::options::EngineOptions;
+use crate::engine::search;
+use crate::engine::search::time_control::TimeStrategy;
+use crate::engine::search::{
+    CapturingReporter, PersistentState, SearchRestrictions, SearchScore, TimeControl,
+};
+use crate::engine::tablebases::{Tablebase, Wdl};
+use rand::Rng;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+const TT_SIZE_MB: usize = 16;
+
+const REPORT_PROGRESS_EVERY_N_GAMES: usize = 100;
+
+// Mate scores are written as a large centipawn score, so they still favour the right side
+const MATE_SCORE: i16 = 10000;
+
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "These are independent command line flags"
+)]
+pub struct DatagenOptions {
+    pub games: usize,
+    pub threads: usize,
+    pub nodes: u64,
+
+    // The number of plies to play at random before starting the game proper. If a book is given,
+    // moves are taken from the book until it runs out, and then played at random.
+    pub random_plies: usize,
+    pub book: Option<String>,
+
+    pub syzygy_path: Option<String>,
+
+    pub write_scores: bool,
+
+    pub skip_in_check: bool,
+    pub skip_captures: bool,
+    pub skip_mate_scores: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GameResult {
+    WhiteWin,
+    Draw,
+    BlackWin,
+}
+
+impl GameResult {
+    fn win_for(player: Player) -> Self {
+        match player {
+            Player::White => Self::WhiteWin,
+            Player::Black => Self::BlackWin,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::WhiteWin => "1.0",
+            Self::Draw => "0.5",
+            Self::BlackWin => "0.0",
+        }
+    }
+}
+
+struct Position {
+    fen: String,
+
+    // The search score from white's perspective
+    score: i16,
+}
+
+pub fn datagen(output: &Path, options: &DatagenOptions) -> Result<(), String> {
+    let mut book = OpeningBook::new();
+    if let Some(path) = &options.book {
+        book.load(path)?;
+    }
+
+    let mut tablebase = Tablebase::new();
+    if let Some(path) = &options.syzygy_path {
+        tablebase.set_paths(path);
+    }
+
+    let file =
+        File::create(output).map_err(|e| format!("Unable to create {}: {e}", output.display()))?;
+    let writer = Mutex::new(BufWriter::new(file));
+
+    let games_started = AtomicUsize::new(0);
+    let games_finished = AtomicUsize::new(0);
+    let positions_written = AtomicUsize::new(0);
+
+    let started_at = Instant::now();
+
+    std::thread::scope(|s| {
+        for _ in 0..options.threads.max(1) {
+            s.spawn(|| {
+                let mut persistent_state = PersistentState::new(TT_SIZE_MB);
+                persistent_state.tablebase = tablebase.clone();
+
+                while games_started.fetch_add(1, Ordering::Relaxed) < options.games {
+                    persistent_state.reset();
+
+                    // An opening very rarely ends the game, so we just pick another one
+                    let game = loop {
+                        if let Some(game) = play_opening(&book, options.random_plies) {
+                            break game;
+                        }
+                    };
+
+                    let (positions, result) = play_game(game, &mut persistent_state, options);
+
+                    let mut output = String::new();
+                    for position in &positions {
+                        write!(output, "{} [{}]", position.fen, result.as_str()).unwrap();
+
+                        if options.write_scores {
+                            write!(output, " {}", position.score).unwrap();
+                        }
+
+                        output.push('\n');
+                    }
+
+                    writer
+                        .lock()
+                        .unwrap()
+                        .write_all(output.as_bytes())
+                        .expect("Unable to write positions");
+
+                    let positions = positions_written.fetch_add(positions.len(), Ordering::Relaxed)
+                        + positions.len();
+                    let games = games_finished.fetch_add(1, Ordering::Relaxed) + 1;
+
+                    if games % REPORT_PROGRESS_EVERY_N_GAMES == 0 || games == options.games {
+                        let elapsed = started_at.elapsed().as_secs().max(1);
+                        eprintln!(
+                            "{games}/{} games, {positions} positions ({} positions/s)",
+                            options.games,
+                            positions as u64 / elapsed
+                        );
+                    }
+                }
+            });
+        }
+    });
+
+    writer
+        .into_inner()
+        .unwrap()
+        .flush()
+        .map_err(|e| format!("Unable to write {}: {e}", output.display()))
+}
+
+// Returns None if the opening happened to end the game, in which case there's nothing to play.
+fn play_opening(book: &OpeningBook, random_plies: usize) -> Option<Game> {
+    let mut game = Game::new();
+    let mut rng = rand::thread_rng();
+
+    // Follow the book for as long as we can
+    while let Some(mv) = book.pick_move(&game) {
+        game.make_move(mv);
+    }
+
+    for _ in 0..random_plies {
+        let moves = game.moves();
+
+        if moves.is_empty() {
+            return None;
+        }
+
+        game.make_move(moves[rng.gen_range(0..moves.len())]);
+    }
+
+    if game.moves().is_empty() {
+        return None;
+    }
+
+    Some(game)
+}
+
+fn play_game(
+    mut game: Game,
+    persistent_state: &mut PersistentState,
+    options: &DatagenOptions,
+) -> (Vec<Position>, GameResult) {
+    let engine_options = EngineOptions::default();
+    let search_restrictions = SearchRestrictions {
+        nodes: Some(options.nodes),
+        ..SearchRestrictions::default()
+    };
+
+    let mut positions = Vec::new();
+
+    loop {
+        if let Some(result) = adjudicate(&game, &persistent_state.tablebase) {
+            return (positions, result);
+        }
+
+        let mut reporter = CapturingReporter::new();
+        let (mut time_strategy, _) =
+            TimeStrategy::new(&game, &TimeControl::Infinite, &engine_options);
+
+        let best_move = search::search(
+            &game,
+            persistent_state,
+            &mut time_strategy,
+            &search_restrictions,
+            &engine_options,
+            &mut reporter,
+        );
+
+        let (score, is_mate_score) = match reporter.score {
+            Some(SearchScore::Centipawns(cp)) => (cp, false),
+            Some(SearchScore::Mate(moves)) => (moves.signum() * MATE_SCORE, true),
+            None => (0, false),
+        };
+
+        let skip_position = (options.skip_in_check && game.is_king_in_check())
+            || (options.skip_captures && best_move.is_capture())
+            || (options.skip_mate_scores && is_mate_score);
+
+        if !skip_position {
+            positions.push(Position {
+                fen: game.to_fen(),
+                score: match game.player {
+                    Player::White => score,
+                    Player::Black => -score,
+                },
+            });
+        }
+
+        game.make_move(best_move);
+    }
+}
+
+fn adjudicate(game: &Game, tablebase: &Tablebase) -> Option<GameResult> {
+    if game.moves().is_empty() {
+        return Some(if game.is_king_in_check() {
+            GameResult::win_for(game.player.other())
+        } else {
+            GameResult::Draw
+        });
+    }
+
+    // Any repetition is treated as a draw, as the search would do
+    if game.is_repeated_position()
+        || game.is_stalemate_by_fifty_move_rule()
+        || game.is_stalemate_by_insufficient_material()
+    {
+        return Some(GameResult::Draw);
+    }
+
+    if game.board.occupancy().count() <= tablebase.n_men() {
+        return tablebase.wdl(game).map(|wdl| match wdl {
+            Wdl::Win => GameResult::win_for(game.player),
+            Wdl::Draw => GameResult::Draw,
+            Wdl::Loss => GameResult::win_for(game.player.other()),
+        });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjudicate_fen(fen: &str) -> Option<GameResult> {
+        adjudicate(&Game::from_fen(fen).unwrap(), &Tablebase::new())
+    }
+
+    #[test]
+    fn test_play_random_opening() {
+        crate::init();
+
+        let book = OpeningBook::new();
+
+        for _ in 0..20 {
+            if let Some(game) = play_opening(&book, 8) {
+                assert_eq!(game.history.len(), 8);
+                assert!(!game.moves().is_empty());
+            }
+        }
+
+        let game = play_opening(&book, 0).unwrap();
+        assert_eq!(game.to_fen(), Game::new().to_fen());
+    }
+
+    #[test]
+    fn test_adjudicate() {
+        crate::init();
+
+        // Checkmate and stalemate
+        assert_eq!(
+            adjudicate_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            Some(GameResult::BlackWin)
+        );
+        assert_eq!(
+            adjudicate_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(GameResult::Draw)
+        );
+
+        // Insufficient material and the fifty move rule
+        assert_eq!(
+            adjudicate_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        assert_eq!(
+            adjudicate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 100 80"),
+            Some(GameResult::Draw)
+        );
+
+        assert_eq!(adjudicate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn test_scores_are_from_whites_perspective() {
+        crate::init();
+
+        // White is a queen up, with black to move
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        let options = DatagenOptions {
+            games: 1,
+            threads: 1,
+            nodes: 5000,
+            random_plies: 0,
+            book: None,
+            syzygy_path: None,
+            write_scores: true,
+            skip_in_check: false,
+            skip_captures: false,
+            skip_mate_scores: false,
+        };
+
+        let mut persistent_state = PersistentState::new(TT_SIZE_MB);
+        let (positions, result) = play_game(game, &mut persistent_state, &options);
+
+        assert_eq!(result, GameResult::WhiteWin);
+        assert!(positions.iter().all(|position| position.score > 0));
+
+        // Both sides' positions are recorded
+        assert!(positions[0].fen.contains(" b "));
+        assert!(positions[1].fen.contains(" w "));
+    }
+}
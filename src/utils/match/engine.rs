@@ -0,0 +1,326 @@
+// A UCI engine running as a child process.
+//
+// The engine's output is read on a separate thread and passed back over a channel, so that we can
+// stop waiting for a reply when the engine runs out of time or stops responding.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+// How long to wait for replies to anything other than 'go'
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long to give an engine to exit after 'quit' before killing it
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct EngineCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl EngineCommand {
+    // Splits a command line such as "./engine --flag" into the program and its arguments
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| "Empty engine command".to_string())?;
+
+        Ok(Self {
+            program: PathBuf::from(program),
+            args: parts.map(str::to_string).collect(),
+        })
+    }
+
+    // This binary, running its own UCI implementation
+    pub fn built_in() -> Result<Self, String> {
+        let program = std::env::current_exe()
+            .map_err(|e| format!("Unable to find the current executable: {e}"))?;
+
+        Ok(Self {
+            program,
+            args: vec!["uci".to_string()],
+        })
+    }
+
+    fn default_name(&self) -> String {
+        self.program.file_name().map_or_else(
+            || self.program.display().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+pub struct SearchResult {
+    pub best_move: String,
+
+    // The last score and depth the engine reported, from its own perspective
+    pub score: Option<Score>,
+    pub depth: Option<u32>,
+
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub enum EngineError {
+    Timeout,
+    Disconnected,
+}
+
+pub struct Engine {
+    pub name: String,
+    process: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    pub fn start(command: &EngineCommand, options: &[(String, String)]) -> Result<Self, String> {
+        let mut process = Command::new(&command.program)
+            .args(&command.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Unable to start {}: {e}", command.program.display()))?;
+
+        let stdin = process.stdin.take().expect("stdin is piped");
+        let stdout = process.stdout.take().expect("stdout is piped");
+
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            name: command.default_name(),
+            process,
+            stdin,
+            lines,
+        };
+
+        engine
+            .initialise(options)
+            .map_err(|e| format!("Unable to initialise {} ({e:?})", command.program.display()))?;
+
+        Ok(engine)
+    }
+
+    fn initialise(&mut self, options: &[(String, String)]) -> Result<(), EngineError> {
+        self.send("uci")?;
+
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            let line = self.read_line(deadline)?;
+
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        for (name, value) in options {
+            self.send(&format!("setoption name {name} value {value}"))?;
+        }
+
+        self.wait_until_ready()
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|()| self.stdin.flush())
+            .map_err(|_| EngineError::Disconnected)
+    }
+
+    fn read_line(&self, deadline: Instant) -> Result<String, EngineError> {
+        self.lines
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .map_err(|e| match e {
+                RecvTimeoutError::Timeout => EngineError::Timeout,
+                RecvTimeoutError::Disconnected => EngineError::Disconnected,
+            })
+    }
+
+    fn wait_until_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        while self.read_line(deadline)?.trim() != "readyok" {}
+
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.send("ucinewgame")?;
+        self.wait_until_ready()
+    }
+
+    // Searches the given position, giving up once the time limit has passed
+    pub fn go(
+        &mut self,
+        position: &str,
+        go: &str,
+        time_limit: Duration,
+    ) -> Result<SearchResult, EngineError> {
+        self.send(position)?;
+        self.send(go)?;
+
+        let started_at = Instant::now();
+        let deadline = started_at + time_limit;
+
+        let mut score = None;
+        let mut depth = None;
+
+        loop {
+            let line = self.read_line(deadline)?;
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("info") => parse_info(tokens, &mut score, &mut depth),
+                Some("bestmove") => {
+                    let best_move = tokens.next().unwrap_or_default().to_string();
+
+                    return Ok(SearchResult {
+                        best_move,
+                        score,
+                        depth,
+                        elapsed: started_at.elapsed(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Stops a search which has overrun, waiting for its best move so that the next command isn't
+    // answered with a stale one
+    pub fn stop(&mut self) -> Result<(), EngineError> {
+        self.send("stop")?;
+
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        while !self.read_line(deadline)?.starts_with("bestmove") {}
+
+        Ok(())
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if !matches!(self.process.try_wait(), Ok(None)) {
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Reap the process so it doesn't linger as a zombie
+        if self.process.kill().is_ok() {
+            let _status = self.process.wait();
+        }
+    }
+}
+
+fn parse_info<'a>(
+    mut tokens: impl Iterator<Item = &'a str>,
+    score: &mut Option<Score>,
+    depth: &mut Option<u32>,
+) {
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => {
+                if let Some(value) = tokens.next().and_then(|value| value.parse().ok()) {
+                    *depth = Some(value);
+                }
+            }
+            "score" => {
+                let kind = tokens.next();
+                let value = tokens.next().and_then(|value| value.parse().ok());
+
+                match (kind, value) {
+                    (Some("cp"), Some(cp)) => *score = Some(Score::Centipawns(cp)),
+                    (Some("mate"), Some(moves)) => *score = Some(Score::Mate(moves)),
+                    _ => {}
+                }
+            }
+            // The rest of the line is the PV, which could contain anything
+            "pv" | "string" => return,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> (Option<Score>, Option<u32>) {
+        let mut score = None;
+        let mut depth = None;
+        parse_info(line.split_whitespace(), &mut score, &mut depth);
+
+        (score, depth)
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let (score, depth) = parse("depth 12 seldepth 18 score cp -35 nodes 1000 pv e2e4 e7e5");
+        assert!(matches!(score, Some(Score::Centipawns(-35))));
+        assert_eq!(depth, Some(12));
+
+        let (score, depth) = parse("depth 20 score mate -3 lowerbound pv a1a2");
+        assert!(matches!(score, Some(Score::Mate(-3))));
+        assert_eq!(depth, Some(20));
+
+        let (score, depth) = parse("nodes 1000 nps 5000");
+        assert!(score.is_none());
+        assert!(depth.is_none());
+    }
+
+    #[test]
+    fn test_parse_info_ignores_pv_and_strings() {
+        let (score, depth) = parse("depth 3 pv score cp 100 depth 99");
+        assert!(score.is_none());
+        assert_eq!(depth, Some(3));
+
+        let (score, depth) = parse("string depth 5 score cp 20");
+        assert!(score.is_none());
+        assert!(depth.is_none());
+    }
+
+    #[test]
+    fn test_parse_info_keeps_previous_values() {
+        let mut score = Some(Score::Centipawns(10));
+        let mut depth = Some(4);
+
+        parse_info(
+            "depth x score wdl 500 300 200".split_whitespace(),
+            &mut score,
+            &mut depth,
+        );
+
+        assert!(matches!(score, Some(Score::Centipawns(10))));
+        assert_eq!(depth, Some(4));
+    }
+}
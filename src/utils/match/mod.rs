@@ -0,0 +1,1022 @@
+// Plays a match between two UCI engines, reporting the Elo difference between them as it goes.
+//
+// Games are played in pairs from the same opening with colours reversed, so that unbalanced
+// openings don't favour either engine. Each engine runs as a separate process talking UCI, and
+// the built-in engine is run the same way (as this binary's 'uci' command), which exercises our
+// UCI implementation end to end.
+//
+// Games end by the rules of chess, when an engine runs out of time, plays an illegal move or
+// crashes, or optionally by adjudication:
+//
+//   draw   - both engines have reported scores within a margin of zero for a number of moves
+//   resign - both engines agree that one side is winning by a margin for a number of moves
+//
+// With an SPRT configured, the match stops as soon as either hypothesis is accepted.
+
+mod engine;
+mod openings;
+mod sprt;
+
+pub use sprt::SprtBounds;
+
+use crate::chess::game::Game;
+use crate::chess::pgn::{self, GameResult, Line, PgnGame, PgnMove};
+use crate::chess::player::{ByPlayer, Player};
+use crate::engine::uci::parser::uci_moves;
+use crate::engine::uci::UciMove;
+use engine::{Engine, EngineCommand, EngineError, Score, SearchResult};
+use openings::Opening;
+use rand::seq::SliceRandom;
+use sprt::{Pentanomial, SprtResult};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Mate scores are treated as a large centipawn score for adjudication
+const MATE_SCORE: i32 = 100_000;
+
+#[derive(Clone, Copy)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    // Parses a time control in seconds, e.g. "8+0.08" or "60"
+    pub fn parse(tc: &str) -> Result<Self, String> {
+        let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+
+        let parse_seconds = |seconds: &str| {
+            seconds
+                .parse::<f64>()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| format!("Invalid time control '{tc}'"))
+        };
+
+        Ok(Self {
+            base: parse_seconds(base)?,
+            increment: parse_seconds(increment)?,
+        })
+    }
+
+    fn notation(self) -> String {
+        format!(
+            "{}+{}",
+            self.base.as_secs_f64(),
+            self.increment.as_secs_f64()
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct DrawAdjudication {
+    // The first move number at which a game can be adjudicated as a draw
+    pub move_number: u32,
+    pub move_count: u32,
+    pub score: i32,
+}
+
+#[derive(Clone, Copy)]
+pub struct ResignAdjudication {
+    pub move_count: u32,
+    pub score: i32,
+}
+
+pub struct MatchOptions {
+    pub openings: Option<PathBuf>,
+    pub random_order: bool,
+
+    // The number of game pairs to play
+    pub rounds: usize,
+    pub concurrency: usize,
+
+    pub time_control: TimeControl,
+
+    // How long an engine may overrun its clock before losing on time
+    pub time_margin: Duration,
+
+    pub hash_size: usize,
+    pub threads: usize,
+
+    pub draw: Option<DrawAdjudication>,
+    pub resign: Option<ResignAdjudication>,
+    pub sprt: Option<SprtBounds>,
+
+    pub pgn_output: Option<PathBuf>,
+}
+
+enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    DrawAdjudication,
+    ResignAdjudication,
+    TimeForfeit,
+    IllegalMove(String),
+    Disconnected,
+}
+
+impl Termination {
+    fn description(&self) -> String {
+        match self {
+            Self::Checkmate => "checkmate".to_string(),
+            Self::Stalemate => "stalemate".to_string(),
+            Self::Repetition => "threefold repetition".to_string(),
+            Self::FiftyMoveRule => "fifty move rule".to_string(),
+            Self::InsufficientMaterial => "insufficient material".to_string(),
+            Self::DrawAdjudication => "draw adjudication".to_string(),
+            Self::ResignAdjudication => "resign adjudication".to_string(),
+            Self::TimeForfeit => "loss on time".to_string(),
+            Self::IllegalMove(mv) => format!("illegal move {mv}"),
+            Self::Disconnected => "engine disconnected".to_string(),
+        }
+    }
+
+    // The value of the PGN Termination tag
+    const fn tag(&self) -> &'static str {
+        match self {
+            Self::Checkmate
+            | Self::Stalemate
+            | Self::Repetition
+            | Self::FiftyMoveRule
+            | Self::InsufficientMaterial => "normal",
+            Self::DrawAdjudication | Self::ResignAdjudication => "adjudication",
+            Self::TimeForfeit => "time forfeit",
+            Self::IllegalMove(_) => "rules infraction",
+            Self::Disconnected => "abandoned",
+        }
+    }
+}
+
+const fn win_for(player: Player) -> GameResult {
+    match player {
+        Player::White => GameResult::WhiteWin,
+        Player::Black => GameResult::BlackWin,
+    }
+}
+
+fn game_over(game: &Game) -> Option<(GameResult, Termination)> {
+    if game.moves().is_empty() {
+        return Some(if game.is_king_in_check() {
+            (win_for(game.player.other()), Termination::Checkmate)
+        } else {
+            (GameResult::Draw, Termination::Stalemate)
+        });
+    }
+
+    if game.is_threefold_repetition() {
+        return Some((GameResult::Draw, Termination::Repetition));
+    }
+
+    if game.halfmove_clock >= 100 {
+        return Some((GameResult::Draw, Termination::FiftyMoveRule));
+    }
+
+    if game.is_insufficient_material() {
+        return Some((GameResult::Draw, Termination::InsufficientMaterial));
+    }
+
+    None
+}
+
+// Tracks how long the engines' scores have met the adjudication conditions
+#[derive(Default)]
+struct Adjudicator {
+    drawn_plies: u32,
+    winning_plies: u32,
+    winner: Option<Player>,
+}
+
+impl Adjudicator {
+    fn update(
+        &mut self,
+        game: &Game,
+        mover: Player,
+        score: Option<Score>,
+        options: &MatchOptions,
+    ) -> Option<(GameResult, Termination)> {
+        let Some(score) = score else {
+            *self = Self::default();
+            return None;
+        };
+
+        let score = match score {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(mate) if mate > 0 => MATE_SCORE,
+            Score::Mate(_) => -MATE_SCORE,
+        };
+
+        let white_score = match mover {
+            Player::White => score,
+            Player::Black => -score,
+        };
+
+        if let Some(draw) = &options.draw {
+            if white_score.abs() <= draw.score {
+                self.drawn_plies += 1;
+            } else {
+                self.drawn_plies = 0;
+            }
+
+            if game.turn() >= draw.move_number && self.drawn_plies >= 2 * draw.move_count {
+                return Some((GameResult::Draw, Termination::DrawAdjudication));
+            }
+        }
+
+        if let Some(resign) = &options.resign {
+            // Both engines have to agree, so the count continues across plies only while the same
+            // side is winning
+            let winner = if white_score >= resign.score {
+                Some(Player::White)
+            } else if white_score <= -resign.score {
+                Some(Player::Black)
+            } else {
+                None
+            };
+
+            if winner.is_some() && winner == self.winner {
+                self.winning_plies += 1;
+            } else {
+                self.winning_plies = u32::from(winner.is_some());
+            }
+            self.winner = winner;
+
+            if let Some(winner) = winner {
+                if self.winning_plies >= 2 * resign.move_count {
+                    return Some((win_for(winner), Termination::ResignAdjudication));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Each engine's remaining time, which is charged for a move once the engine has replied
+struct Clocks {
+    remaining: ByPlayer<Duration>,
+    time_control: TimeControl,
+    time_margin: Duration,
+}
+
+impl Clocks {
+    fn new(options: &MatchOptions) -> Self {
+        let base = options.time_control.base;
+
+        Self {
+            remaining: ByPlayer::new(base, base),
+            time_control: options.time_control,
+            time_margin: options.time_margin,
+        }
+    }
+
+    // How long the player can take over their move before losing on time
+    fn time_limit(&self, player: Player) -> Duration {
+        *self.remaining.for_player(player) + self.time_margin
+    }
+
+    fn go_command(&self) -> String {
+        format!(
+            "go wtime {} btime {} winc {} binc {}",
+            self.remaining.white().as_millis(),
+            self.remaining.black().as_millis(),
+            self.time_control.increment.as_millis(),
+            self.time_control.increment.as_millis()
+        )
+    }
+
+    // Returns false if the player took longer than their time limit
+    fn record_move(&mut self, player: Player, elapsed: Duration) -> bool {
+        if elapsed > self.time_limit(player) {
+            return false;
+        }
+
+        let remaining = self.remaining.for_player_mut(player);
+        *remaining = remaining.saturating_sub(elapsed) + self.time_control.increment;
+
+        true
+    }
+}
+
+struct GameRecord {
+    result: GameResult,
+    termination: Termination,
+    moves: Vec<PgnMove>,
+}
+
+fn parse_move(game: &Game, mv: &str) -> Option<crate::chess::moves::Move> {
+    match uci_moves(mv) {
+        Ok(("", moves)) => match moves.as_slice() {
+            [uci_move] => uci_move.to_move(game),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A comment like "+0.25/12 0.150s", with the score from the mover's perspective
+fn format_move_comment(result: &SearchResult) -> String {
+    let score = match result.score {
+        Some(Score::Centipawns(cp)) => format!("{:+.2}", f64::from(cp) / 100.0),
+        Some(Score::Mate(moves)) if moves > 0 => format!("+M{moves}"),
+        Some(Score::Mate(moves)) => format!("-M{}", -moves),
+        None => "?".to_string(),
+    };
+
+    format!(
+        "{score}/{} {:.3}s",
+        result.depth.unwrap_or_default(),
+        result.elapsed.as_secs_f64()
+    )
+}
+
+fn play_game(
+    white: &mut Engine,
+    black: &mut Engine,
+    opening: &Opening,
+    options: &MatchOptions,
+) -> GameRecord {
+    let mut game = opening.start.clone();
+    let start_fen = game.to_fen();
+
+    let mut uci_history = Vec::new();
+    let mut moves = Vec::new();
+
+    for mv in &opening.moves {
+        uci_history.push(UciMove::from_move(*mv, &game, false).notation());
+
+        let mut pgn_move = PgnMove::new(*mv);
+        pgn_move.comment = Some("book".to_string());
+        moves.push(pgn_move);
+
+        game.make_move(*mv);
+    }
+
+    let mut clocks = Clocks::new(options);
+    let mut engines = ByPlayer::new(white, black);
+    let mut adjudicator = Adjudicator::default();
+
+    let (result, termination) = loop {
+        if let Some(outcome) = game_over(&game) {
+            break outcome;
+        }
+
+        let player = game.player;
+
+        let position = if uci_history.is_empty() {
+            format!("position fen {start_fen}")
+        } else {
+            format!("position fen {start_fen} moves {}", uci_history.join(" "))
+        };
+
+        let go = clocks.go_command();
+        let time_limit = clocks.time_limit(player);
+
+        let engine = engines.for_player_mut(player);
+
+        let search_result = match engine.go(&position, &go, time_limit) {
+            Ok(search_result) => search_result,
+            Err(EngineError::Timeout) => {
+                // If the engine doesn't stop either, it'll be restarted before the next game
+                let _ = engine.stop();
+                break (win_for(player.other()), Termination::TimeForfeit);
+            }
+            Err(EngineError::Disconnected) => {
+                break (win_for(player.other()), Termination::Disconnected)
+            }
+        };
+
+        if !clocks.record_move(player, search_result.elapsed) {
+            break (win_for(player.other()), Termination::TimeForfeit);
+        }
+
+        let Some(mv) = parse_move(&game, &search_result.best_move) else {
+            break (
+                win_for(player.other()),
+                Termination::IllegalMove(search_result.best_move),
+            );
+        };
+
+        uci_history.push(UciMove::from_move(mv, &game, false).notation());
+
+        let mut pgn_move = PgnMove::new(mv);
+        pgn_move.comment = Some(format_move_comment(&search_result));
+        moves.push(pgn_move);
+
+        game.make_move(mv);
+
+        if let Some(outcome) = adjudicator.update(&game, player, search_result.score, options) {
+            break outcome;
+        }
+    };
+
+    GameRecord {
+        result,
+        termination,
+        moves,
+    }
+}
+
+#[derive(Default)]
+struct Standings {
+    games: usize,
+
+    // From the first engine's perspective
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    pentanomial: Pentanomial,
+
+    sprt_result: Option<SprtResult>,
+}
+
+struct Match<'a> {
+    names: [String; 2],
+    openings: Vec<Opening>,
+    options: &'a MatchOptions,
+    engine_options: Vec<(String, String)>,
+
+    standings: Mutex<Standings>,
+    pgn_output: Mutex<Option<BufWriter<File>>>,
+    next_pair: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+impl Match<'_> {
+    fn record_game(
+        &self,
+        white: &str,
+        black: &str,
+        opening: &Opening,
+        record: &GameRecord,
+        round: usize,
+    ) {
+        let game_number = {
+            let mut standings = self.standings.lock().unwrap();
+            standings.games += 1;
+            standings.games
+        };
+
+        println!(
+            "Finished game {game_number} ({white} vs {black}): {} {{{}}}",
+            record.result.notation(),
+            record.termination.description()
+        );
+
+        if self.options.pgn_output.is_none() {
+            return;
+        }
+
+        let mut pgn_game = PgnGame {
+            tags: Vec::new(),
+            start: opening.start.clone(),
+            mainline: Line {
+                comment: None,
+                moves: record.moves.clone(),
+            },
+            result: record.result,
+        };
+
+        pgn_game.set_tag("Event", &format!("{} match", crate::ENGINE_NAME));
+        pgn_game.set_tag("Round", &(round + 1).to_string());
+        pgn_game.set_tag("White", white);
+        pgn_game.set_tag("Black", black);
+        pgn_game.set_tag("Result", record.result.notation());
+        pgn_game.set_tag("TimeControl", &self.options.time_control.notation());
+        pgn_game.set_tag("Termination", record.termination.tag());
+
+        if let Some(last) = pgn_game.mainline.moves.last_mut() {
+            let description = record.termination.description();
+            last.comment = Some(match last.comment.take() {
+                Some(comment) => format!("{comment}, {description}"),
+                None => description,
+            });
+        }
+
+        let pgn = pgn::write(&pgn_game);
+
+        let mut pgn_output = self.pgn_output.lock().unwrap();
+        let Some(writer) = pgn_output.as_mut() else {
+            return;
+        };
+
+        if let Err(e) = writeln!(writer, "{pgn}").and_then(|()| writer.flush()) {
+            eprintln!("Unable to write PGN: {e}");
+        }
+    }
+
+    // The first engine's points (in half points) from a pair of games
+    fn record_pair(&self, half_points: usize) {
+        let mut standings = self.standings.lock().unwrap();
+        standings.pentanomial.0[half_points] += 1;
+
+        if let Some(bounds) = &self.options.sprt {
+            standings.sprt_result = standings.pentanomial.sprt(bounds);
+
+            if standings.sprt_result.is_some() {
+                self.stopped.store(true, Ordering::Relaxed);
+            }
+        }
+
+        self.print_standings(&standings);
+    }
+
+    fn print_standings(&self, standings: &Standings) {
+        let games = standings.wins + standings.draws + standings.losses;
+        let points = f64::from(standings.wins) + f64::from(standings.draws) / 2.0;
+
+        println!();
+        println!(
+            "Score of {} vs {}: {} - {} - {} [{:.3}] {games}",
+            self.names[0],
+            self.names[1],
+            standings.wins,
+            standings.losses,
+            standings.draws,
+            points / f64::from(games.max(1))
+        );
+
+        let (lower, elo, upper) = standings.pentanomial.elo();
+        println!(
+            "Elo: {elo:.2} +/- {:.2}, Ptnml(0-2): {:?}",
+            (upper - lower) / 2.0,
+            standings.pentanomial.0
+        );
+
+        if let Some(bounds) = &self.options.sprt {
+            let llr = standings
+                .pentanomial
+                .llr(bounds)
+                .map_or_else(|| "n/a".to_string(), |llr| format!("{llr:.2}"));
+
+            println!(
+                "LLR: {llr} ({:.2}, {:.2}) [{:.2}, {:.2}]",
+                bounds.lower_llr(),
+                bounds.upper_llr(),
+                bounds.elo0,
+                bounds.elo1
+            );
+        }
+
+        match standings.sprt_result {
+            Some(SprtResult::AcceptH0) => println!("SPRT: H0 accepted"),
+            Some(SprtResult::AcceptH1) => println!("SPRT: H1 accepted"),
+            None => {}
+        }
+
+        println!();
+    }
+
+    // Restarts any engine which didn't respond to a new game, e.g. because it crashed
+    fn prepare_engines(
+        &self,
+        engines: &mut [Engine; 2],
+        commands: &[EngineCommand; 2],
+    ) -> Result<(), String> {
+        for (engine, command) in engines.iter_mut().zip(commands) {
+            if engine.new_game().is_err() {
+                eprintln!("Restarting {}", engine.name);
+                *engine = Engine::start(command, &self.engine_options)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn play_pairs(&self, engines: &mut [Engine; 2], commands: &[EngineCommand; 2]) {
+        while !self.stopped.load(Ordering::Relaxed) {
+            let round = self.next_pair.fetch_add(1, Ordering::Relaxed);
+            if round >= self.options.rounds {
+                return;
+            }
+
+            let opening = &self.openings[round % self.openings.len()];
+            let mut half_points = 0;
+
+            for first_plays_white in [true, false] {
+                if let Err(e) = self.prepare_engines(engines, commands) {
+                    eprintln!("{e}");
+                    self.stopped.store(true, Ordering::Relaxed);
+                    return;
+                }
+
+                let [first, second] = &mut *engines;
+                let [first_name, second_name] = &self.names;
+                let (white, black, white_name, black_name) = if first_plays_white {
+                    (first, second, first_name, second_name)
+                } else {
+                    (second, first, second_name, first_name)
+                };
+
+                let record = play_game(white, black, opening, self.options);
+                self.record_game(white_name, black_name, opening, &record, round);
+
+                let white_half_points = match record.result {
+                    GameResult::WhiteWin => 2,
+                    GameResult::Draw | GameResult::Unknown => 1,
+                    GameResult::BlackWin => 0,
+                };
+
+                let first_half_points = if first_plays_white {
+                    white_half_points
+                } else {
+                    2 - white_half_points
+                };
+
+                let mut standings = self.standings.lock().unwrap();
+                match first_half_points {
+                    2 => standings.wins += 1,
+                    1 => standings.draws += 1,
+                    _ => standings.losses += 1,
+                }
+                drop(standings);
+
+                half_points += first_half_points;
+            }
+
+            self.record_pair(half_points);
+        }
+    }
+}
+
+// With a single engine command, the built-in engine plays against it
+pub fn play_match(engines: &[String], options: &MatchOptions) -> Result<(), String> {
+    let commands = match engines {
+        [engine] => [EngineCommand::built_in()?, EngineCommand::parse(engine)?],
+        [first, second] => [EngineCommand::parse(first)?, EngineCommand::parse(second)?],
+        _ => return Err("Expected one or two engine commands".to_string()),
+    };
+
+    if let Some(bounds) = &options.sprt {
+        bounds.validate()?;
+    }
+
+    let mut openings = match &options.openings {
+        Some(path) => openings::load(path)?,
+        None => vec![Opening::start_position()],
+    };
+
+    if options.random_order {
+        openings.shuffle(&mut rand::thread_rng());
+    }
+
+    let engine_options = vec![
+        ("Hash".to_string(), options.hash_size.to_string()),
+        ("Threads".to_string(), options.threads.to_string()),
+    ];
+
+    // Start every engine up front, so that a bad command fails the match immediately
+    let mut engine_pairs = Vec::new();
+    for _ in 0..options.concurrency.max(1) {
+        engine_pairs.push([
+            Engine::start(&commands[0], &engine_options)?,
+            Engine::start(&commands[1], &engine_options)?,
+        ]);
+    }
+
+    let mut names = engine_pairs[0].each_ref().map(|engine| engine.name.clone());
+    if names[0] == names[1] {
+        names = [format!("{} (1)", names[0]), format!("{} (2)", names[1])];
+    }
+
+    let pgn_output = options
+        .pgn_output
+        .as_ref()
+        .map(|path| {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| format!("Unable to create {}: {e}", path.display()))
+        })
+        .transpose()?;
+
+    let state = Match {
+        names,
+        openings,
+        options,
+        engine_options,
+        standings: Mutex::new(Standings::default()),
+        pgn_output: Mutex::new(pgn_output),
+        next_pair: AtomicUsize::new(0),
+        stopped: AtomicBool::new(false),
+    };
+
+    std::thread::scope(|s| {
+        for mut engines in engine_pairs {
+            let state = &state;
+            let commands = &commands;
+            s.spawn(move || state.play_pairs(&mut engines, commands));
+        }
+    });
+
+    let standings = state.standings.lock().unwrap();
+    if standings.sprt_result.is_none() {
+        println!("Finished match");
+        state.print_standings(&standings);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn match_options() -> MatchOptions {
+        MatchOptions {
+            openings: None,
+            random_order: false,
+            rounds: 1,
+            concurrency: 1,
+            time_control: TimeControl {
+                base: Duration::from_secs(1),
+                increment: Duration::from_millis(100),
+            },
+            time_margin: Duration::from_millis(50),
+            hash_size: 1,
+            threads: 1,
+            draw: None,
+            resign: None,
+            sprt: None,
+            pgn_output: None,
+        }
+    }
+
+    fn game_after(moves: &[&str]) -> Game {
+        let mut game = Game::new();
+
+        for mv in moves {
+            let mv = parse_move(&game, mv).unwrap();
+            game.make_move(mv);
+        }
+
+        game
+    }
+
+    #[test]
+    fn test_parse_time_control() {
+        let tc = TimeControl::parse("8+0.08").unwrap();
+        assert_eq!(tc.base, Duration::from_secs(8));
+        assert_eq!(tc.increment, Duration::from_millis(80));
+
+        let tc = TimeControl::parse("60").unwrap();
+        assert_eq!(tc.base, Duration::from_secs(60));
+        assert_eq!(tc.increment, Duration::ZERO);
+
+        assert_eq!(TimeControl::parse("0.5+1").unwrap().notation(), "0.5+1");
+
+        for invalid in ["", "abc", "10+", "10+x", "-1", "10+-1", "inf"] {
+            assert!(TimeControl::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_game_over() {
+        crate::init();
+
+        assert!(game_over(&Game::new()).is_none());
+
+        let checkmate = game_after(&["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert!(matches!(
+            game_over(&checkmate),
+            Some((GameResult::BlackWin, Termination::Checkmate))
+        ));
+
+        let stalemate = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(matches!(
+            game_over(&stalemate),
+            Some((GameResult::Draw, Termination::Stalemate))
+        ));
+
+        let repetition = game_after(&[
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ]);
+        assert!(matches!(
+            game_over(&repetition),
+            Some((GameResult::Draw, Termination::Repetition))
+        ));
+
+        let fifty_moves = Game::from_fen("4k3/8/8/8/8/8/4P3/4K2R w - - 100 80").unwrap();
+        assert!(matches!(
+            game_over(&fifty_moves),
+            Some((GameResult::Draw, Termination::FiftyMoveRule))
+        ));
+
+        let insufficient_material = Game::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert!(matches!(
+            game_over(&insufficient_material),
+            Some((GameResult::Draw, Termination::InsufficientMaterial))
+        ));
+    }
+
+    #[test]
+    fn test_draw_adjudication() {
+        crate::init();
+
+        let options = MatchOptions {
+            draw: Some(DrawAdjudication {
+                move_number: 40,
+                move_count: 2,
+                score: 10,
+            }),
+            ..match_options()
+        };
+
+        let early = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 30").unwrap();
+        let late = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 40").unwrap();
+
+        // Both engines have to report drawn scores for the given number of moves
+        let mut adjudicator = Adjudicator::default();
+        for (mover, cp) in [(Player::White, 5), (Player::Black, -10), (Player::White, 0)] {
+            let score = Some(Score::Centipawns(cp));
+            assert!(adjudicator.update(&late, mover, score, &options).is_none());
+        }
+        assert!(matches!(
+            adjudicator.update(&late, Player::Black, Some(Score::Centipawns(3)), &options),
+            Some((GameResult::Draw, Termination::DrawAdjudication))
+        ));
+
+        // A score outside the margin starts the count again
+        let mut adjudicator = Adjudicator::default();
+        for (mover, cp) in [(Player::White, 0), (Player::Black, 0), (Player::White, 11)] {
+            let score = Some(Score::Centipawns(cp));
+            assert!(adjudicator.update(&late, mover, score, &options).is_none());
+        }
+        assert!(adjudicator
+            .update(&late, Player::Black, Some(Score::Centipawns(0)), &options)
+            .is_none());
+
+        // Games aren't adjudicated before the given move number
+        let mut adjudicator = Adjudicator::default();
+        for mover in [Player::White, Player::Black, Player::White, Player::Black] {
+            let score = Some(Score::Centipawns(0));
+            assert!(adjudicator.update(&early, mover, score, &options).is_none());
+        }
+    }
+
+    #[test]
+    fn test_resign_adjudication() {
+        crate::init();
+
+        let options = MatchOptions {
+            resign: Some(ResignAdjudication {
+                move_count: 2,
+                score: 500,
+            }),
+            ..match_options()
+        };
+
+        let game = Game::new();
+
+        // Scores are from the mover's perspective, so both engines agree that white is winning
+        let mut adjudicator = Adjudicator::default();
+        let scores = [
+            (Player::White, Score::Centipawns(600)),
+            (Player::Black, Score::Centipawns(-550)),
+            (Player::White, Score::Mate(5)),
+        ];
+        for (mover, score) in scores {
+            assert!(adjudicator
+                .update(&game, mover, Some(score), &options)
+                .is_none());
+        }
+        assert!(matches!(
+            adjudicator.update(&game, Player::Black, Some(Score::Mate(-4)), &options),
+            Some((GameResult::WhiteWin, Termination::ResignAdjudication))
+        ));
+
+        // The engines disagree about who is winning
+        let mut adjudicator = Adjudicator::default();
+        for mover in [Player::White, Player::Black, Player::White, Player::Black] {
+            let score = Some(Score::Centipawns(600));
+            assert!(adjudicator.update(&game, mover, score, &options).is_none());
+        }
+
+        // An engine not reporting a score starts the count again
+        let mut adjudicator = Adjudicator::default();
+        let scores = [
+            (Player::White, Some(Score::Centipawns(-700))),
+            (Player::Black, Some(Score::Centipawns(700))),
+            (Player::White, None),
+            (Player::Black, Some(Score::Centipawns(700))),
+            (Player::White, Some(Score::Centipawns(-700))),
+            (Player::Black, Some(Score::Centipawns(700))),
+        ];
+        for (mover, score) in scores {
+            assert!(adjudicator.update(&game, mover, score, &options).is_none());
+        }
+        assert!(matches!(
+            adjudicator.update(
+                &game,
+                Player::White,
+                Some(Score::Centipawns(-700)),
+                &options
+            ),
+            Some((GameResult::BlackWin, Termination::ResignAdjudication))
+        ));
+    }
+
+    #[test]
+    fn test_clocks() {
+        let mut clocks = Clocks::new(&match_options());
+
+        assert_eq!(
+            clocks.go_command(),
+            "go wtime 1000 btime 1000 winc 100 binc 100"
+        );
+        assert_eq!(
+            clocks.time_limit(Player::White),
+            Duration::from_millis(1050)
+        );
+
+        // The increment is added once the move has been played
+        assert!(clocks.record_move(Player::White, Duration::from_millis(300)));
+        assert_eq!(
+            clocks.go_command(),
+            "go wtime 800 btime 1000 winc 100 binc 100"
+        );
+
+        // Moves within the margin are allowed, but use up the whole clock
+        assert!(clocks.record_move(Player::Black, Duration::from_millis(1040)));
+        assert_eq!(
+            clocks.go_command(),
+            "go wtime 800 btime 100 winc 100 binc 100"
+        );
+        assert_eq!(clocks.time_limit(Player::Black), Duration::from_millis(150));
+
+        assert!(!clocks.record_move(Player::Black, Duration::from_millis(151)));
+    }
+
+    // An engine which answers every search with the given command, e.g. sleeping and then
+    // playing a move
+    #[cfg(unix)]
+    fn scripted_engine(go: &str) -> Engine {
+        let script = format!(
+            "while read -r line; do case \"$line\" in \
+                uci) echo uciok;; \
+                isready) echo readyok;; \
+                go*) {go};; \
+                quit) exit;; \
+            esac; done"
+        );
+
+        let command = EngineCommand {
+            program: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), script],
+        };
+
+        Engine::start(&command, &[]).unwrap()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_play_game_time_forfeit() {
+        crate::init();
+
+        let options = MatchOptions {
+            time_control: TimeControl {
+                base: Duration::from_millis(50),
+                increment: Duration::ZERO,
+            },
+            time_margin: Duration::ZERO,
+            ..match_options()
+        };
+
+        let mut white = scripted_engine("echo bestmove e2e4");
+        let mut black = scripted_engine("sleep 0.5; echo bestmove e7e5");
+
+        let record = play_game(&mut white, &mut black, &Opening::start_position(), &options);
+
+        assert_eq!(record.result, GameResult::WhiteWin);
+        assert!(matches!(record.termination, Termination::TimeForfeit));
+        assert_eq!(record.moves.len(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_play_game_illegal_move() {
+        crate::init();
+
+        let mut white = scripted_engine("echo bestmove e2e5");
+        let mut black = scripted_engine("echo bestmove e7e5");
+
+        let record = play_game(
+            &mut white,
+            &mut black,
+            &Opening::start_position(),
+            &match_options(),
+        );
+
+        assert_eq!(record.result, GameResult::BlackWin);
+        assert!(matches!(
+            record.termination,
+            Termination::IllegalMove(ref mv) if mv == "e2e5"
+        ));
+        assert!(record.moves.is_empty());
+    }
+}
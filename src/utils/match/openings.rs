@@ -0,0 +1,118 @@
+// Loads the positions games start from. An EPD file gives one position per line (any operations
+// after the position are ignored), and a PGN file gives each game's starting position and
+// mainline moves.
+
+use crate::chess::game::Game;
+use crate::chess::moves::Move;
+use crate::chess::pgn;
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct Opening {
+    pub start: Game,
+    pub moves: Vec<Move>,
+}
+
+impl Opening {
+    pub fn start_position() -> Self {
+        Self {
+            start: Game::new(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+fn parse_epd(epd: &str) -> Result<Vec<Opening>, String> {
+    epd.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let position = line
+                .split_whitespace()
+                .take(4)
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let start = Game::from_fen(&position)
+                .map_err(|e| format!("Invalid position on line {}: {e}", i + 1))?;
+
+            Ok(Opening {
+                start,
+                moves: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+fn parse_pgn(pgn: &str) -> Result<Vec<Opening>, String> {
+    Ok(pgn::parse(pgn)?
+        .into_iter()
+        .map(|game| Opening {
+            moves: game
+                .mainline
+                .moves
+                .iter()
+                .map(|pgn_move| pgn_move.mv)
+                .collect(),
+            start: game.start,
+        })
+        .collect())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Opening>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+
+    let is_pgn = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"));
+
+    let openings = if is_pgn {
+        parse_pgn(&contents)?
+    } else {
+        parse_epd(&contents)?
+    };
+
+    if openings.is_empty() {
+        return Err(format!("No openings in {}", path.display()));
+    }
+
+    Ok(openings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_epd() {
+        crate::init();
+
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";\n\
+                   \n\
+                   4k3/8/8/8/8/8/4P3/4K3 w - -\n";
+
+        let openings = parse_epd(epd).unwrap();
+        assert_eq!(openings.len(), 2);
+
+        assert_eq!(
+            openings[0].start.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        assert!(openings[0].moves.is_empty());
+        assert_eq!(
+            openings[1].start.to_fen(),
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_epd_reports_line_of_invalid_position() {
+        crate::init();
+
+        let epd = "4k3/8/8/8/8/8/4P3/4K3 w - -\nnot a position\n";
+
+        let err = parse_epd(epd).err().unwrap();
+        assert!(err.starts_with("Invalid position on line 2"), "{err}");
+    }
+}
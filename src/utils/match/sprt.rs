@@ -0,0 +1,325 @@
+// Elo estimates and the sequential probability ratio test over game pairs, ported from
+// etc/sprt.py (which in turn takes its pentanomial SPRT from Fishtest). See
+// https://hardy.uhasselt.be/Fishtest/normalized_elo_practical.pdf for the maths.
+//
+// Results are counted per game pair rather than per game, as the two games of a pair share an
+// opening and so aren't independent. A pair scores 0, 0.5, 1, 1.5 or 2 points for the first
+// engine.
+
+// The 97.5th percentile of the standard normal distribution, for 95% confidence intervals
+const NORMAL_QUANTILE_97_5: f64 = 1.959_963_984_540_054;
+
+// Empty buckets are given a tiny count to avoid dividing by zero
+const MIN_BUCKET_COUNT: f64 = 1e-3;
+
+const MLE_ITERATIONS: usize = 10;
+const MLE_TOLERANCE: f64 = 1e-9;
+
+const SECULAR_EPSILON: f64 = 1e-9;
+const SECULAR_ITERATIONS: usize = 200;
+
+// Each pair's score as a fraction of the maximum
+const PAIR_SCORES: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+// A distribution over pair scores, as (value, probability)
+type Pdf = [(f64, f64); 5];
+
+#[derive(Clone, Copy)]
+pub struct SprtBounds {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl SprtBounds {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.elo0 >= self.elo1 {
+            return Err("elo0 must be less than elo1".to_string());
+        }
+
+        if !(0.0..1.0).contains(&self.alpha) || !(0.0..1.0).contains(&self.beta) {
+            return Err("alpha and beta must be between 0 and 1".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn lower_llr(&self) -> f64 {
+        (self.beta / (1.0 - self.alpha)).ln()
+    }
+
+    pub fn upper_llr(&self) -> f64 {
+        ((1.0 - self.beta) / self.alpha).ln()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SprtResult {
+    AcceptH0,
+    AcceptH1,
+}
+
+// The number of game pairs which scored each of 0, 0.5, 1, 1.5 and 2 points
+#[derive(Clone, Copy, Default)]
+pub struct Pentanomial(pub [u32; 5]);
+
+impl Pentanomial {
+    pub fn pairs(&self) -> u32 {
+        self.0.iter().sum()
+    }
+
+    // Each bucket as (pair score, count)
+    fn outcomes(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        PAIR_SCORES
+            .into_iter()
+            .zip(self.0.iter().map(|count| f64::from(*count)))
+    }
+
+    // The Elo difference with the bounds of its 95% confidence interval, as (lower, elo, upper)
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let n = f64::from(self.pairs());
+        if n == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let mu = self
+            .outcomes()
+            .map(|(score, count)| score * count)
+            .sum::<f64>()
+            / n;
+        let var = self
+            .outcomes()
+            .map(|(score, count)| (score - mu).powi(2) * count)
+            .sum::<f64>()
+            / n;
+
+        let interval = NORMAL_QUANTILE_97_5 * var.sqrt() / n.sqrt();
+
+        (
+            logistic_elo(mu - interval),
+            logistic_elo(mu),
+            logistic_elo(mu + interval),
+        )
+    }
+
+    // None if the bounds are too far apart from the results for the model to fit, which only
+    // happens with unrealistically large Elo bounds
+    pub fn llr(&self, bounds: &SprtBounds) -> Option<f64> {
+        let counts = self.0.map(|count| f64::from(count).max(MIN_BUCKET_COUNT));
+        let n = counts.iter().sum::<f64>();
+
+        let pdf: Pdf = std::array::from_fn(|i| (PAIR_SCORES[i], counts[i] / n));
+
+        // Convert logistic Elo to a normalised t-value
+        let nelo_divided_by_nt = 800.0 / std::f64::consts::LN_10;
+        let t0 = bounds.elo0 / nelo_divided_by_nt * std::f64::consts::SQRT_2;
+        let t1 = bounds.elo1 / nelo_divided_by_nt * std::f64::consts::SQRT_2;
+
+        let pdf0 = mle_tvalue(&pdf, 0.5, t0)?;
+        let pdf1 = mle_tvalue(&pdf, 0.5, t1)?;
+
+        let llr_per_pair = pdf
+            .iter()
+            .zip(pdf0.iter().zip(&pdf1))
+            .map(|((_, p), ((_, p0), (_, p1)))| p * (p1.ln() - p0.ln()))
+            .sum::<f64>();
+
+        Some(n * llr_per_pair)
+    }
+
+    pub fn sprt(&self, bounds: &SprtBounds) -> Option<SprtResult> {
+        let llr = self.llr(bounds)?;
+
+        if llr >= bounds.upper_llr() {
+            Some(SprtResult::AcceptH1)
+        } else if llr <= bounds.lower_llr() {
+            Some(SprtResult::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+fn logistic_elo(score: f64) -> f64 {
+    let score = score.clamp(1e-3, 1.0 - 1e-3);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// The mean and variance of a distribution given as (value, probability) pairs
+fn stats(pdf: &Pdf) -> (f64, f64) {
+    let mean = pdf.iter().map(|(value, p)| p * value).sum::<f64>();
+    let var = pdf
+        .iter()
+        .map(|(value, p)| p * (value - mean).powi(2))
+        .sum();
+    (mean, var)
+}
+
+// Solves the secular equation sum_i p_i * a_i / (1 + x * a_i) = 0 for x. The left hand side is
+// strictly decreasing between its poles at -1 / max(a_i) and -1 / min(a_i), so bisection finds the
+// single root between them. There's no root if the a_i all have the same sign.
+fn secular(pdf: &Pdf) -> Option<f64> {
+    let min = pdf.iter().map(|(a, _)| *a).fold(f64::INFINITY, f64::min);
+    let max = pdf
+        .iter()
+        .map(|(a, _)| *a)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    if min * max >= 0.0 {
+        return None;
+    }
+
+    let f = |x: f64| pdf.iter().map(|(a, p)| p * a / (1.0 + x * a)).sum::<f64>();
+
+    let mut lower = -1.0 / max + SECULAR_EPSILON;
+    let mut upper = -1.0 / min - SECULAR_EPSILON;
+
+    for _ in 0..SECULAR_ITERATIONS {
+        let mid = (lower + upper) / 2.0;
+
+        if f(mid) > 0.0 {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+
+    Some((lower + upper) / 2.0)
+}
+
+// The maximum likelihood distribution with the given normalised t-value, against a reference
+// score of `reference`
+fn mle_tvalue(pdf: &Pdf, reference: f64, t: f64) -> Option<Pdf> {
+    let mut mle = pdf.map(|(a, _)| (a, 1.0 / 5.0));
+
+    for _ in 0..MLE_ITERATIONS {
+        let (mu, var) = stats(&mle);
+        let sigma = var.sqrt();
+
+        let shifted = pdf.map(|(a, p)| {
+            let z = (mu - a) / sigma;
+            (a - reference - t * sigma * z.mul_add(z, 1.0) / 2.0, p)
+        });
+
+        let x = secular(&shifted)?;
+
+        let next: Pdf = std::array::from_fn(|i| (pdf[i].0, pdf[i].1 / (1.0 + x * shifted[i].0)));
+
+        let converged = mle
+            .iter()
+            .zip(&next)
+            .all(|((_, old), (_, new))| (old - new).abs() < MLE_TOLERANCE);
+
+        mle = next;
+
+        if converged {
+            break;
+        }
+    }
+
+    Some(mle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference values are from etc/sprt.py
+    const BOUNDS: SprtBounds = SprtBounds {
+        elo0: 0.0,
+        elo1: 5.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    fn assert_approx_eq(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_elo(pentanomial: Pentanomial, expected: (f64, f64, f64)) {
+        let (lower, elo, upper) = pentanomial.elo();
+        assert_approx_eq(lower, expected.0);
+        assert_approx_eq(elo, expected.1);
+        assert_approx_eq(upper, expected.2);
+    }
+
+    #[test]
+    fn test_even_results() {
+        let pentanomial = Pentanomial([10, 20, 40, 20, 10]);
+
+        assert_elo(
+            pentanomial,
+            (-37.442_059_735_357_475, 0.0, 37.442_059_735_357_54),
+        );
+        assert_approx_eq(
+            pentanomial.llr(&BOUNDS).unwrap(),
+            -0.020_709_467_029_953_472,
+        );
+        assert!(pentanomial.sprt(&BOUNDS).is_none());
+    }
+
+    #[test]
+    fn test_empty_buckets() {
+        let pentanomial = Pentanomial([0, 5, 30, 10, 0]);
+
+        assert_elo(
+            pentanomial,
+            (
+                -9.456_478_434_296_969,
+                19.321_871_829_822_037,
+                48.368_164_861_424_3,
+            ),
+        );
+        assert_approx_eq(pentanomial.llr(&BOUNDS).unwrap(), 0.167_038_444_214_453_5);
+    }
+
+    #[test]
+    fn test_sprt_accepts_h1() {
+        let pentanomial = Pentanomial([40, 200, 500, 320, 80]);
+
+        assert_elo(
+            pentanomial,
+            (
+                21.225_662_470_775_51,
+                30.555_338_345_381_905,
+                39.929_185_516_789_815,
+            ),
+        );
+
+        let llr = pentanomial.llr(&BOUNDS).unwrap();
+        assert_approx_eq(llr, 4.091_111_908_142_159);
+        assert!(llr >= BOUNDS.upper_llr());
+        assert!(pentanomial.sprt(&BOUNDS) == Some(SprtResult::AcceptH1));
+    }
+
+    #[test]
+    fn test_sprt_accepts_h0() {
+        let pentanomial = Pentanomial([120, 300, 500, 200, 40]);
+
+        assert_approx_eq(pentanomial.llr(&BOUNDS).unwrap(), -5.598_167_730_760_291);
+        assert!(pentanomial.sprt(&BOUNDS) == Some(SprtResult::AcceptH0));
+    }
+
+    #[test]
+    fn test_no_games() {
+        let pentanomial = Pentanomial::default();
+
+        assert_elo(pentanomial, (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_secular_without_sign_change() {
+        let positive: Pdf = std::array::from_fn(|i| (PAIR_SCORES[i] + 0.1, 0.2));
+        let negative: Pdf = std::array::from_fn(|i| (PAIR_SCORES[i] - 1.1, 0.2));
+
+        assert!(secular(&positive).is_none());
+        assert!(secular(&negative).is_none());
+
+        let mixed: Pdf = std::array::from_fn(|i| (PAIR_SCORES[i] - 0.5, 0.2));
+        assert_approx_eq(secular(&mixed).unwrap(), 0.0);
+    }
+}
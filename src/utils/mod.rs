@@ -1,4 +1,8 @@
+pub mod analyse;
 pub mod cli;
+pub mod datagen;
+pub mod r#match;
+pub mod suite;
 
 #[cfg(feature = "tuner")]
 pub mod tuner;
@@ -0,0 +1,453 @@
+// Runs an EPD test suite, checking the move the engine chooses in each position.
+//
+// Each line of the file is a position (the first four fields of a FEN) followed by operations:
+//
+//   <position> bm Qg6; id "WAC.001";
+//
+// The following operations are understood, with moves in either SAN or UCI notation:
+//
+//   bm - the best moves, one of which must be played
+//   am - moves which must not be played
+//   dm - a direct mate in this many moves, which must be found
+//   id - a name for the position
+//
+// The time to solution is when the search started reporting a solving move without changing its
+// mind afterwards.
+
+use crate::chess::game::Game;
+use crate::chess::moves::Move;
+use crate::chess::san;
+use crate::engine::options::EngineOptions;
+use crate::engine::search;
+use crate::engine::search::time_control::TimeStrategy;
+use crate::engine::search::{
+    PersistentState, Reporter, SearchInfo, SearchRestrictions, SearchScore, TimeControl,
+};
+use crate::engine::uci::parser::uci_moves;
+use crate::engine::util;
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, take_till, take_while1},
+    character::complete::{char, space0, space1},
+    combinator::{eof, recognize},
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+use std::path::Path;
+use std::time::Duration;
+
+// Used when no search limit is given
+const DEFAULT_TIME: Duration = Duration::from_secs(1);
+
+pub struct SuiteOptions {
+    pub time: Option<Duration>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub hash_size: usize,
+    pub threads: usize,
+}
+
+struct TestPosition {
+    id: String,
+    game: Game,
+    best_moves: Vec<Move>,
+    avoid_moves: Vec<Move>,
+    direct_mate: Option<i16>,
+}
+
+impl TestPosition {
+    fn is_solved_by(&self, mv: Move, score: Option<&SearchScore>) -> bool {
+        let finds_mate = self.direct_mate.is_none_or(
+            |moves| matches!(score, Some(SearchScore::Mate(mate)) if *mate > 0 && *mate <= moves),
+        );
+
+        (self.best_moves.is_empty() || self.best_moves.contains(&mv))
+            && !self.avoid_moves.contains(&mv)
+            && finds_mate
+    }
+
+    fn expected(&self) -> String {
+        let format_moves = |moves: &[Move]| {
+            moves
+                .iter()
+                .map(|mv| san::format_move(&self.game, *mv))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let mut expected = Vec::new();
+
+        if !self.best_moves.is_empty() {
+            expected.push(format!("bm {}", format_moves(&self.best_moves)));
+        }
+
+        if !self.avoid_moves.is_empty() {
+            expected.push(format!("am {}", format_moves(&self.avoid_moves)));
+        }
+
+        if let Some(moves) = self.direct_mate {
+            expected.push(format!("dm {moves}"));
+        }
+
+        expected.join(", ")
+    }
+}
+
+struct Solution {
+    time: Duration,
+    depth: u8,
+    nodes: u64,
+}
+
+struct SuiteReporter<'a> {
+    position: &'a TestPosition,
+    solution: Option<Solution>,
+    score: Option<SearchScore>,
+    nodes: u64,
+}
+
+impl Reporter for SuiteReporter<'_> {
+    fn generic_report(&self, _: &str) {}
+
+    fn report_search_progress(&mut self, _: &Game, progress: SearchInfo) {
+        if progress.multipv != 1 {
+            return;
+        }
+
+        let is_solved = progress
+            .pv
+            .first()
+            .is_some_and(|mv| self.position.is_solved_by(*mv, Some(&progress.score)));
+
+        if !is_solved {
+            self.solution = None;
+        } else if self.solution.is_none() {
+            self.solution = Some(Solution {
+                time: progress.stats.time,
+                depth: progress.depth,
+                nodes: progress.stats.nodes,
+            });
+        }
+
+        self.score = Some(progress.score);
+        self.nodes = progress.stats.nodes;
+    }
+
+    fn book_move(&self, _: &Game, _: Move) {}
+
+    fn best_move(&self, _: &Game, _: Move) {}
+}
+
+fn epd_position(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        is_not(" "),
+        space1,
+        is_not(" "),
+        space1,
+        is_not(" "),
+        space1,
+        is_not(" ;"),
+    )))(input)
+}
+
+fn epd_operand(input: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(char('"'), take_till(|c| c == '"'), char('"')),
+        is_not(" ;"),
+    ))(input)
+}
+
+fn epd_operation(input: &str) -> IResult<&str, (&str, Vec<&str>)> {
+    terminated(
+        pair(
+            take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+            many0(preceded(space1, epd_operand)),
+        ),
+        pair(space0, char(';')),
+    )(input)
+}
+
+fn epd_line(input: &str) -> IResult<&str, (&str, Vec<(&str, Vec<&str>)>)> {
+    terminated(
+        pair(epd_position, many0(preceded(space0, epd_operation))),
+        pair(space0, eof),
+    )(input)
+}
+
+fn parse_move(game: &Game, mv: &str) -> Result<Move, String> {
+    if let Ok(("", uci_moves)) = uci_moves(mv) {
+        if let [uci_move] = uci_moves.as_slice() {
+            return uci_move
+                .to_move(game)
+                .ok_or_else(|| format!("Illegal move {mv}"));
+        }
+    }
+
+    san::parse_move(game, mv).map_err(|e| format!("Invalid move {mv} ({e:?})"))
+}
+
+fn parse_position(line: &str, line_number: usize) -> Result<TestPosition, String> {
+    let (_, (position, operations)) =
+        epd_line(line).map_err(|e| format!("Invalid EPD on line {line_number} ({e})"))?;
+
+    let game = Game::from_fen(position)?;
+
+    let mut test_position = TestPosition {
+        id: format!("Line {line_number}"),
+        game,
+        best_moves: Vec::new(),
+        avoid_moves: Vec::new(),
+        direct_mate: None,
+    };
+
+    for (opcode, operands) in operations {
+        let parse_moves = || {
+            operands
+                .iter()
+                .map(|mv| parse_move(&test_position.game, mv))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("{e} on line {line_number}"))
+        };
+
+        match opcode {
+            "bm" => test_position.best_moves = parse_moves()?,
+            "am" => test_position.avoid_moves = parse_moves()?,
+            "dm" => {
+                let moves = operands
+                    .first()
+                    .and_then(|moves| moves.parse().ok())
+                    .ok_or_else(|| format!("Invalid dm on line {line_number}"))?;
+
+                test_position.direct_mate = Some(moves);
+            }
+            "id" => {
+                if let Some(id) = operands.first() {
+                    test_position.id = (*id).to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if test_position.best_moves.is_empty()
+        && test_position.avoid_moves.is_empty()
+        && test_position.direct_mate.is_none()
+    {
+        return Err(format!("No bm, am or dm operation on line {line_number}"));
+    }
+
+    Ok(test_position)
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    reason = "Suites are nowhere near large enough for these casts to be lossy"
+)]
+fn print_summary(
+    positions: usize,
+    failed: &[&str],
+    total_solution_time: Duration,
+    total_time: Duration,
+    total_nodes: u64,
+) {
+    let solved = positions - failed.len();
+
+    println!();
+    println!(
+        "Solved {solved}/{positions} ({:.1}%)",
+        100.0 * solved as f64 / positions.max(1) as f64
+    );
+
+    if !failed.is_empty() {
+        println!("Failed: {}", failed.join(", "));
+    }
+
+    if solved > 0 {
+        println!(
+            "Average time to solution: {}",
+            format_duration(total_solution_time / solved as u32)
+        );
+    }
+
+    println!(
+        "Total time: {}, {total_nodes} nodes ({} nps)",
+        format_duration(total_time),
+        util::metrics::nodes_per_second(total_nodes, total_time)
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2}s", duration.as_secs_f64())
+}
+
+pub fn suite(file: &Path, options: &SuiteOptions) -> Result<(), String> {
+    let epd = std::fs::read_to_string(file)
+        .map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+
+    let positions = epd
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_position(line.trim(), i + 1))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let engine_options = EngineOptions {
+        hash_size: options.hash_size,
+        threads: options.threads,
+        ..EngineOptions::default()
+    };
+
+    let search_restrictions = SearchRestrictions {
+        depth: options.depth,
+        nodes: options.nodes,
+        ..SearchRestrictions::default()
+    };
+
+    let time = if options.time.is_none() && options.depth.is_none() && options.nodes.is_none() {
+        Some(DEFAULT_TIME)
+    } else {
+        options.time
+    };
+
+    let time_control = time.map_or(TimeControl::Infinite, TimeControl::ExactTime);
+
+    let mut persistent_state = PersistentState::new(options.hash_size);
+
+    let mut failed = Vec::new();
+    let mut total_solution_time = Duration::ZERO;
+    let mut total_time = Duration::ZERO;
+    let mut total_nodes = 0;
+
+    for position in &positions {
+        persistent_state.reset();
+
+        let mut reporter = SuiteReporter {
+            position,
+            solution: None,
+            score: None,
+            nodes: 0,
+        };
+
+        let (mut time_strategy, _) =
+            TimeStrategy::new(&position.game, &time_control, &engine_options);
+
+        let best_move = search::search(
+            &position.game,
+            &mut persistent_state,
+            &mut time_strategy,
+            &search_restrictions,
+            &engine_options,
+            &mut reporter,
+        );
+
+        let elapsed = time_strategy.elapsed();
+        total_time += elapsed;
+        total_nodes += reporter.nodes;
+
+        let played = san::format_move(&position.game, best_move);
+
+        let solution = reporter
+            .solution
+            .filter(|_| position.is_solved_by(best_move, reporter.score.as_ref()));
+
+        if let Some(solution) = solution {
+            total_solution_time += solution.time;
+
+            println!(
+                "{}: solved in {} at depth {} ({} nodes), played {played}",
+                position.id,
+                format_duration(solution.time),
+                solution.depth,
+                solution.nodes
+            );
+        } else {
+            failed.push(position.id.as_str());
+
+            println!(
+                "{}: failed, played {played} but expected {}",
+                position.id,
+                position.expected()
+            );
+        }
+    }
+
+    print_summary(
+        positions.len(),
+        &failed,
+        total_solution_time,
+        total_time,
+        total_nodes,
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::moves::MoveListExt;
+    use crate::chess::square::squares::all::*;
+
+    const WAC_001: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -";
+
+    #[test]
+    fn test_parse_best_move() {
+        crate::init();
+
+        let position = parse_position(&format!("{WAC_001} bm Qg6; id \"WAC.001\";"), 1).unwrap();
+        let qg6 = position.game.moves().expect_matching(G3, G6, None);
+
+        assert_eq!(position.id, "WAC.001");
+        assert_eq!(position.best_moves, vec![qg6]);
+        assert!(position.avoid_moves.is_empty());
+        assert!(position.is_solved_by(qg6, None));
+
+        let position = parse_position(&format!("{WAC_001} bm g3g6;"), 7).unwrap();
+        assert_eq!(position.id, "Line 7");
+        assert_eq!(position.best_moves, vec![qg6]);
+    }
+
+    #[test]
+    fn test_parse_avoid_moves_and_direct_mate() {
+        crate::init();
+
+        let position = parse_position(
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - am Kf1 Rb1; dm 1; id \"Back rank mate\";",
+            1,
+        )
+        .unwrap();
+
+        let game = &position.game;
+        assert_eq!(
+            position.avoid_moves,
+            vec![
+                game.moves().expect_matching(G1, F1, None),
+                game.moves().expect_matching(A1, B1, None),
+            ]
+        );
+        assert_eq!(position.direct_mate, Some(1));
+        assert_eq!(position.id, "Back rank mate");
+
+        let ra8 = game.moves().expect_matching(A1, A8, None);
+        assert!(position.is_solved_by(ra8, Some(&SearchScore::Mate(1))));
+        assert!(!position.is_solved_by(ra8, Some(&SearchScore::Centipawns(900))));
+    }
+
+    #[test]
+    fn test_parse_invalid_positions() {
+        crate::init();
+
+        assert!(parse_position(&format!("{WAC_001} id \"WAC.001\";"), 1)
+            .is_err_and(|e| e.starts_with("No bm, am or dm")));
+        assert!(parse_position(&format!("{WAC_001} bm Qh8;"), 1)
+            .is_err_and(|e| e.starts_with("Invalid move Qh8")));
+        assert!(parse_position(&format!("{WAC_001} bm g3h8;"), 1)
+            .is_err_and(|e| e.starts_with("Illegal move g3h8")));
+        assert!(parse_position(&format!("{WAC_001} bm Qg6"), 1)
+            .is_err_and(|e| e.starts_with("Invalid EPD")));
+        assert!(parse_position(&format!("{WAC_001} dm mate;"), 1)
+            .is_err_and(|e| e.starts_with("Invalid dm")));
+    }
+}
@@ -0,0 +1,180 @@
+// A snapshot of a tuning run, so that it can be stopped and later resumed from where it left off.
+//
+// Checkpoints are stored as a header:
+//   magic (8 bytes), version (u32), number of parameters (u32)
+//
+// Followed by the state of the run, all little-endian:
+//   epoch (u64), seed (u64), k (f32)
+//   and then the parameters, momentum and velocities, each as (midgame, endgame) f32 pairs
+
+use super::{read_bytes, TunerEval};
+use crate::engine::eval::Trace;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"TUNECKPT";
+const VERSION: u32 = 1;
+
+pub struct Checkpoint {
+    // The number of epochs which have been run so far
+    pub epoch: usize,
+
+    // The seed used to split the dataset, so that we hold out the same validation positions
+    pub seed: u64,
+
+    pub k: f32,
+
+    pub parameters: [TunerEval; Trace::SIZE],
+    pub momentum: [TunerEval; Trace::SIZE],
+    pub velocities: [TunerEval; Trace::SIZE],
+}
+
+impl Checkpoint {
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        // Write to a temporary file first, so that stopping the tuner part way through writing
+        // can't leave us with a corrupted checkpoint.
+        let temporary_path = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&u32::try_from(Trace::SIZE).unwrap().to_le_bytes())?;
+
+        writer.write_all(&(self.epoch as u64).to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.k.to_le_bytes())?;
+
+        for values in [&self.parameters, &self.momentum, &self.velocities] {
+            for value in values {
+                writer.write_all(&value.midgame().to_le_bytes())?;
+                writer.write_all(&value.endgame().to_le_bytes())?;
+            }
+        }
+
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(temporary_path, path)
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let magic: [u8; 8] = read_bytes(&mut reader)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a tuner checkpoint"));
+        }
+
+        let version = u32::from_le_bytes(read_bytes(&mut reader)?);
+        if version != VERSION {
+            return Err(invalid_data("Unsupported checkpoint version"));
+        }
+
+        let parameters = u32::from_le_bytes(read_bytes(&mut reader)?);
+        if parameters as usize != Trace::SIZE {
+            return Err(invalid_data(
+                "Checkpoint was created for a different set of parameters",
+            ));
+        }
+
+        let epoch = usize::try_from(u64::from_le_bytes(read_bytes(&mut reader)?))
+            .map_err(|_| invalid_data("Checkpoint epoch is out of range"))?;
+        let seed = u64::from_le_bytes(read_bytes(&mut reader)?);
+        let k = f32::from_le_bytes(read_bytes(&mut reader)?);
+
+        let mut checkpoint = Self {
+            epoch,
+            seed,
+            k,
+            parameters: [TunerEval::ZERO; Trace::SIZE],
+            momentum: [TunerEval::ZERO; Trace::SIZE],
+            velocities: [TunerEval::ZERO; Trace::SIZE],
+        };
+
+        for values in [
+            &mut checkpoint.parameters,
+            &mut checkpoint.momentum,
+            &mut checkpoint.velocities,
+        ] {
+            for value in values {
+                let midgame = f32::from_le_bytes(read_bytes(&mut reader)?);
+                let endgame = f32::from_le_bytes(read_bytes(&mut reader)?);
+                *value = TunerEval::new(midgame, endgame);
+            }
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}.ckpt", std::process::id()))
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path = temporary_path("checkpoint-round-trip");
+
+        let mut checkpoint = Checkpoint {
+            epoch: 12,
+            seed: 0xDEAD_BEEF,
+            k: 1.25,
+            parameters: [TunerEval::ZERO; Trace::SIZE],
+            momentum: [TunerEval::ZERO; Trace::SIZE],
+            velocities: [TunerEval::ZERO; Trace::SIZE],
+        };
+
+        checkpoint.parameters[0] = TunerEval::new(101.5, 169.25);
+        checkpoint.momentum[Trace::SIZE - 1] = TunerEval::new(-0.5, 0.125);
+        checkpoint.velocities[7] = TunerEval::new(3.0, -4.0);
+
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.epoch, checkpoint.epoch);
+        assert_eq!(loaded.seed, checkpoint.seed);
+        assert_eq!(loaded.k.to_bits(), checkpoint.k.to_bits());
+
+        assert_eq!(loaded.parameters, checkpoint.parameters);
+        assert_eq!(loaded.momentum, checkpoint.momentum);
+        assert_eq!(loaded.velocities, checkpoint.velocities);
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_checkpoints() {
+        let path = temporary_path("checkpoint-invalid");
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"NOTACKPT");
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+        let err = Checkpoint::load(&path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+        let err = Checkpoint::load(&path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(u32::try_from(Trace::SIZE).unwrap() + 1).to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+        let err = Checkpoint::load(&path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+// Tuner datasets can be provided in two formats:
+//
+// * Text, with one position per line: '<fen> [<outcome>]', optionally followed by a search score.
+//   The evaluation coefficients for each position have to be calculated every time it is loaded.
+//
+// * Binary, converted from the text format with the 'convert-dataset' command. This stores the
+//   pre-calculated coefficients for each position, so it is much faster to load and much smaller.
+//
+// Binary datasets are memory-mapped rather than loaded, so the operating system can page records
+// in and out as each epoch reads through them and the dataset doesn't need to fit in memory.
+// Text datasets are converted to the binary format in memory as they are loaded, so they use
+// about as much memory as the equivalent binary file.
+
+use super::{start_progress_bar, tuner_eval, Entry, NonZeroCoefficient, Outcome};
+use crate::chess::game::Game;
+use crate::engine::eval::{absolute_eval_with_trace, Trace};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::Deref;
+use std::path::Path;
+
+// The binary format starts with a header:
+//   magic (8 bytes), version (u32), number of parameters (u32)
+//
+// Followed by one record per position, all little-endian:
+//   outcome (u8), phase (u8), score (i16), static eval (i16), number of coefficients (u16)
+//   and then for each coefficient: parameter index (u16), value (i16)
+const MAGIC: &[u8; 8] = b"TUNEDATA";
+const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = MAGIC.len() + 4 + 4;
+const RECORD_HEADER_SIZE: usize = 8;
+const COEFFICIENT_SIZE: usize = 4;
+
+// Written in place of a search score, for positions which don't have one
+const NO_SCORE: i16 = i16::MIN;
+
+// A position parsed from the text format, before it is packed into a record
+struct Position {
+    outcome: Outcome,
+    phase: u8,
+    score: Option<i16>,
+    static_eval: i16,
+    coefficients: Vec<NonZeroCoefficient>,
+}
+
+enum Bytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(mmap) => mmap,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+// A dataset in the binary format. Entries are decoded from their records as they are needed, and
+// are identified by the offset of their record.
+pub struct Dataset {
+    bytes: Bytes,
+}
+
+impl Dataset {
+    pub fn load(path: &Path) -> Self {
+        let mut reader = BufReader::new(File::open(path).expect("Unable to read file"));
+
+        let is_binary = reader
+            .fill_buf()
+            .expect("Unable to read file")
+            .starts_with(MAGIC);
+
+        let bytes = if is_binary {
+            // !: The file isn't expected to be modified while we're tuning with it.
+            Bytes::Mapped(unsafe { Mmap::map(reader.get_ref()) }.expect("Unable to read file"))
+        } else {
+            Bytes::Owned(convert_text(reader, file_size(path)))
+        };
+
+        Self::from_bytes(bytes).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, String> {
+        read_header(&bytes)?;
+        Ok(Self { bytes })
+    }
+
+    // The offsets of every record, in the order they appear in the file
+    pub fn records(&self) -> Vec<usize> {
+        let mut records = Vec::new();
+        let mut offset = HEADER_SIZE;
+
+        while offset < self.bytes.len() {
+            records.push(offset);
+            offset += record_size(&self.bytes[offset..]);
+        }
+
+        assert_eq!(offset, self.bytes.len(), "Dataset is truncated");
+
+        records
+    }
+
+    pub fn entry(&self, record: usize) -> Entry<'_> {
+        read_entry(&self.bytes[record..])
+    }
+}
+
+pub fn convert(input: &Path, output: &Path) {
+    let reader = BufReader::new(File::open(input).expect("Unable to read file"));
+    let mut writer = BufWriter::new(File::create(output).expect("Unable to create file"));
+
+    write_text_as_binary(
+        reader,
+        &mut writer,
+        file_size(input),
+        "Converting positions",
+    )
+    .expect("Unable to write file");
+
+    writer.flush().expect("Unable to write file");
+}
+
+fn convert_text(reader: impl BufRead, size: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    write_text_as_binary(reader, &mut bytes, size, "Loading positions")
+        .expect("Unable to read file");
+
+    bytes
+}
+
+fn write_text_as_binary(
+    reader: impl BufRead,
+    writer: &mut impl Write,
+    size: usize,
+    label: &str,
+) -> std::io::Result<()> {
+    write_header(writer)?;
+
+    let progress = start_progress_bar(size, label);
+
+    for line in reader.lines() {
+        let line = line?;
+        progress.inc(line.len() as u64 + 1);
+
+        write_entry(writer, &parse_line(&line))?;
+    }
+
+    progress.finish();
+
+    Ok(())
+}
+
+fn write_header(writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&number_of_parameters().to_le_bytes())
+}
+
+fn read_header(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(MAGIC) {
+        return Err("Not a tuner dataset".to_string());
+    }
+
+    let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+
+    if version != VERSION {
+        return Err(format!("Unsupported dataset version: {version}"));
+    }
+
+    let parameters = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+
+    if parameters != number_of_parameters() {
+        return Err(
+            "Dataset was created for a different set of parameters. Convert it again.".to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "Scores and evals are small integers"
+)]
+fn parse_line(line: &str) -> Position {
+    let (fen_str, rest) = line.split_once('[').expect("Unexpected file format");
+    let (outcome_str, score_str) = rest.split_once(']').expect("Unexpected file format");
+    let fen_str = fen_str.trim();
+    let score_str = score_str.trim();
+
+    let game = Game::from_fen(fen_str).expect("Unexpected fen");
+
+    let outcome = match outcome_str.trim() {
+        "1.0" => Outcome::Win,
+        "0.5" => Outcome::Draw,
+        "0.0" => Outcome::Loss,
+        _ => panic!("Unexpected outcome format"),
+    };
+
+    let score = if score_str.is_empty() {
+        None
+    } else {
+        Some(
+            score_str
+                .parse::<f32>()
+                .expect("Unexpected score format")
+                .round() as i16,
+        )
+    };
+
+    let mut trace = Trace::new();
+    let static_eval = absolute_eval_with_trace::<true>(&game, &mut trace);
+
+    Position {
+        outcome,
+        phase: u8::try_from(game.incremental_eval.phase_value).unwrap(),
+        score,
+        static_eval: static_eval.0,
+        coefficients: trace.non_zero_coefficients(),
+    }
+}
+
+// Coefficients were integers before they were converted to floats for the tuner, so we can convert
+// them back without losing anything.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "Coefficients are small integers"
+)]
+fn write_entry(writer: &mut impl Write, position: &Position) -> std::io::Result<()> {
+    let outcome: u8 = match position.outcome {
+        Outcome::Loss => 0,
+        Outcome::Draw => 1,
+        Outcome::Win => 2,
+    };
+
+    let score = position.score.unwrap_or(NO_SCORE);
+    let number_of_coefficients = u16::try_from(position.coefficients.len()).unwrap();
+
+    writer.write_all(&[outcome, position.phase])?;
+    writer.write_all(&score.to_le_bytes())?;
+    writer.write_all(&position.static_eval.to_le_bytes())?;
+    writer.write_all(&number_of_coefficients.to_le_bytes())?;
+
+    for coefficient in &position.coefficients {
+        let idx = u16::try_from(coefficient.idx).unwrap();
+        let value = coefficient.value as i16;
+
+        writer.write_all(&idx.to_le_bytes())?;
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn record_size(record: &[u8]) -> usize {
+    assert!(record.len() >= RECORD_HEADER_SIZE, "Dataset is truncated");
+
+    let number_of_coefficients = u16::from_le_bytes([record[6], record[7]]);
+    RECORD_HEADER_SIZE + COEFFICIENT_SIZE * usize::from(number_of_coefficients)
+}
+
+fn read_entry(record: &[u8]) -> Entry<'_> {
+    let outcome = match record[0] {
+        0 => Outcome::Loss,
+        1 => Outcome::Draw,
+        2 => Outcome::Win,
+        _ => panic!("Unexpected outcome format"),
+    };
+
+    let score = i16::from_le_bytes([record[2], record[3]]);
+    let static_eval = i16::from_le_bytes([record[4], record[5]]);
+
+    let midgame_percentage = f32::from(record[1]) / f32::from(tuner_eval::PHASE_COUNT_MAX);
+    let endgame_percentage = 1.0 - midgame_percentage;
+
+    Entry {
+        outcome,
+        coefficients: &record[RECORD_HEADER_SIZE..record_size(record)],
+
+        score: (score != NO_SCORE).then(|| f32::from(score)),
+        static_eval: f32::from(static_eval),
+
+        midgame_percentage,
+        endgame_percentage,
+    }
+}
+
+fn number_of_parameters() -> u32 {
+    u32::try_from(Trace::SIZE).unwrap()
+}
+
+fn file_size(path: &Path) -> usize {
+    std::fs::metadata(path).map_or(0, |m| usize::try_from(m.len()).unwrap_or(usize::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset_bytes(positions: &[Position]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes).unwrap();
+
+        for position in positions {
+            write_entry(&mut bytes, position).unwrap();
+        }
+
+        bytes
+    }
+
+    #[test]
+    #[expect(
+        clippy::float_cmp,
+        reason = "Values are stored as integers, so they are read back exactly"
+    )]
+    fn test_write_and_read_entries() {
+        let bytes = dataset_bytes(&[
+            Position {
+                outcome: Outcome::Win,
+                phase: 24,
+                score: Some(-153),
+                static_eval: 87,
+                coefficients: vec![
+                    NonZeroCoefficient::new(0, 1.0),
+                    NonZeroCoefficient::new(Trace::SIZE - 1, -2.0),
+                ],
+            },
+            Position {
+                outcome: Outcome::Draw,
+                phase: 0,
+                score: None,
+                static_eval: -5,
+                coefficients: vec![],
+            },
+        ]);
+
+        let dataset = Dataset::from_bytes(Bytes::Owned(bytes)).unwrap();
+        let records = dataset.records();
+        assert_eq!(records.len(), 2);
+
+        let entry = dataset.entry(records[0]);
+        assert!(matches!(entry.outcome, Outcome::Win));
+        assert_eq!(entry.score, Some(-153.0));
+        assert_eq!(entry.static_eval, 87.0);
+        assert_eq!(entry.midgame_percentage, 1.0);
+        assert_eq!(
+            entry.coefficients().collect::<Vec<_>>(),
+            vec![(0, 1.0), (Trace::SIZE - 1, -2.0)]
+        );
+
+        let entry = dataset.entry(records[1]);
+        assert!(matches!(entry.outcome, Outcome::Draw));
+        assert_eq!(entry.score, None);
+        assert_eq!(entry.static_eval, -5.0);
+        assert_eq!(entry.endgame_percentage, 1.0);
+        assert_eq!(entry.coefficients().count(), 0);
+    }
+
+    #[test]
+    fn test_reject_mismatched_header() {
+        let mut bytes = dataset_bytes(&[]);
+        assert!(Dataset::from_bytes(Bytes::Owned(bytes.clone())).is_ok());
+
+        bytes[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(Dataset::from_bytes(Bytes::Owned(bytes.clone()))
+            .is_err_and(|e| e.starts_with("Unsupported dataset version")));
+
+        bytes[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[12..16].copy_from_slice(&(number_of_parameters() + 1).to_le_bytes());
+        assert!(Dataset::from_bytes(Bytes::Owned(bytes))
+            .is_err_and(|e| e.contains("different set of parameters")));
+
+        assert!(Dataset::from_bytes(Bytes::Owned(b"8/8/8/8".to_vec())).is_err());
+    }
+}
@@ -2,17 +2,45 @@
 // This code borrows heavily from https://github.com/GediminasMasaitis/texel-tuner
 // which is in turn based on https://github.com/AndyGrant/Ethereal/blob/master/Tuning.pdf
 
-use crate::chess::game::Game;
-use crate::engine::eval::{absolute_eval_with_trace, Parameters, Trace};
+use crate::engine::eval::{Parameters, Trace};
+use checkpoint::Checkpoint;
+use dataset::Dataset;
 use indicatif::{ProgressBar, ProgressStyle};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
+mod checkpoint;
+mod dataset;
 pub mod parameters;
 mod tuner_eval;
 
+pub use dataset::convert;
 pub use tuner_eval::TunerEval;
 
+const K_FITTING_ITERATIONS: usize = 4;
+
+pub struct TuneOptions {
+    pub epochs: usize,
+
+    // How much to fit to the positions' search scores, rather than the outcomes of their games
+    pub lambda: f32,
+
+    // The fraction of positions to hold out for validation
+    pub validation_split: f32,
+
+    pub checkpoint: PathBuf,
+    pub checkpoint_every: usize,
+    pub resume: bool,
+
+    // If set, the tuned parameters are written to this file in the layout of params.rs rather than
+    // being printed.
+    pub output: Option<PathBuf>,
+}
+
 enum Outcome {
     Win,
     Draw,
@@ -41,14 +69,34 @@ impl NonZeroCoefficient {
     }
 }
 
-struct Entry {
+// A position from the dataset, decoded from its record as it's needed
+struct Entry<'a> {
     outcome: Outcome,
-    coefficients: Vec<NonZeroCoefficient>,
+
+    // The packed (parameter index, value) pairs from the record
+    coefficients: &'a [u8],
+
+    // The search score for the position, from white's perspective, if the dataset has one
+    score: Option<f32>,
+
+    // The evaluation of the position using the engine's current parameters, used to fit K
+    static_eval: f32,
 
     midgame_percentage: f32,
     endgame_percentage: f32,
 }
 
+impl Entry<'_> {
+    fn coefficients(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.coefficients.chunks_exact(4).map(|c| {
+            (
+                usize::from(u16::from_le_bytes([c[0], c[1]])),
+                f32::from(i16::from_le_bytes([c[2], c[3]])),
+            )
+        })
+    }
+}
+
 fn start_progress_bar(size: usize, label: &str) -> ProgressBar {
     let p = ProgressBar::new(size as u64);
     p.set_prefix(label.to_owned());
@@ -62,73 +110,17 @@ fn start_progress_bar(size: usize, label: &str) -> ProgressBar {
     p
 }
 
-fn load_entries_from_file(path: &Path) -> Vec<Entry> {
-    let file_contents = std::fs::read_to_string(path).expect("Unable to read file");
-    let lines = file_contents.lines().collect::<Vec<&str>>();
-
-    let number_of_positions = lines.len();
-
-    let parsing_progress = start_progress_bar(number_of_positions, "Loading positions");
-    let mut parse_results: Vec<(Game, Outcome)> = Vec::new();
-
-    for (i, line) in lines.iter().enumerate() {
-        let (fen_str, outcome_str) = line.split_once('[').expect("Unexpected file format");
-        let fen_str = fen_str.trim();
-        let outcome_str = outcome_str.trim().replace(']', "");
-
-        let game = Game::from_fen(fen_str).expect("Unexpected fen");
-
-        let outcome = match outcome_str.as_str() {
-            "1.0" => Outcome::Win,
-            "0.5" => Outcome::Draw,
-            "0.0" => Outcome::Loss,
-            _ => panic!("Unexpected outcome format"),
-        };
-
-        parse_results.push((game, outcome));
-
-        if i % 1000 == 0 {
-            parsing_progress.set_position(i as u64);
-        }
-    }
-
-    parsing_progress.finish();
-
-    let coefficients_progress = start_progress_bar(number_of_positions, "Calculating coefficients");
-    let mut entries: Vec<Entry> = Vec::new();
-
-    for (i, (game, outcome)) in parse_results.into_iter().enumerate() {
-        let mut trace = Trace::new();
-        absolute_eval_with_trace::<true>(&game, &mut trace);
-        let coefficients = trace.non_zero_coefficients();
-
-        let midgame_percentage =
-            f32::from(game.incremental_eval.phase_value) / f32::from(tuner_eval::PHASE_COUNT_MAX);
-        let endgame_percentage = 1.0 - midgame_percentage;
-
-        entries.push(Entry {
-            outcome,
-            coefficients,
-
-            midgame_percentage,
-            endgame_percentage,
-        });
-
-        if i % 1000 == 0 {
-            coefficients_progress.set_position(i as u64);
-        }
-    }
-
-    coefficients_progress.finish();
-
-    entries
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
 }
 
-fn evaluate(entry: &Entry, parameters: &[TunerEval]) -> f32 {
+fn evaluate(entry: &Entry<'_>, parameters: &[TunerEval]) -> f32 {
     let mut s = TunerEval::ZERO;
 
-    for coefficient in &entry.coefficients {
-        s += parameters[coefficient.idx] * coefficient.value;
+    for (idx, value) in entry.coefficients() {
+        s += parameters[idx] * value;
     }
 
     s.midgame().mul_add(
@@ -141,10 +133,81 @@ fn sigmoid(x: f32) -> f32 {
     1.0 / (1.0 + f32::exp(-x))
 }
 
+// The value we're trying to predict for an entry: the outcome of the game, blended with the
+// search score for the position (as a win probability) if we have one.
+fn target(entry: &Entry<'_>, k: f32, lambda: f32) -> f32 {
+    let outcome = entry.outcome.numeric_outcome();
+
+    match entry.score {
+        Some(score) => lambda.mul_add(sigmoid(k * score / 400.0) - outcome, outcome),
+        None => outcome,
+    }
+}
+
+#[expect(clippy::cast_precision_loss, reason = "Known imprecise calculations")]
+fn loss(
+    dataset: &Dataset,
+    records: &[usize],
+    parameters: &[TunerEval; Trace::SIZE],
+    k: f32,
+    lambda: f32,
+) -> f32 {
+    let total_error: f32 = records
+        .par_iter()
+        .map(|&record| {
+            let entry = &dataset.entry(record);
+            let error = target(entry, k, lambda) - sigmoid(k * evaluate(entry, parameters) / 400.0);
+            error * error
+        })
+        .sum();
+
+    total_error / records.len() as f32
+}
+
+// Find the scaling constant K which best maps the engine's current evaluations onto the game
+// outcomes. We search a coarse range of values first, then repeatedly narrow in on the best one.
+#[expect(clippy::cast_precision_loss, reason = "Known imprecise calculations")]
+fn fit_k(dataset: &Dataset, records: &[usize]) -> f32 {
+    let static_eval_loss = |k: f32| -> f32 {
+        let total_error: f32 = records
+            .par_iter()
+            .map(|&record| {
+                let entry = dataset.entry(record);
+                let error =
+                    entry.outcome.numeric_outcome() - sigmoid(k * entry.static_eval / 400.0);
+                error * error
+            })
+            .sum();
+
+        total_error / records.len() as f32
+    };
+
+    let mut best_k = 1.0;
+    let mut step = 1.0;
+
+    for _ in 0..K_FITTING_ITERATIONS {
+        let candidates = (-10..=10)
+            .map(|i| best_k + step * i as f32)
+            .filter(|&k| k > 0.0);
+
+        best_k = candidates
+            .map(|k| (k, static_eval_loss(k)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(k, _)| k)
+            .unwrap();
+
+        step /= 10.0;
+    }
+
+    best_k
+}
+
 fn calculate_gradient(
-    entries: &[Entry],
+    dataset: &Dataset,
+    records: &[usize],
     parameters: &[TunerEval; Trace::SIZE],
     k: f32,
+    lambda: f32,
 ) -> [TunerEval; Trace::SIZE] {
     // Break the entries into chunks, aiming for as many chunks as we have CPUs.
     //
@@ -152,23 +215,25 @@ fn calculate_gradient(
     // due to Rayon's default binary-tree-of-tasks strategy. Each leaf gets its own array, which means
     // we have to allocate a huge number of trace-sized arrays.
     // With this approach, we allocate only a single array per chunk, and sum them at the end.
-    let entry_chunks = entries.chunks(entries.len() / 10).collect::<Vec<_>>();
+    let record_chunks = records
+        .chunks((records.len() / 10).max(1))
+        .collect::<Vec<_>>();
 
-    entry_chunks
+    record_chunks
         .par_iter()
-        .map(|&entries| {
+        .map(|&records| {
             let mut gradient = [TunerEval::ZERO; Trace::SIZE];
 
-            for entry in entries {
+            for &record in records {
+                let entry = &dataset.entry(record);
                 let eval = evaluate(entry, parameters);
                 let sigmoid = sigmoid(k * eval / 400.0);
-                let result =
-                    (entry.outcome.numeric_outcome() - sigmoid) * sigmoid * (1.0 - sigmoid);
+                let result = (target(entry, k, lambda) - sigmoid) * sigmoid * (1.0 - sigmoid);
 
-                for coefficient in &entry.coefficients {
-                    gradient[coefficient.idx] += TunerEval::new(
-                        entry.midgame_percentage * coefficient.value,
-                        entry.endgame_percentage * coefficient.value,
+                for (idx, value) in entry.coefficients() {
+                    gradient[idx] += TunerEval::new(
+                        entry.midgame_percentage * value,
+                        entry.endgame_percentage * value,
                     ) * result;
                 }
             }
@@ -188,33 +253,66 @@ fn calculate_gradient(
 }
 
 #[expect(clippy::cast_precision_loss, reason = "Known imprecise calculations")]
-pub fn tune(path: &Path, epochs: usize) {
+#[expect(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "The validation split is clamped to [0, 1]"
+)]
+pub fn tune(path: &Path, options: &TuneOptions) {
     rayon::ThreadPoolBuilder::new()
         .stack_size(5_000_000)
         .build_global()
         .unwrap();
 
-    let entries = load_entries_from_file(path);
+    let resumed_from = options
+        .resume
+        .then(|| Checkpoint::load(&options.checkpoint).expect("Unable to load checkpoint"));
 
-    // TODO: Using the same k as was determined by texel-tuner until we compute it here.
-    let k = 2.5;
+    let dataset = Dataset::load(path);
+    let mut records = dataset.records();
+
+    // Hold out a random subset of the positions, so we can check that we're not overfitting
+    let seed = resumed_from
+        .as_ref()
+        .map_or_else(|| rand::thread_rng().gen(), |c| c.seed);
+    records.shuffle(&mut StdRng::seed_from_u64(seed));
+    let validation_size =
+        (records.len() as f32 * options.validation_split.clamp(0.0, 1.0)) as usize;
+    let validation_records = records.split_off(records.len() - validation_size);
+
+    let lambda = options.lambda;
 
     let learning_rate = 1.0;
     let beta1 = 0.9;
     let beta2 = 0.999;
 
-    let mut parameters: [TunerEval; Trace::SIZE] = [TunerEval::ZERO; Trace::SIZE];
-    let mut momentum: [TunerEval; Trace::SIZE] = [TunerEval::ZERO; Trace::SIZE];
-    let mut velocities: [TunerEval; Trace::SIZE] = [TunerEval::ZERO; Trace::SIZE];
-
-    let epoch_progress = start_progress_bar(epochs, "Running epochs");
-
-    for epoch in 0..epochs {
-        let gradient = calculate_gradient(&entries, &parameters, k);
+    let Checkpoint {
+        epoch: start_epoch,
+        seed,
+        k,
+        mut parameters,
+        mut momentum,
+        mut velocities,
+    } = resumed_from.unwrap_or_else(|| Checkpoint {
+        epoch: 0,
+        seed,
+        k: fit_k(&dataset, &records),
+        parameters: [TunerEval::ZERO; Trace::SIZE],
+        momentum: [TunerEval::ZERO; Trace::SIZE],
+        velocities: [TunerEval::ZERO; Trace::SIZE],
+    });
+
+    println!("Using K = {k:.4}");
+
+    let epoch_progress = start_progress_bar(options.epochs, "Running epochs");
+    epoch_progress.set_position(start_epoch as u64);
+
+    for epoch in start_epoch..options.epochs {
+        let gradient = calculate_gradient(&dataset, &records, &parameters, k, lambda);
 
         for param in 0..Trace::SIZE {
             let grad = TunerEval::v(-k) / TunerEval::v(400.0) * gradient[param]
-                / TunerEval::v(entries.len() as f32);
+                / TunerEval::v(records.len() as f32);
             momentum[param] = momentum[param] * beta1 + grad * (1.0 - beta1);
             velocities[param] = velocities[param] * beta2 + (grad * grad) * (1.0 - beta2);
 
@@ -222,10 +320,45 @@ pub fn tune(path: &Path, epochs: usize) {
                 momentum[param] * learning_rate / (TunerEval::v(1e-8) + velocities[param].sqrt());
         }
 
+        let train_loss = loss(&dataset, &records, &parameters, k, lambda);
+
+        let report = if validation_records.is_empty() {
+            format!("Epoch {}: train loss {train_loss:.6}", epoch + 1)
+        } else {
+            let validation_loss = loss(&dataset, &validation_records, &parameters, k, lambda);
+            format!(
+                "Epoch {}: train loss {train_loss:.6}, validation loss {validation_loss:.6}",
+                epoch + 1
+            )
+        };
+
+        epoch_progress.suspend(|| println!("{report}"));
+
         epoch_progress.set_position((epoch + 1) as u64);
+
+        if (epoch + 1) % options.checkpoint_every.max(1) == 0 || epoch + 1 == options.epochs {
+            let checkpoint = Checkpoint {
+                epoch: epoch + 1,
+                seed,
+                k,
+                parameters,
+                momentum,
+                velocities,
+            };
+
+            checkpoint
+                .save(&options.checkpoint)
+                .expect("Unable to save checkpoint");
+        }
     }
 
     let mut parameters = Parameters::from_array(&parameters);
     parameters.rebalance();
-    println!("{}", &parameters);
+
+    match &options.output {
+        Some(output) => parameters
+            .write_params_file(output)
+            .expect("Unable to write parameters"),
+        None => println!("{}", &parameters),
+    }
 }
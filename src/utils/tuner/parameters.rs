@@ -2,6 +2,50 @@ use crate::chess::bitboard::{bitboards, Bitboard};
 use crate::chess::piece::PieceKind;
 use crate::chess::square::{File, Rank, Square};
 use crate::engine::eval::{Parameters, PhasedEval};
+use std::path::Path;
+
+const PARAMS_FILE: &str = include_str!("../../engine/eval/params.rs");
+
+// Everything in params.rs which comes before the parameters themselves
+fn params_file_header() -> &'static str {
+    let parameters_start = PARAMS_FILE
+        .match_indices("\npub const ")
+        .map(|(idx, _)| idx + 1)
+        .find(|&idx| !PARAMS_FILE[idx..].starts_with("pub const fn"))
+        .expect("params.rs should define some parameters");
+
+    &PARAMS_FILE[..parameters_start]
+}
+
+// Converts a parameter as it's defined in params.rs into the layout used by `Parameters`
+#[cfg(test)]
+pub trait ToParameters<const N: usize> {
+    fn to_parameters(&self) -> [PhasedEval; N];
+}
+
+#[cfg(test)]
+impl<const N: usize> ToParameters<N> for [PhasedEval; N] {
+    fn to_parameters(&self) -> [PhasedEval; N] {
+        *self
+    }
+}
+
+#[cfg(test)]
+impl ToParameters<1> for PhasedEval {
+    fn to_parameters(&self) -> [PhasedEval; 1] {
+        [*self]
+    }
+}
+
+#[cfg(test)]
+impl ToParameters<{ Square::N }> for [[PhasedEval; File::N]; Rank::N] {
+    fn to_parameters(&self) -> [PhasedEval; Square::N] {
+        use crate::engine::eval::piece_square_tables::{flatten, flip};
+
+        // The definitions are written with the 8th rank first
+        flatten(flip(*self))
+    }
+}
 
 pub fn print_param(f: &mut std::fmt::Formatter<'_>, p: PhasedEval) -> std::fmt::Result {
     let (mg, eg) = (p.midgame().0, p.endgame().0);
@@ -105,6 +149,15 @@ impl Parameters {
         }
     }
 
+    // Write the parameters out as a replacement for engine/eval/params.rs
+    pub fn write_params_file(&self, path: &Path) -> std::io::Result<()> {
+        let parameters = self.to_string();
+        std::fs::write(
+            path,
+            format!("{}{}\n", params_file_header(), parameters.trim_end()),
+        )
+    }
+
     pub fn rebalance(&mut self) {
         Self::rebalance_pst(
             &mut self.pawn_pst,
@@ -138,3 +191,28 @@ impl Parameters {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_file_header() {
+        let header = params_file_header();
+
+        assert!(header.starts_with("#![cfg_attr(any(), rustfmt::skip)]"));
+        assert!(header.contains("pub const fn s("));
+        assert!(header.ends_with("= [[PhasedEval; File::N]; Rank::N];\n\n"));
+    }
+
+    #[test]
+    fn test_writing_current_parameters_reproduces_params_file() {
+        let path = std::env::temp_dir().join(format!("params-{}.rs", std::process::id()));
+
+        Parameters::current().write_params_file(&path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, PARAMS_FILE);
+    }
+}